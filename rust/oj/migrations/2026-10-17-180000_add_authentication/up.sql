@@ -0,0 +1,10 @@
+ALTER TABLE users ADD COLUMN password_hash TEXT;
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'Contestant' CHECK(role IN ('Admin', 'Contestant'));
+UPDATE users SET role = 'Admin' WHERE id = 0;
+
+CREATE TABLE sessions (
+    token TEXT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    expire_time TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE subtask_results (
+    job_id INT NOT NULL,
+    id INT NOT NULL,
+    score DOUBLE NOT NULL,
+    max_score DOUBLE NOT NULL,
+    PRIMARY KEY (job_id, id),
+    FOREIGN KEY (job_id) REFERENCES jobs(id)
+);
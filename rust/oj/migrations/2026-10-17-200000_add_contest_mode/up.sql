@@ -0,0 +1 @@
+ALTER TABLE contests ADD COLUMN mode TEXT NOT NULL DEFAULT 'IOI' CHECK(mode IN ('IOI', 'ICPC'));
@@ -0,0 +1,2 @@
+DROP TABLE contest_reveals;
+ALTER TABLE contests DROP COLUMN freeze_time;
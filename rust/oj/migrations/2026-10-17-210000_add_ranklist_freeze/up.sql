@@ -0,0 +1,10 @@
+ALTER TABLE contests ADD COLUMN freeze_time TIMESTAMP;
+
+CREATE TABLE contest_reveals (
+    contest_id INT NOT NULL,
+    user_id INT NOT NULL,
+    problem_id INT NOT NULL,
+    PRIMARY KEY (contest_id, user_id, problem_id),
+    FOREIGN KEY (contest_id) REFERENCES contests(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,18 @@
+CREATE TABLE rank_results (
+    contest_id INT NOT NULL,
+    user_id INT NOT NULL,
+    problem_id INT NOT NULL,
+    submission_count INT NOT NULL,
+    latest_job_id INT NOT NULL,
+    latest_score DOUBLE NOT NULL,
+    highest_job_id INT NOT NULL,
+    highest_score DOUBLE NOT NULL,
+    accepted_job_id INT,
+    accepted_score DOUBLE,
+    first_accepted_job_id INT,
+    first_accepted_time TIMESTAMP,
+    rejected_count INT NOT NULL,
+    PRIMARY KEY (contest_id, user_id, problem_id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+    -- contest_id is not foreign key because it can be zero
+);
@@ -0,0 +1,8 @@
+-- Existing sessions are dropped, since their tokens can't be recovered from the hashes.
+DROP TABLE sessions;
+CREATE TABLE sessions (
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    expire_time TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1 @@
+ALTER TABLE contest_reveals DROP COLUMN reveal_time;
@@ -0,0 +1,3 @@
+-- Submissions are only revealed up to the reveal time, and the existing pairs are revealed up to now.
+ALTER TABLE contest_reveals ADD COLUMN reveal_time TIMESTAMP NOT NULL DEFAULT '1970-01-01 00:00:00';
+UPDATE contest_reveals SET reveal_time = strftime('%Y-%m-%d %H:%M:%f', 'now');
@@ -0,0 +1,8 @@
+DROP TABLE case_results;
+DROP TABLE jobs;
+DROP TABLE contest_users;
+DROP TABLE contest_problems;
+DROP TABLE contests;
+DROP TABLE users;
+DROP TYPE job_result;
+DROP TYPE job_state;
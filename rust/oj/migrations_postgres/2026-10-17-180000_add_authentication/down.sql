@@ -0,0 +1,5 @@
+DROP TABLE sessions;
+ALTER TABLE users DROP COLUMN role;
+ALTER TABLE users DROP COLUMN password_hash;
+
+DROP TYPE user_role;
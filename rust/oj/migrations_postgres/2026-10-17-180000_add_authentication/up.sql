@@ -0,0 +1,12 @@
+CREATE TYPE user_role AS ENUM ('Admin', 'Contestant');
+
+ALTER TABLE users ADD COLUMN password_hash TEXT;
+ALTER TABLE users ADD COLUMN role user_role NOT NULL DEFAULT 'Contestant';
+UPDATE users SET role = 'Admin' WHERE id = 0;
+
+CREATE TABLE sessions (
+    token TEXT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    expire_time TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1 @@
+DROP TABLE subtask_results;
@@ -0,0 +1,3 @@
+ALTER TABLE contests DROP COLUMN mode;
+
+DROP TYPE contest_mode;
@@ -0,0 +1,3 @@
+CREATE TYPE contest_mode AS ENUM ('IOI', 'ICPC');
+
+ALTER TABLE contests ADD COLUMN mode contest_mode NOT NULL DEFAULT 'IOI';
@@ -0,0 +1,7 @@
+DROP TABLE sessions;
+CREATE TABLE sessions (
+    token TEXT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    expire_time TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
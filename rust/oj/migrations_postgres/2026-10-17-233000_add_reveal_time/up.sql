@@ -0,0 +1,3 @@
+-- Submissions are only revealed up to the reveal time, and the existing pairs are revealed up to now.
+ALTER TABLE contest_reveals ADD COLUMN reveal_time TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC');
+ALTER TABLE contest_reveals ALTER COLUMN reveal_time DROP DEFAULT;
@@ -0,0 +1,206 @@
+//! Authenticate users and check their permissions.
+//!
+//! Authentication is enabled by `server.authentication` in the config. When it's disabled, every
+//! request is permitted, as if it's sent by an admin.
+
+use crate::config::ServerConfig;
+use crate::db::connection::ConnectionPool;
+use crate::db::enums::UserRole;
+use crate::db::{sessions, users};
+use crate::error::*;
+use actix_web::{http::header, web, FromRequest, HttpRequest};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use blake2::{Blake2s256, Digest};
+use chrono::{Duration, Utc};
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+
+/// How long a session lasts after logging in.
+const SESSION_DURATION_DAYS: i64 = 7;
+
+/// The environment variable to set the password of the root user (user 0) on startup.
+const ROOT_PASSWORD_ENV: &str = "OJ_ROOT_PASSWORD";
+
+/// Hash a password with a random salt.
+pub fn hash_password(password: &str) -> ApiResult<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|error| {
+            ApiError::new(
+                ApiErrorType::Internal,
+                format!("failed to hash password: {}", error),
+            )
+        })
+}
+
+/// Check a password against a hash returned by `hash_password`.
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Hash a session token to store it, so that a leaked database doesn't leak valid tokens.
+///
+/// The tokens are random, so a fast hash without salt is enough.
+pub fn hash_token(token: &str) -> String {
+    Blake2s256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The API response of a successful login.
+#[derive(Serialize)]
+pub struct Session {
+    token: String,
+    user_id: i32,
+    role: UserRole,
+    expire_time: String,
+}
+
+/// Check the password and create a new session.
+pub fn login(name: &str, password: &str, pool: &ConnectionPool) -> ApiResult<Session> {
+    let user = users::get_user_info(name, pool)?
+        .filter(|user| {
+            user.password_hash
+                .as_ref()
+                .is_some_and(|hash| verify_password(password, hash))
+        })
+        .ok_or_else(|| {
+            ApiError::new(
+                ApiErrorType::Unauthorized,
+                String::from("Wrong user name or password."),
+            )
+        })?;
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    let expire_time = Utc::now().naive_utc() + Duration::days(SESSION_DURATION_DAYS);
+    sessions::add_session(&hash_token(&token), user.id, expire_time, pool)?;
+    Ok(Session {
+        token,
+        user_id: user.id,
+        role: user.role,
+        expire_time: expire_time.format(crate::TIME_FORMAT).to_string(),
+    })
+}
+
+/// Set the password of the root user from the environment variable if it's set.
+pub fn init_root_password(pool: &ConnectionPool) -> ApiResult<()> {
+    if let Ok(password) = std::env::var(ROOT_PASSWORD_ENV) {
+        users::set_password_hash(0, &hash_password(&password)?, pool)?;
+        log::info!("Root password set from {}", ROOT_PASSWORD_ENV);
+    }
+    Ok(())
+}
+
+/// Get the token in the `Authorization: Bearer` header.
+pub fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// The identity of the sender of a request, extracted from the `Authorization: Bearer` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Identity {
+    /// Authentication is disabled, so everything is permitted.
+    Disabled,
+    /// Not logged in.
+    Anonymous,
+    User {
+        id: i32,
+        role: UserRole,
+    },
+}
+
+impl Identity {
+    fn forbidden() -> ApiError {
+        ApiError::new(ApiErrorType::Forbidden, String::from("Permission denied."))
+    }
+
+    /// Returns whether the sender is permitted to do everything.
+    pub fn is_admin(&self) -> bool {
+        matches!(
+            self,
+            Identity::Disabled
+                | Identity::User {
+                    role: UserRole::Admin,
+                    ..
+                }
+        )
+    }
+
+    /// Errors if not logged in. Returns the user id, or `None` if authentication is disabled.
+    pub fn require_login(&self) -> ApiResult<Option<i32>> {
+        match self {
+            Identity::Disabled => Ok(None),
+            Identity::Anonymous => Err(ApiError::new(
+                ApiErrorType::Unauthorized,
+                String::from("Login required."),
+            )),
+            Identity::User { id, .. } => Ok(Some(*id)),
+        }
+    }
+
+    /// Errors if the sender is not an admin.
+    pub fn require_admin(&self) -> ApiResult<()> {
+        self.require_login()?;
+        if self.is_admin() {
+            Ok(())
+        } else {
+            Err(Self::forbidden())
+        }
+    }
+
+    /// Errors if the sender is neither the given user nor an admin.
+    pub fn require_user(&self, id: i32) -> ApiResult<()> {
+        match self.require_login()? {
+            Some(user_id) if user_id != id && !self.is_admin() => Err(Self::forbidden()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromRequest for Identity {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = ApiResult<Self>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let enabled = req
+            .app_data::<web::Data<ServerConfig>>()
+            .is_some_and(|config| config.authentication);
+        let token = bearer_token(req);
+        let pool = req.app_data::<web::Data<ConnectionPool>>().cloned();
+        Box::pin(async move {
+            if !enabled {
+                return Ok(Identity::Disabled);
+            }
+            let (token, pool) = match (token, pool) {
+                (Some(token), Some(pool)) => (token, pool),
+                _ => return Ok(Identity::Anonymous),
+            };
+            let token_hash = hash_token(&token);
+            match web::block(move || sessions::get_session_user(&token_hash, &pool)).await?? {
+                None => Err(ApiError::new(
+                    ApiErrorType::Unauthorized,
+                    String::from("Invalid or expired token."),
+                )),
+                Some((id, role)) => Ok(Identity::User { id, role }),
+            }
+        })
+    }
+}
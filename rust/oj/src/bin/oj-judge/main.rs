@@ -0,0 +1,87 @@
+//! A remote judge node, which pulls jobs from an OJ server and judges them locally.
+//!
+//! The node reads problems and languages from its own config file, which should be the same as
+//! the one of the server, with the paths pointing to the test data on this machine. The
+//! `oj-runner` binary should be in the same directory as this binary.
+
+use clap::Parser;
+use oj::config::{Config, SharedConfig};
+use oj::judger::remote::{self, NodeOptions};
+use std::path::PathBuf;
+
+/// The environment variable to set the token of judge nodes, `server.node_token` in the config of
+/// the server.
+const NODE_TOKEN_ENV: &str = "OJ_NODE_TOKEN";
+
+/// The command-line options.
+#[derive(Parser)]
+#[clap(about = "Run a remote judge node for the OJ server")]
+struct Cli {
+    /// Set path to the config file
+    #[clap(short, long)]
+    config: String,
+
+    /// The base URL of the server
+    #[clap(short, long, default_value = "http://127.0.0.1:12345")]
+    server: String,
+
+    /// The name of the node shown in the judger status [default: oj-judge-<pid>]
+    #[clap(short, long)]
+    name: Option<String>,
+
+    /// The number of workers [default: half the number of CPUs]
+    #[clap(short, long)]
+    workers: Option<usize>,
+
+    /// The directory to create temporary files in
+    #[clap(long, default_value = "tmp/oj-judge")]
+    tmp_dir: PathBuf,
+}
+
+#[actix_web::main]
+async fn main() {
+    // load env from the `.env` file, but it's ok if this file is missing
+    dotenvy::dotenv().ok();
+
+    env_logger::Builder::from_env(env_logger::Env::new().default_filter_or("info"))
+        .format_timestamp_micros()
+        .init();
+
+    let args = Cli::parse();
+
+    let token = std::env::var(NODE_TOKEN_ENV).unwrap_or_else(|_| {
+        eprintln!(
+            "Error: the environment variable {} is not set",
+            NODE_TOKEN_ENV
+        );
+        std::process::exit(1);
+    });
+
+    let config = std::fs::read_to_string(&args.config)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Config::new(&json))
+        .unwrap_or_else(|err| {
+            eprintln!("Error: {:?}", err);
+            std::process::exit(1);
+        });
+
+    if args.workers == Some(0) {
+        eprintln!("Error: the number of workers should be positive");
+        std::process::exit(1);
+    }
+
+    let options = NodeOptions {
+        server: args.server,
+        token,
+        name: args
+            .name
+            .unwrap_or_else(|| format!("oj-judge-{}", std::process::id())),
+        workers: args.workers.unwrap_or_else(|| (num_cpus::get() / 2).max(1)),
+        tmp_dir: args.tmp_dir,
+    };
+
+    if let Err(err) = remote::run_node(SharedConfig::new(config, &args.config), options).await {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,287 @@
+//! Limit the program and all its children with a cgroup.
+//!
+//! The cgroup is created in the cgroup of the runner, which must be writable. With cgroup v2, the
+//! controllers must be enabled in the `cgroup.subtree_control` of the runner's cgroup, e.g. by
+//! delegating it to the user of the server. With cgroup v1, a cgroup is created in the hierarchy
+//! of each controller. If neither is available, the runner falls back to rlimits.
+
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Where the cgroup file systems are mounted.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// How many times to check whether all processes in the cgroup are killed before removing it.
+const KILL_RETRIES: usize = 100;
+const KILL_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The limits enforced by the cgroup, `None` for no limit.
+pub struct Limits {
+    /// The memory usage in bytes.
+    pub memory: Option<usize>,
+    /// The number of processes and threads.
+    pub processes: Option<usize>,
+}
+
+/// A cgroup controller and the files to write its limit to in cgroup v2 and v1.
+struct Controller {
+    name: &'static str,
+    v2_file: &'static str,
+    v1_file: &'static str,
+}
+
+const MEMORY: Controller = Controller {
+    name: "memory",
+    v2_file: "memory.max",
+    v1_file: "memory.limit_in_bytes",
+};
+
+const PIDS: Controller = Controller {
+    name: "pids",
+    v2_file: "pids.max",
+    v1_file: "pids.max",
+};
+
+/// A cgroup in one hierarchy.
+struct Directory {
+    /// The parent directory and the directory itself are kept open, so that they are still
+    /// accessible after the sandbox hides or remounts the file system.
+    parent: File,
+    name: CString,
+    dir: File,
+    /// `cgroup.procs` opened for writing, used by the program to join the cgroup.
+    procs: File,
+    v2: bool,
+    /// The controllers whose limits are set in this directory.
+    controllers: Vec<&'static str>,
+}
+
+/// A cgroup created for the program, which is removed when dropped.
+pub struct Cgroup {
+    dirs: Vec<Directory>,
+}
+
+impl Cgroup {
+    /// Create a cgroup that enforces the limits on the program and its children with the
+    /// available controllers.
+    ///
+    /// Returns `None` if no limit can be enforced by cgroups.
+    pub fn create(limits: &Limits) -> Option<Cgroup> {
+        let settings = [(&MEMORY, limits.memory), (&PIDS, limits.processes)]
+            .into_iter()
+            .filter_map(|(controller, limit)| Some((controller, limit?)))
+            .collect::<Vec<_>>();
+        let name = format!("oj-runner-{}", std::process::id());
+        let controllers = settings
+            .iter()
+            .map(|(controller, _)| controller.name)
+            .collect::<Vec<_>>();
+        let mut dirs = Vec::new();
+        for (path, v2, controllers) in hierarchies(&controllers) {
+            let mut dir = match Directory::create(&path, &name, v2) {
+                Ok(dir) => dir,
+                Err(_) => continue,
+            };
+            for (controller, limit) in &settings {
+                let file = if v2 {
+                    controller.v2_file
+                } else {
+                    controller.v1_file
+                };
+                if controllers.contains(&controller.name)
+                    && open_at(&dir.dir, file, true)
+                        .and_then(|mut file| file.write_all(limit.to_string().as_bytes()))
+                        .is_ok()
+                {
+                    dir.controllers.push(controller.name);
+                }
+            }
+            dirs.push(dir);
+        }
+        let cgroup = Cgroup { dirs };
+        (!cgroup.dirs.iter().all(|dir| dir.controllers.is_empty())).then_some(cgroup)
+    }
+
+    /// Whether the memory usage is limited by the cgroup.
+    pub fn limits_memory(&self) -> bool {
+        self.dir(MEMORY.name).is_some()
+    }
+
+    /// Whether the number of processes is limited by the cgroup.
+    pub fn limits_processes(&self) -> bool {
+        self.dir(PIDS.name).is_some()
+    }
+
+    /// Move the calling process into the cgroup. Called in the forked program before `exec`.
+    pub fn enter(&self) -> io::Result<()> {
+        for dir in &self.dirs {
+            // Writing 0 moves the writing process, even in another PID namespace.
+            (&dir.procs).write_all(b"0")?;
+        }
+        Ok(())
+    }
+
+    /// Whether any process in the cgroup has been killed for exceeding the memory limit.
+    pub fn oom_killed(&self) -> bool {
+        let dir = match self.dir(MEMORY.name) {
+            None => return false,
+            Some(dir) => dir,
+        };
+        let events_file = if dir.v2 {
+            "memory.events"
+        } else {
+            "memory.oom_control"
+        };
+        read_at(&dir.dir, events_file).is_ok_and(|events| {
+            events
+                .lines()
+                .filter_map(|line| line.strip_prefix("oom_kill "))
+                .any(|count| count.trim().parse::<u64>().is_ok_and(|count| count > 0))
+        })
+    }
+
+    fn dir(&self, controller: &str) -> Option<&Directory> {
+        self.dirs
+            .iter()
+            .find(|dir| dir.controllers.contains(&controller))
+    }
+}
+
+impl Drop for Cgroup {
+    /// Kill the remaining processes, e.g. those forked by the program, and remove the cgroup.
+    fn drop(&mut self) {
+        for dir in &self.dirs {
+            for _ in 0..KILL_RETRIES {
+                let pids = read_at(&dir.dir, "cgroup.procs")
+                    .map(|procs| {
+                        procs
+                            .lines()
+                            .filter_map(|pid| pid.parse::<libc::pid_t>().ok())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                if pids.is_empty() {
+                    break;
+                }
+                for pid in pids {
+                    unsafe { libc::kill(pid, libc::SIGKILL) };
+                }
+                thread::sleep(KILL_RETRY_INTERVAL);
+            }
+            unsafe {
+                libc::unlinkat(
+                    dir.parent.as_raw_fd(),
+                    dir.name.as_ptr(),
+                    libc::AT_REMOVEDIR,
+                )
+            };
+        }
+    }
+}
+
+impl Directory {
+    fn create(parent_path: &Path, name: &str, v2: bool) -> io::Result<Directory> {
+        let path = parent_path.join(name);
+        fs::create_dir(&path)?;
+        let open = || -> io::Result<Directory> {
+            Ok(Directory {
+                parent: File::open(parent_path)?,
+                name: CString::new(name)?,
+                dir: File::open(&path)?,
+                procs: OpenOptions::new()
+                    .write(true)
+                    .open(path.join("cgroup.procs"))?,
+                v2,
+                controllers: Vec::new(),
+            })
+        };
+        open().inspect_err(|_| {
+            fs::remove_dir(&path).ok();
+        })
+    }
+}
+
+/// Find the cgroups of the runner to create the cgroup in, with the controllers available in each
+/// of them. It's a single cgroup in cgroup v2 if any of the controllers is enabled for its
+/// children, otherwise a cgroup in the hierarchy of each available controller in cgroup v1.
+fn hierarchies(controllers: &[&'static str]) -> Vec<(PathBuf, bool, Vec<&'static str>)> {
+    let cgroups = match fs::read_to_string("/proc/self/cgroup") {
+        Ok(cgroups) => cgroups,
+        Err(_) => return Vec::new(),
+    };
+    // Each line is `hierarchy-ID:controller-list:cgroup-path`.
+    let cgroups = cgroups
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            Some((fields.nth(1)?, fields.next()?))
+        })
+        .collect::<Vec<_>>();
+
+    let v2_root = if Path::new(CGROUP_ROOT).join("cgroup.controllers").exists() {
+        PathBuf::from(CGROUP_ROOT)
+    } else {
+        // The hybrid layout of systemd.
+        Path::new(CGROUP_ROOT).join("unified")
+    };
+    let v2 = cgroups
+        .iter()
+        .find(|(names, _)| names.is_empty())
+        .and_then(|(_, path)| {
+            let dir = v2_root.join(path.trim_start_matches('/'));
+            let enabled = fs::read_to_string(dir.join("cgroup.subtree_control")).ok()?;
+            let enabled = controllers
+                .iter()
+                .copied()
+                .filter(|controller| enabled.split_whitespace().any(|name| name == *controller))
+                .collect::<Vec<_>>();
+            (!enabled.is_empty()).then_some((dir, true, enabled))
+        });
+    if let Some(v2) = v2 {
+        return vec![v2];
+    }
+
+    controllers
+        .iter()
+        .filter_map(|controller| {
+            cgroups
+                .iter()
+                .find(|(names, _)| names.split(',').any(|name| name == *controller))
+                .map(|(_, path)| {
+                    Path::new(CGROUP_ROOT)
+                        .join(controller)
+                        .join(path.trim_start_matches('/'))
+                })
+                .filter(|dir| dir.join("cgroup.procs").exists())
+                .map(|dir| (dir, false, vec![*controller]))
+        })
+        .collect()
+}
+
+/// Open a file in a directory, which may be hidden from the current mount namespace.
+fn open_at(dir: &File, name: &str, write: bool) -> io::Result<File> {
+    let name = CString::new(Path::new(name).as_os_str().as_bytes())?;
+    let flags = libc::O_CLOEXEC
+        | if write {
+            libc::O_WRONLY
+        } else {
+            libc::O_RDONLY
+        };
+    let fd = unsafe { libc::openat(dir.as_raw_fd(), name.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn read_at(dir: &File, name: &str) -> io::Result<String> {
+    let mut content = String::new();
+    open_at(dir, name, false)?.read_to_string(&mut content)?;
+    Ok(content)
+}
@@ -0,0 +1,335 @@
+//! Run a single program under resource limits and report its resource usage.
+//!
+//! The judger runs programs through this small binary instead of spawning them directly, because
+//! the `ru_maxrss` of a child also counts the memory of its parent before `exec`, which would make
+//! the memory usage of a solution spawned by the large server process meaningless.
+//!
+//! The program inherits stdin, stdout and stderr. The report written to the report file is the
+//! raw wait status, the peak memory usage in bytes, the CPU time (user + system) in microseconds
+//! and the wall-clock time in microseconds, separated by spaces.
+//!
+//! The memory usage is checked periodically, so a large allocation between two checks is capped by
+//! a cgroup if it's available (see `cgroup.rs`), otherwise by `RLIMIT_DATA`.
+
+mod cgroup;
+mod sandbox;
+
+use cgroup::Cgroup;
+use clap::Parser;
+use sandbox::Sandbox;
+use std::ffi::{CString, OsString};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+/// The interval between two checks of the memory usage of the running program.
+const MEMORY_CHECK_INTERVAL_NANOS: libc::c_long = 5_000_000;
+/// Without a cgroup, the data segment (heap, anonymous mappings and static data) of the program is
+/// capped at this multiple of the memory limit. Allocated but untouched memory is counted, so the
+/// cap is looser than the limit.
+const DATA_LIMIT_RATIO: usize = 2;
+
+/// The command-line options.
+#[derive(Parser)]
+#[clap(about = "Run a program under resource limits for the OJ judger")]
+struct Cli {
+    /// Path to write the report to
+    #[clap(long)]
+    report: PathBuf,
+
+    /// Kill the program when its peak memory usage exceeds this number of bytes
+    #[clap(long)]
+    memory_limit: usize,
+
+    /// Kill the program when its CPU time exceeds this number of microseconds
+    #[clap(long)]
+    cpu_time_limit: u64,
+
+    /// Kill the program when its wall-clock time exceeds this number of microseconds
+    #[clap(long)]
+    wall_time_limit: u64,
+
+    /// Limit the size of files written by the program to this number of bytes
+    #[clap(long)]
+    file_size_limit: Option<u64>,
+
+    /// Run the program in a sandbox
+    #[clap(long)]
+    sandbox: bool,
+
+    /// Hide a directory from the sandboxed program
+    #[clap(long, requires = "sandbox")]
+    hide: Vec<PathBuf>,
+
+    /// The working directory of the sandboxed program, visible even if it's in a hidden directory
+    #[clap(long, requires = "sandbox")]
+    work_dir: Option<PathBuf>,
+
+    /// Make the working directory writable
+    #[clap(long, requires = "work-dir")]
+    writable: bool,
+
+    /// Forbid the sandboxed program to create processes, but not threads
+    #[clap(long, requires = "sandbox")]
+    deny_fork: bool,
+
+    /// The program to run and its arguments
+    #[clap(required = true, multiple_values = true, allow_hyphen_values = true)]
+    command: Vec<OsString>,
+}
+
+fn main() {
+    let args = Cli::parse();
+    if let Err(error) = run(&args) {
+        eprintln!("oj-runner: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Cli) -> io::Result<()> {
+    // Open the report file before the file system is made read-only by the sandbox.
+    let mut report = File::create(&args.report)?;
+    // Create the cgroup before the sandbox hides it.
+    let cgroup = Cgroup::create(&cgroup::Limits {
+        memory: (args.memory_limit != usize::MAX).then_some(args.memory_limit),
+        processes: args.sandbox.then_some(sandbox::PROCESS_LIMIT),
+    });
+
+    let mut filter = Vec::new();
+    if args.sandbox {
+        let work_dir = args
+            .work_dir
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "--work-dir is required"))?;
+        sandbox::enter(&Sandbox {
+            hidden_dirs: &args.hide,
+            work_dir,
+            writable: args.writable,
+        })?;
+        filter = sandbox::seccomp_filter(args.deny_fork)?;
+    }
+
+    let command = args
+        .command
+        .iter()
+        .map(|arg| CString::new(arg.as_bytes()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut argv = command.iter().map(|arg| arg.as_ptr()).collect::<Vec<_>>();
+    argv.push(ptr::null());
+
+    // The program finds out whether the runner is still alive by reading from this pipe.
+    let mut alive_pipe = [0; 2];
+    check(unsafe { libc::pipe2(alive_pipe.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) })?;
+    let [alive_read, alive_write] = alive_pipe;
+
+    // Block SIGCHLD to wait for it with a timeout, see `wait_for_exit`.
+    let mut sigchld = MaybeUninit::<libc::sigset_t>::zeroed();
+    let mut old_mask = MaybeUninit::<libc::sigset_t>::zeroed();
+    unsafe {
+        libc::sigemptyset(sigchld.as_mut_ptr());
+        libc::sigaddset(sigchld.as_mut_ptr(), libc::SIGCHLD);
+    }
+    check(unsafe { libc::sigprocmask(libc::SIG_BLOCK, sigchld.as_ptr(), old_mask.as_mut_ptr()) })?;
+    let sigchld = unsafe { sigchld.assume_init() };
+
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if pid == 0 {
+        // The runner is single-threaded, so it's safe to allocate in the child.
+        let result = (|| -> io::Result<()> {
+            unsafe {
+                libc::close(alive_write);
+                check(libc::sigprocmask(
+                    libc::SIG_SETMASK,
+                    old_mask.as_ptr(),
+                    ptr::null_mut(),
+                ))?;
+                // Kill the program if the runner is killed, e.g. on timeout.
+                check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+                let mut buf = [0u8; 1];
+                if libc::read(alive_read, buf.as_mut_ptr() as *mut libc::c_void, 1) == 0 {
+                    return Err(io::Error::other("the runner has exited"));
+                }
+            }
+            // The runner kills the program more precisely, this is only a fallback.
+            let cpu_seconds = Duration::from_micros(args.cpu_time_limit).as_secs();
+            set_rlimit(
+                libc::RLIMIT_CPU,
+                cpu_seconds.saturating_add(1) as libc::rlim_t,
+            )?;
+            if let Some(cgroup) = &cgroup {
+                cgroup.enter()?;
+            }
+            if !cgroup.as_ref().is_some_and(Cgroup::limits_memory) {
+                if let Some(data_limit) = args.memory_limit.checked_mul(DATA_LIMIT_RATIO) {
+                    set_rlimit(libc::RLIMIT_DATA, data_limit as libc::rlim_t)?;
+                }
+            }
+            // The sandbox always limits the size of files.
+            let file_size_limit = args
+                .file_size_limit
+                .into_iter()
+                .chain(args.sandbox.then_some(sandbox::FILE_SIZE_LIMIT))
+                .min();
+            if let Some(file_size_limit) = file_size_limit {
+                set_rlimit(libc::RLIMIT_FSIZE, file_size_limit as libc::rlim_t)?;
+            }
+            if args.sandbox {
+                let limit_processes = !cgroup.as_ref().is_some_and(Cgroup::limits_processes);
+                sandbox::restrict(&filter, limit_processes)?;
+            }
+            unsafe { libc::execvp(argv[0], argv.as_ptr()) };
+            Err(io::Error::last_os_error())
+        })();
+        if let Err(error) = result {
+            eprintln!("oj-runner: failed to execute the program: {}", error);
+        }
+        unsafe { libc::_exit(127) };
+    }
+    let start = Instant::now();
+    unsafe { libc::close(alive_read) };
+
+    let sampled_memory = wait_for_exit(pid, args, start, &sigchld)?;
+    let wall_time = start.elapsed();
+
+    let mut status = 0;
+    let mut usage = MaybeUninit::<libc::rusage>::zeroed();
+    if unsafe { libc::wait4(pid, &mut status, 0, usage.as_mut_ptr()) } != pid {
+        return Err(io::Error::last_os_error());
+    }
+    let usage = unsafe { usage.assume_init() };
+    let mut memory = sampled_memory.max(usage.ru_maxrss as usize * 1024);
+    if let Some(cgroup) = cgroup {
+        // The program is killed by the kernel before its usage is sampled over the limit.
+        if cgroup.oom_killed() {
+            memory = memory.max(args.memory_limit.saturating_add(1));
+        }
+    }
+    let cpu_time = timeval_micros(&usage.ru_utime) + timeval_micros(&usage.ru_stime);
+
+    writeln!(
+        report,
+        "{} {} {} {}",
+        status,
+        memory,
+        cpu_time,
+        wall_time.as_micros()
+    )
+}
+
+/// Wait for the program to exit without reaping it, and kill it if it exceeds any limit.
+///
+/// Returns the peak memory usage sampled while waiting.
+fn wait_for_exit(
+    pid: libc::pid_t,
+    args: &Cli,
+    start: Instant,
+    sigchld: &libc::sigset_t,
+) -> io::Result<usize> {
+    let mut memory = 0;
+    let mut killed = false;
+    loop {
+        memory = memory.max(peak_memory(pid));
+        let exceeded = memory > args.memory_limit
+            || cpu_time(pid) > args.cpu_time_limit
+            || start.elapsed().as_micros() > args.wall_time_limit as u128;
+        if exceeded && !killed {
+            unsafe { libc::kill(pid, libc::SIGKILL) };
+            killed = true;
+        }
+
+        let mut info = MaybeUninit::<libc::siginfo_t>::zeroed();
+        check(unsafe {
+            libc::waitid(
+                libc::P_PID,
+                pid as libc::id_t,
+                info.as_mut_ptr(),
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+            )
+        })?;
+        if unsafe { info.assume_init().si_pid() } == pid {
+            return Ok(memory);
+        }
+
+        // Sleep until the program exits or the interval passes.
+        let timeout = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: MEMORY_CHECK_INTERVAL_NANOS,
+        };
+        if unsafe { libc::sigtimedwait(sigchld, ptr::null_mut(), &timeout) } < 0 {
+            let error = io::Error::last_os_error();
+            if !matches!(error.raw_os_error(), Some(libc::EAGAIN | libc::EINTR)) {
+                return Err(error);
+            }
+        }
+    }
+}
+
+/// Get the peak resident set size in bytes of a running process, or zero if it's not available.
+fn peak_memory(pid: libc::pid_t) -> usize {
+    match fs::read_to_string(format!("/proc/{}/status", pid)) {
+        Err(_) => 0,
+        Ok(status) => status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmHWM:"))
+            .and_then(|kb| {
+                kb.trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+            })
+            .map_or(0, |kb| kb * 1024),
+    }
+}
+
+/// Get the CPU time (user + system) in microseconds of a running process, or zero if it's not
+/// available.
+fn cpu_time(pid: libc::pid_t) -> u64 {
+    let stat = match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Err(_) => return 0,
+        Ok(stat) => stat,
+    };
+    // The fields after the command name, which is in parentheses and may contain spaces, start
+    // from the third field. `utime` and `stime` are the 14th and 15th fields.
+    let ticks = stat
+        .rsplit_once(')')
+        .map(|(_, fields)| {
+            fields
+                .split_whitespace()
+                .skip(11)
+                .take(2)
+                .filter_map(|ticks| ticks.parse::<u64>().ok())
+                .sum::<u64>()
+        })
+        .unwrap_or(0);
+    let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+    ticks.saturating_mul(1_000_000) / ticks_per_second
+}
+
+fn timeval_micros(time: &libc::timeval) -> u64 {
+    time.tv_sec as u64 * 1_000_000 + time.tv_usec as u64
+}
+
+fn set_rlimit(resource: libc::__rlimit_resource_t, limit: libc::rlim_t) -> io::Result<()> {
+    let rlimit = libc::rlimit {
+        rlim_cur: limit,
+        rlim_max: limit,
+    };
+    check(unsafe { libc::setrlimit(resource, &rlimit) })
+}
+
+/// Convert the return value of a libc function to `io::Result`.
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
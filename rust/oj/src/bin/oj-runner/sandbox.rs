@@ -0,0 +1,409 @@
+//! Isolate the program from the rest of the system with Linux namespaces, seccomp and rlimits.
+//!
+//! The runner enters new user, mount, network, IPC, UTS and PID namespaces before forking the
+//! program, so the program has no network and can't see or signal processes outside the sandbox.
+//! In the new mount namespace, hidden directories (e.g. test data) are replaced with empty
+//! directories, `/tmp` is replaced with a private tmpfs, and everything else except the working
+//! directory (if it's writable) is read-only.
+
+use super::{check, set_rlimit};
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// The limit of the size of files written by the sandboxed program, including its output, unless
+/// a smaller limit is given.
+pub const FILE_SIZE_LIMIT: u64 = 1 << 30;
+
+/// The limit of the number of processes and threads of the program, which prevents fork bombs.
+pub const PROCESS_LIMIT: usize = 256;
+
+/// See `linux/securebits.h`.
+const SECBIT_NOROOT: libc::c_ulong = 1 << 0;
+const SECBIT_NOROOT_LOCKED: libc::c_ulong = 1 << 1;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+/// `CLONE_THREAD` in `linux/sched.h`.
+const CLONE_THREAD: u32 = 0x0001_0000;
+
+/// Syscalls that the program is not permitted to use. They fail with `EPERM`.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_kexec_load,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+];
+
+/// The sandbox options.
+pub struct Sandbox<'a> {
+    /// Directories replaced with empty directories.
+    pub hidden_dirs: &'a [PathBuf],
+    /// The working directory of the program, which stays visible even if it's in a hidden
+    /// directory.
+    pub work_dir: &'a Path,
+    /// Whether the working directory is writable.
+    pub writable: bool,
+}
+
+/// Enter the namespaces and set up the file system. Called by the runner before forking.
+pub fn enter(sandbox: &Sandbox) -> io::Result<()> {
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+    check(unsafe {
+        libc::unshare(
+            libc::CLONE_NEWUSER
+                | libc::CLONE_NEWNS
+                | libc::CLONE_NEWNET
+                | libc::CLONE_NEWIPC
+                | libc::CLONE_NEWUTS
+                | libc::CLONE_NEWPID,
+        )
+    })?;
+    fs::write("/proc/self/setgroups", "deny")?;
+    fs::write("/proc/self/uid_map", format!("{} {} 1", uid, uid))?;
+    fs::write("/proc/self/gid_map", format!("{} {} 1", gid, gid))?;
+
+    // Don't propagate mounts back to the host.
+    mount(
+        None,
+        Path::new("/"),
+        None,
+        libc::MS_REC | libc::MS_PRIVATE,
+        None,
+    )?;
+
+    // Keep a reference to the working directory before it might be hidden.
+    let work_dir = fs::File::open(sandbox.work_dir)?;
+
+    // Hide directories. Skip directories in other hidden directories or in `/tmp`, which are
+    // already hidden.
+    let tmp = Path::new("/tmp");
+    let mut hidden_dirs = sandbox.hidden_dirs.to_vec();
+    hidden_dirs.sort();
+    let mut last_hidden: Option<&Path> = None;
+    for dir in &hidden_dirs {
+        if dir.starts_with(tmp) || last_hidden.is_some_and(|last| dir.starts_with(last)) {
+            continue;
+        }
+        mount_tmpfs(dir, "mode=0755")?;
+        last_hidden = Some(dir);
+    }
+    mount_tmpfs(tmp, "mode=1777")?;
+
+    // Bind the working directory back to its path, which also makes it a separate mount.
+    fs::create_dir_all(sandbox.work_dir)?;
+    let work_dir_fd = PathBuf::from(format!(
+        "/proc/self/fd/{}",
+        std::os::unix::io::AsRawFd::as_raw_fd(&work_dir)
+    ));
+    mount(
+        Some(&work_dir_fd),
+        sandbox.work_dir,
+        None,
+        libc::MS_BIND,
+        None,
+    )?;
+
+    // Make every mount read-only except the writable ones.
+    for mount_point in mount_points()? {
+        if mount_point == tmp || (sandbox.writable && mount_point == sandbox.work_dir) {
+            continue;
+        }
+        match remount_readonly(&mount_point) {
+            Err(error)
+                if matches!(
+                    error.raw_os_error(),
+                    Some(libc::ENOENT | libc::EACCES | libc::ENOTDIR | libc::EINVAL)
+                ) =>
+            {
+                // The mount point is hidden by another mount.
+            }
+            result => result?,
+        }
+    }
+
+    std::env::set_current_dir(sandbox.work_dir)
+}
+
+/// Build the seccomp filter. Called by the runner before forking.
+///
+/// With `deny_fork`, `clone` without `CLONE_THREAD` (which `fork` and `posix_spawn` use) also
+/// fails with `EPERM`, and `clone3`, whose flags can't be checked, fails with `ENOSYS` so that
+/// threads are created with `clone` instead.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn seccomp_filter(deny_fork: bool) -> io::Result<Vec<libc::sock_filter>> {
+    let statement = |code: u32, k: u32| libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let jump = |code: u32, k: u32, jt: u8, jf: u8| libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    };
+    let deny = statement(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+    );
+
+    // Offsets in `struct seccomp_data`.
+    const NR_OFFSET: u32 = 0;
+    const ARCH_OFFSET: u32 = 4;
+    // The lower half of the first argument on little-endian architectures.
+    const ARG0_OFFSET: u32 = 16;
+
+    let mut filter = vec![
+        // Kill the program if it uses syscalls of another architecture.
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH_OFFSET),
+        jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            AUDIT_ARCH,
+            1,
+            0,
+        ),
+        statement(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+        statement(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR_OFFSET),
+    ];
+    #[cfg(target_arch = "x86_64")]
+    {
+        // Deny x32 syscalls, which have different numbers.
+        const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+        filter.push(jump(
+            libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+            X32_SYSCALL_BIT,
+            0,
+            1,
+        ));
+        filter.push(deny);
+    }
+    for syscall in DENIED_SYSCALLS {
+        filter.push(jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            *syscall as u32,
+            0,
+            1,
+        ));
+        filter.push(deny);
+    }
+    if deny_fork {
+        #[cfg(target_arch = "x86_64")]
+        for syscall in [libc::SYS_fork, libc::SYS_vfork] {
+            filter.push(jump(
+                libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+                syscall as u32,
+                0,
+                1,
+            ));
+            filter.push(deny);
+        }
+        filter.push(jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            libc::SYS_clone3 as u32,
+            0,
+            1,
+        ));
+        filter.push(statement(
+            libc::BPF_RET | libc::BPF_K,
+            libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32,
+        ));
+        // Skip the check if it's not `clone`. Otherwise deny it unless it has `CLONE_THREAD`, and
+        // load the syscall number back.
+        filter.push(jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            libc::SYS_clone as u32,
+            0,
+            4,
+        ));
+        filter.push(statement(
+            libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+            ARG0_OFFSET,
+        ));
+        filter.push(jump(
+            libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K,
+            CLONE_THREAD,
+            1,
+            0,
+        ));
+        filter.push(deny);
+        filter.push(statement(
+            libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+            NR_OFFSET,
+        ));
+    }
+    filter.push(statement(
+        libc::BPF_RET | libc::BPF_K,
+        libc::SECCOMP_RET_ALLOW,
+    ));
+    Ok(filter)
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn seccomp_filter(_deny_fork: bool) -> io::Result<Vec<libc::sock_filter>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the sandbox is not supported on this architecture",
+    ))
+}
+
+/// Restrict the program. Called in the forked program right before `exec`.
+///
+/// * `limit_processes`: limit the number of processes with `RLIMIT_NPROC`, used if it's not limited
+///   by a cgroup. It counts all processes of the user before Linux 5.14, and doesn't apply to root.
+pub fn restrict(filter: &[libc::sock_filter], limit_processes: bool) -> io::Result<()> {
+    // The program is the init process of the new PID namespace, so mount its own `/proc` in its
+    // own mount namespace without affecting the runner.
+    check(unsafe { libc::unshare(libc::CLONE_NEWNS) })?;
+    mount(
+        Some(Path::new("proc")),
+        Path::new("/proc"),
+        Some("proc"),
+        libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+        None,
+    )?;
+
+    set_rlimit(libc::RLIMIT_CORE, 0)?;
+    if limit_processes {
+        set_rlimit(libc::RLIMIT_NPROC, PROCESS_LIMIT as libc::rlim_t)?;
+    }
+
+    // Don't regain capabilities in the user namespace on `exec` even if the uid is 0.
+    check(unsafe {
+        libc::prctl(
+            libc::PR_SET_SECUREBITS,
+            SECBIT_NOROOT | SECBIT_NOROOT_LOCKED,
+        )
+    })?;
+    check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+    let program = libc::sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_ptr() as *mut libc::sock_filter,
+    };
+    check(unsafe {
+        libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &program as *const libc::sock_fprog,
+        )
+    })
+}
+
+fn c_string(s: &OsStr) -> io::Result<CString> {
+    Ok(CString::new(s.as_bytes())?)
+}
+
+fn mount(
+    source: Option<&Path>,
+    target: &Path,
+    fs_type: Option<&str>,
+    flags: libc::c_ulong,
+    data: Option<&str>,
+) -> io::Result<()> {
+    let source = source.map(|s| c_string(s.as_os_str())).transpose()?;
+    let target = c_string(target.as_os_str())?;
+    let fs_type = fs_type.map(|s| c_string(s.as_ref())).transpose()?;
+    let data = data.map(|s| c_string(s.as_ref())).transpose()?;
+    check(unsafe {
+        libc::mount(
+            source.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            target.as_ptr(),
+            fs_type.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+            flags,
+            data.as_ref()
+                .map_or(ptr::null(), |s| s.as_ptr() as *const libc::c_void),
+        )
+    })
+    .map_err(|error| io::Error::new(error.kind(), format!("mount {:?}: {}", target, error)))
+}
+
+fn mount_tmpfs(target: &Path, data: &str) -> io::Result<()> {
+    mount(
+        Some(Path::new("tmpfs")),
+        target,
+        Some("tmpfs"),
+        libc::MS_NOSUID | libc::MS_NODEV,
+        Some(data),
+    )
+}
+
+/// Remount a mount read-only, keeping the flags that can't be changed in a user namespace.
+fn remount_readonly(mount_point: &Path) -> io::Result<()> {
+    let path = c_string(mount_point.as_os_str())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::zeroed();
+    check(unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) })?;
+    let stat_flags = unsafe { stat.assume_init() }.f_flag;
+    let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+    for (stat_flag, mount_flag) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if stat_flags & stat_flag != 0 {
+            flags |= mount_flag;
+        }
+    }
+    mount(None, mount_point, None, flags, None)
+}
+
+/// Get all mount points in the current mount namespace.
+fn mount_points() -> io::Result<Vec<PathBuf>> {
+    Ok(fs::read_to_string("/proc/self/mountinfo")?
+        .lines()
+        .filter_map(|line| line.split(' ').nth(4))
+        .map(unescape_mount_point)
+        .collect())
+}
+
+/// Unescape the octal escapes such as `\040` in `/proc/self/mountinfo`.
+fn unescape_mount_point(escaped: &str) -> PathBuf {
+    let bytes = escaped.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) if bytes[i] == b'\\' => {
+                result.push(byte);
+                i += 4;
+            }
+            _ => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(OsStr::from_bytes(&result))
+}
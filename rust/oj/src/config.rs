@@ -1,22 +1,58 @@
 //! Get the configuration.
 
 mod deserialize;
+mod managed;
 
+use crate::db::connection::ConnectionPool;
+use crate::db::{contests, jobs};
+use crate::error::{ApiError, ApiErrorType, ApiResult};
 use anyhow::{anyhow, bail, Context, Result};
+use arc_swap::ArcSwap;
+use pulldown_cmark::{html, Options, Parser};
+use serde::Serialize;
 use serde_json::error::Category;
 use std::collections::HashSet;
 use std::collections::{hash_map::Entry, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task;
 
-pub use deserialize::ServerConfig;
+pub use deserialize::{ServerConfig, SubtaskPolicy, WrongAnswerInfo};
+pub use managed::{CaseSettings, ProblemSettings, UploadedCase, MAX_ARCHIVE_SIZE};
 
 /// The type of a problem with type-specific data.
 #[derive(Debug, Clone)]
 pub enum ProblemType {
     Standard,
     Strict,
-    Spj { command: String, args: Vec<String> },
-    DynamicRanking { ratio: f64 },
+    Spj {
+        command: String,
+        args: Vec<String>,
+    },
+    DynamicRanking {
+        ratio: f64,
+    },
+    /// The output is checked by a testlib checker, which may give partial points.
+    Testlib {
+        command: String,
+        args: Vec<String>,
+    },
+    /// The solution interacts with the interactor through pipes.
+    Interactive {
+        command: String,
+        args: Vec<String>,
+    },
+    /// Whitespace-separated tokens are compared as numbers if possible, and each number is
+    /// accepted if either its absolute error or its relative error is within the limit.
+    Float {
+        absolute_error: f64,
+        relative_error: f64,
+    },
+    /// Whitespace-separated tokens are compared exactly, ignoring how they are separated.
+    Tokens,
 }
 
 #[readonly::make]
@@ -29,6 +65,25 @@ pub struct TestCase {
     pub memory_limit: usize,
 }
 
+/// A sample case shown with the statement.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sample {
+    pub input: String,
+    pub output: String,
+}
+
+/// A group of cases scored together.
+#[derive(Debug, Clone)]
+pub struct Subtask {
+    /// Case indices starting from 0, sorted.
+    pub cases: Vec<i32>,
+    pub score: f64,
+    pub policy: SubtaskPolicy,
+    /// Indices of earlier subtasks starting from 0, which must get full scores for this subtask to
+    /// be judged.
+    pub dependencies: Vec<usize>,
+}
+
 #[readonly::make]
 #[derive(Debug, Clone)]
 pub struct Problem {
@@ -36,14 +91,53 @@ pub struct Problem {
     pub name: String,
     pub tp: ProblemType,
     pub cases: Vec<TestCase>,
-    pub packing: Vec<Vec<i32>>,
+    pub subtasks: Vec<Subtask>,
+    /// What the info of a "Wrong Answer" case shows.
+    pub wrong_answer_info: WrongAnswerInfo,
+    /// The statement rendered from Markdown to HTML.
+    pub statement: Option<String>,
+    pub samples: Vec<Sample>,
+    pub tags: Vec<String>,
 }
 
+/// A language, whose commands may contain the placeholders `%INPUT%` for the source file,
+/// `%OUTPUT%` for the compiled program and `%DIR%` for the working directory.
 #[derive(Debug, Clone)]
 pub struct Language {
-    pub command: String,
+    /// The compiler, or `None` if the source code is run without compilation.
+    pub command: Option<String>,
     pub args: Vec<String>,
+    /// The command to run the solution.
+    pub run_command: String,
+    pub run_args: Vec<String>,
     pub file_name: String,
+    /// Whether to compile and run the solutions in a sandbox.
+    pub sandbox: bool,
+    /// Whether to forbid the solutions to create processes, which requires the sandbox.
+    pub forbid_fork: bool,
+    pub time_multiplier: f64,
+    pub memory_multiplier: f64,
+}
+
+impl Language {
+    /// Multiply a time limit for the language.
+    pub fn time_limit(&self, time_limit: Duration) -> Duration {
+        match time_limit {
+            Duration::MAX => Duration::MAX,
+            time_limit => {
+                Duration::try_from_secs_f64(time_limit.as_secs_f64() * self.time_multiplier)
+                    .unwrap_or(Duration::MAX)
+            }
+        }
+    }
+
+    /// Multiply a memory limit for the language.
+    pub fn memory_limit(&self, memory_limit: usize) -> usize {
+        match memory_limit {
+            usize::MAX => usize::MAX,
+            memory_limit => (memory_limit as f64 * self.memory_multiplier) as usize,
+        }
+    }
 }
 
 pub type ProblemMap = HashMap<i32, Problem>;
@@ -54,6 +148,8 @@ pub struct Config {
     pub server: ServerConfig,
     pub problem_map: ProblemMap,
     pub language_map: LanguageMap,
+    /// Canonicalized directories of the test data, which are hidden from sandboxed programs.
+    pub data_dirs: Vec<PathBuf>,
 }
 
 impl Config {
@@ -62,126 +158,21 @@ impl Config {
         if json.trim().is_empty() {
             bail!("config is empty");
         }
-        match serde_json::from_str::<deserialize::Config>(&json) {
+        match serde_json::from_str::<deserialize::Config>(json) {
             Ok(config) => {
                 let mut problem_map = HashMap::new();
 
-                for problem in config.problems {
+                let mut problems = config.problems;
+                if let Some(data_dir) = &config.server.data_dir {
+                    problems.extend(managed::load_problems(Path::new(data_dir))?);
+                }
+                for problem in problems {
                     match problem_map.entry(problem.id) {
                         Entry::Occupied(_) => {
                             bail!("config contains duplicate problem id {}", problem.id);
                         }
                         Entry::Vacant(entry) => {
-                            let packing = match problem
-                                .misc
-                                .as_ref()
-                                .and_then(|misc| misc.packing.as_ref())
-                            {
-                                None => {
-                                    // Treat each test case of a problem without packing as a
-                                    // subtask with only a single case.
-                                    (0..problem.cases.len()).map(|id| vec![id as i32]).collect()
-                                }
-                                Some(packing) => {
-                                    // Minus case ids by one to get case indices.
-                                    // Sort cases by id.
-                                    let mut packing = packing
-                                        .iter()
-                                        .map(|subtask| {
-                                            let mut subtask = subtask
-                                                .iter()
-                                                .map(|case| *case - 1)
-                                                .collect::<Vec<_>>();
-                                            subtask.sort();
-                                            subtask
-                                        })
-                                        .collect::<Vec<_>>();
-                                    packing.sort();
-                                    // Check that the packing is a partition.
-                                    let mut used_in_packing = HashSet::new();
-                                    for subtask in &packing {
-                                        for case in subtask {
-                                            if *case < 0 || *case >= problem.cases.len() as i32 {
-                                                bail!(
-                                                    "the packing of problem {} contains case id {} which is out of the bound of [1, {}]",
-                                                    problem.id, case + 1, problem.cases.len()
-                                                );
-                                            }
-                                            if !used_in_packing.insert(case) {
-                                                bail!("duplicated test case {} in the packing of problem {}", case, problem.id);
-                                            }
-                                        }
-                                    }
-                                    if used_in_packing.len() != problem.cases.len() {
-                                        bail!(
-                                            "missing cases in the packing of problem {}",
-                                            problem.id
-                                        );
-                                    }
-                                    packing
-                                }
-                            };
-
-                            let tp = match problem.tp {
-                                deserialize::ProblemType::Standard => ProblemType::Standard,
-                                deserialize::ProblemType::Strict => ProblemType::Strict,
-                                deserialize::ProblemType::Spj => {
-                                    let mut command_iter = problem.misc
-                                        .ok_or(anyhow!("problem {} is of spj type but has no misc field", problem.id))?
-                                        .special_judge
-                                        .ok_or(anyhow!("problem {} is of spj type but has no misc.special_judge field", problem.id))?
-                                        .into_iter();
-                                    // Split the command into command and args.
-                                    let command = command_iter.next().ok_or(anyhow!("problem {} has empty spj command", problem.id))?;
-                                    let args = command_iter.collect();
-                                    ProblemType::Spj {command, args}
-                                },
-                                deserialize::ProblemType::DynamicRanking => ProblemType::DynamicRanking {
-                                    ratio: problem.misc
-                                        .ok_or(anyhow!("problem {} is of dynamic_ranking type but has no misc field", problem.id))?
-                                        .dynamic_ranking_ratio
-                                        .ok_or(anyhow!("problem {} is of dynamic_ranking type but has no misc.dynamic_ranking_ratio field", problem.id))?,
-                                },
-                            };
-
-                            // Ensure that the total score is 100.
-                            let total_score =
-                                problem.cases.iter().map(|case| case.score).sum::<f64>();
-                            if (total_score - 100.0).abs() > 1e-10 {
-                                bail!(
-                                    "the total score of problem {} is {} instead of 100",
-                                    problem.id,
-                                    total_score
-                                );
-                            }
-
-                            // Transform time limit from micros to `Duration`.
-                            // Transform no limit to the `MAX` value.
-                            let cases = problem
-                                .cases
-                                .into_iter()
-                                .map(|case| TestCase {
-                                    score: case.score,
-                                    input_file: case.input_file,
-                                    answer_file: case.answer_file,
-                                    time_limit: match case.time_limit {
-                                        0 => Duration::MAX,
-                                        micros => Duration::from_micros(micros),
-                                    },
-                                    memory_limit: match case.memory_limit {
-                                        0 => usize::MAX,
-                                        bytes => bytes,
-                                    },
-                                })
-                                .collect();
-
-                            entry.insert(Problem {
-                                id: problem.id,
-                                name: problem.name,
-                                tp,
-                                cases,
-                                packing,
-                            });
+                            entry.insert(self::problem(problem)?);
                         }
                     }
                 }
@@ -194,26 +185,77 @@ impl Config {
                             bail!("duplicate language name {} in the config", language.name);
                         }
                         Entry::Vacant(entry) => {
-                            // Split command into command and args.
-                            let mut iter = language.command.into_iter();
-                            let command = match iter.next() {
-                                None => bail!("language {} has empty command", language.name),
+                            // Split commands into command and args.
+                            let (command, args) = match language.command {
+                                None => (None, Vec::new()),
+                                Some(command) => {
+                                    let mut iter = command.into_iter();
+                                    let command = match iter.next() {
+                                        None => {
+                                            bail!("language {} has empty command", language.name)
+                                        }
+                                        Some(command) => command,
+                                    };
+                                    (Some(command), iter.collect())
+                                }
+                            };
+                            let mut iter = language.run_command.into_iter();
+                            let run_command = match iter.next() {
+                                None => bail!("language {} has empty run_command", language.name),
                                 Some(command) => command,
                             };
-                            let args = iter.collect();
+                            let run_args = iter.collect::<Vec<_>>();
+                            if command.is_none()
+                                && std::iter::once(&run_command)
+                                    .chain(&run_args)
+                                    .any(|arg| arg == "%OUTPUT%")
+                            {
+                                bail!(
+                                    "language {} runs %OUTPUT% but has no compile command",
+                                    language.name
+                                );
+                            }
+                            if language.forbid_fork && !language.sandbox {
+                                bail!("language {} has forbid_fork but no sandbox", language.name);
+                            }
+                            for (name, multiplier) in [
+                                ("time_multiplier", language.time_multiplier),
+                                ("memory_multiplier", language.memory_multiplier),
+                            ] {
+                                if !(multiplier.is_finite() && multiplier > 0.0) {
+                                    bail!(
+                                        "the {} of language {} should be positive",
+                                        name,
+                                        language.name
+                                    );
+                                }
+                            }
                             entry.insert(Language {
                                 command,
                                 args,
+                                run_command,
+                                run_args,
                                 file_name: language.file_name,
+                                sandbox: language.sandbox,
+                                forbid_fork: language.forbid_fork,
+                                time_multiplier: language.time_multiplier,
+                                memory_multiplier: language.memory_multiplier,
                             });
                         }
                     }
                 }
 
+                if config.server.workers == Some(0) && config.server.node_token.is_none() {
+                    bail!("the number of judger workers should be positive without remote judge nodes");
+                }
+
+                let data_dirs = data_dirs(&problem_map, config.server.data_dir.as_deref());
+
                 Ok(Self {
                     server: config.server,
                     problem_map,
                     language_map,
+                    data_dirs,
                 })
             }
             Err(error) => {
@@ -228,3 +270,452 @@ impl Config {
         }
     }
 }
+
+/// Validate and convert a problem in the config file format.
+fn problem(problem: deserialize::Problem) -> Result<Problem> {
+    let has_subtasks = problem
+        .misc
+        .as_ref()
+        .is_some_and(|misc| misc.subtasks.is_some());
+    let subtasks = subtasks(&problem)?;
+    let wrong_answer_info = problem
+        .misc
+        .as_ref()
+        .and_then(|misc| misc.wrong_answer_info);
+    if wrong_answer_info.is_some()
+        && !matches!(
+            problem.tp,
+            deserialize::ProblemType::Standard
+                | deserialize::ProblemType::Strict
+                | deserialize::ProblemType::DynamicRanking
+                | deserialize::ProblemType::Float
+                | deserialize::ProblemType::Tokens
+        )
+    {
+        bail!(
+            "problem {} has misc.wrong_answer_info but its output is not compared with the answer directly",
+            problem.id
+        );
+    }
+
+    let tp = match problem.tp {
+        deserialize::ProblemType::Standard => ProblemType::Standard,
+        deserialize::ProblemType::Strict => ProblemType::Strict,
+        deserialize::ProblemType::Spj => {
+            let mut command_iter = problem.misc
+                .ok_or(anyhow!("problem {} is of spj type but has no misc field", problem.id))?
+                .special_judge
+                .ok_or(anyhow!("problem {} is of spj type but has no misc.special_judge field", problem.id))?
+                .into_iter();
+            // Split the command into command and args.
+            let command = command_iter.next().ok_or(anyhow!("problem {} has empty spj command", problem.id))?;
+            let args = command_iter.collect();
+            ProblemType::Spj {command, args}
+        },
+        deserialize::ProblemType::Testlib => {
+            let mut command_iter = problem.misc
+                .ok_or(anyhow!("problem {} is of testlib type but has no misc field", problem.id))?
+                .special_judge
+                .ok_or(anyhow!("problem {} is of testlib type but has no misc.special_judge field", problem.id))?
+                .into_iter();
+            // Split the command into command and args.
+            let command = command_iter.next().ok_or(anyhow!("problem {} has empty checker command", problem.id))?;
+            let args = command_iter.collect();
+            ProblemType::Testlib {command, args}
+        },
+        deserialize::ProblemType::DynamicRanking => ProblemType::DynamicRanking {
+            ratio: problem.misc
+                .ok_or(anyhow!("problem {} is of dynamic_ranking type but has no misc field", problem.id))?
+                .dynamic_ranking_ratio
+                .ok_or(anyhow!("problem {} is of dynamic_ranking type but has no misc.dynamic_ranking_ratio field", problem.id))?,
+        },
+        deserialize::ProblemType::Interactive => {
+            let mut command_iter = problem.misc
+                .ok_or(anyhow!("problem {} is of interactive type but has no misc field", problem.id))?
+                .interactor
+                .ok_or(anyhow!("problem {} is of interactive type but has no misc.interactor field", problem.id))?
+                .into_iter();
+            // Split the command into command and args.
+            let command = command_iter.next().ok_or(anyhow!("problem {} has empty interactor command", problem.id))?;
+            let args = command_iter.collect();
+            ProblemType::Interactive {command, args}
+        },
+        deserialize::ProblemType::Float => {
+            let misc = problem.misc
+                .ok_or(anyhow!("problem {} is of float type but has no misc field", problem.id))?;
+            if misc.absolute_error.is_none() && misc.relative_error.is_none() {
+                bail!("problem {} is of float type but has neither misc.absolute_error nor misc.relative_error", problem.id);
+            }
+            let absolute_error = misc.absolute_error.unwrap_or(0.0);
+            let relative_error = misc.relative_error.unwrap_or(0.0);
+            if !(absolute_error >= 0.0 && absolute_error.is_finite()
+                && relative_error >= 0.0 && relative_error.is_finite())
+            {
+                bail!("the allowed errors of problem {} must be non-negative and finite", problem.id);
+            }
+            ProblemType::Float {absolute_error, relative_error}
+        },
+        deserialize::ProblemType::Tokens => ProblemType::Tokens,
+    };
+
+    // Ensure that the total score is 100.
+    let total_score = subtasks.iter().map(|subtask| subtask.score).sum::<f64>();
+    if (total_score - 100.0).abs() > 1e-10 {
+        bail!(
+            "the total score of problem {} is {} instead of 100",
+            problem.id,
+            total_score
+        );
+    }
+
+    // Transform time limit from micros to `Duration`.
+    // Transform no limit to the `MAX` value.
+    // With `misc.subtasks`, each case is an equal share of its subtask.
+    let mut case_scores = problem
+        .cases
+        .iter()
+        .map(|case| case.score)
+        .collect::<Vec<_>>();
+    if has_subtasks {
+        for subtask in &subtasks {
+            for &case in &subtask.cases {
+                case_scores[case as usize] = subtask.score / subtask.cases.len() as f64;
+            }
+        }
+    }
+    let cases = problem
+        .cases
+        .into_iter()
+        .zip(case_scores)
+        .map(|(case, score)| TestCase {
+            score,
+            input_file: case.input_file,
+            answer_file: case.answer_file,
+            time_limit: match case.time_limit {
+                0 => Duration::MAX,
+                micros => Duration::from_micros(micros),
+            },
+            memory_limit: match case.memory_limit {
+                0 => usize::MAX,
+                bytes => bytes,
+            },
+        })
+        .collect();
+
+    let statement = problem
+        .statement
+        .map(|path| render_statement(&path))
+        .transpose()
+        .with_context(|| format!("failed to read the statement of problem {}", problem.id))?;
+    let samples = problem
+        .samples
+        .into_iter()
+        .map(|sample| {
+            Ok(Sample {
+                input: fs::read_to_string(&sample.input_file)?,
+                output: fs::read_to_string(&sample.answer_file)?,
+            })
+        })
+        .collect::<Result<_>>()
+        .with_context(|| format!("failed to read the samples of problem {}", problem.id))?;
+
+    Ok(Problem {
+        id: problem.id,
+        name: problem.name,
+        tp,
+        cases,
+        subtasks,
+        wrong_answer_info: wrong_answer_info.unwrap_or_default(),
+        statement,
+        samples,
+        tags: problem.tags,
+    })
+}
+
+/// Get the subtasks of a problem from `misc.subtasks`, or `misc.packing` where the score of a
+/// subtask is the sum of the scores of its cases, or a subtask for each case if neither is set.
+fn subtasks(problem: &deserialize::Problem) -> Result<Vec<Subtask>> {
+    let misc = problem.misc.as_ref();
+    match (
+        misc.and_then(|misc| misc.packing.as_ref()),
+        misc.and_then(|misc| misc.subtasks.as_ref()),
+    ) {
+        (Some(_), Some(_)) => bail!(
+            "problem {} has both misc.packing and misc.subtasks",
+            problem.id
+        ),
+        (None, None) => Ok(problem
+            .cases
+            .iter()
+            .enumerate()
+            .map(|(index, case)| Subtask {
+                cases: vec![index as i32],
+                score: case.score,
+                policy: SubtaskPolicy::MinRatio,
+                dependencies: Vec::new(),
+            })
+            .collect()),
+        (Some(packing), None) => {
+            let mut packing = case_indices(problem, "packing", packing)?;
+            packing.sort();
+            Ok(packing
+                .into_iter()
+                .map(|cases| Subtask {
+                    score: cases
+                        .iter()
+                        .map(|&case| problem.cases[case as usize].score)
+                        .sum(),
+                    cases,
+                    policy: SubtaskPolicy::MinRatio,
+                    dependencies: Vec::new(),
+                })
+                .collect())
+        }
+        (None, Some(subtasks)) => {
+            let cases = subtasks
+                .iter()
+                .map(|subtask| subtask.cases.clone())
+                .collect::<Vec<_>>();
+            let cases = case_indices(problem, "subtasks", &cases)?;
+            subtasks
+                .iter()
+                .zip(cases)
+                .enumerate()
+                .map(|(index, (subtask, cases))| {
+                    if cases.is_empty() {
+                        bail!("subtask {} of problem {} has no cases", index + 1, problem.id);
+                    }
+                    if subtask.score < 0.0 {
+                        bail!(
+                            "subtask {} of problem {} has a negative score",
+                            index + 1,
+                            problem.id
+                        );
+                    }
+                    let dependencies = subtask
+                        .dependencies
+                        .iter()
+                        .map(|&dependency| {
+                            if dependency == 0 || dependency > index {
+                                bail!(
+                                    "subtask {} of problem {} depends on subtask {}, which is not an earlier subtask",
+                                    index + 1,
+                                    problem.id,
+                                    dependency
+                                );
+                            }
+                            Ok(dependency - 1)
+                        })
+                        .collect::<Result<_>>()?;
+                    Ok(Subtask {
+                        cases,
+                        score: subtask.score,
+                        policy: subtask.policy,
+                        dependencies,
+                    })
+                })
+                .collect()
+        }
+    }
+}
+
+/// Convert groups of case ids starting from 1 to sorted case indices starting from 0, and check
+/// that the groups are a partition of all cases.
+///
+/// * `field`: the name of the field in `misc` used in error messages.
+fn case_indices(
+    problem: &deserialize::Problem,
+    field: &str,
+    groups: &[Vec<i32>],
+) -> Result<Vec<Vec<i32>>> {
+    let groups = groups
+        .iter()
+        .map(|group| {
+            let mut group = group.iter().map(|case| *case - 1).collect::<Vec<_>>();
+            group.sort();
+            group
+        })
+        .collect::<Vec<_>>();
+    let mut used = HashSet::new();
+    for group in &groups {
+        for case in group {
+            if *case < 0 || *case >= problem.cases.len() as i32 {
+                bail!(
+                    "the {} of problem {} contains case id {} which is out of the bound of [1, {}]",
+                    field,
+                    problem.id,
+                    case + 1,
+                    problem.cases.len()
+                );
+            }
+            if !used.insert(case) {
+                bail!(
+                    "duplicated test case {} in the {} of problem {}",
+                    case + 1,
+                    field,
+                    problem.id
+                );
+            }
+        }
+    }
+    if used.len() != problem.cases.len() {
+        bail!("missing cases in the {} of problem {}", field, problem.id);
+    }
+    Ok(groups)
+}
+
+/// Read a statement in Markdown and render it to HTML.
+fn render_statement(path: &str) -> Result<String> {
+    let markdown = fs::read_to_string(path)?;
+    let parser = Parser::new_ext(
+        &markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    );
+    let mut statement = String::new();
+    html::push_html(&mut statement, parser);
+    Ok(statement)
+}
+
+/// Get the directories containing the input and answer files of all problems, and the directory of
+/// the managed problems, which also contains data not used yet.
+fn data_dirs(problem_map: &ProblemMap, data_dir: Option<&str>) -> Vec<PathBuf> {
+    let files = problem_map.values().flat_map(|problem| {
+        problem
+            .cases
+            .iter()
+            .flat_map(|case| [case.input_file.as_str(), case.answer_file.as_str()])
+    });
+    let mut dirs = Vec::new();
+    for file in files {
+        // Canonicalize the paths because symlinks can't be mount points.
+        match fs::canonicalize(file) {
+            Ok(path) => dirs.extend(path.parent().map(Path::to_path_buf)),
+            Err(error) => log::warn!("Failed to find the test data [{}]: {}", file, error),
+        }
+    }
+    dirs.extend(data_dir.and_then(|dir| fs::canonicalize(dir).ok()));
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+/// The config shared by the routes and the judger, which can be reloaded from the config file
+/// while the server is running.
+///
+/// Each request or job should `load` the config once, so that it sees a consistent config even if
+/// the config is reloaded in the meantime.
+#[derive(Clone)]
+pub struct SharedConfig {
+    path: Arc<PathBuf>,
+    current: Arc<ArcSwap<Config>>,
+    /// Serializes the reloads and the changes to the managed problems.
+    lock: Arc<Mutex<()>>,
+}
+
+impl SharedConfig {
+    /// Share the config loaded from the config file at `path`.
+    pub fn new(config: Config, path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: Arc::new(path.into()),
+            current: Arc::new(ArcSwap::from_pointee(config)),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Get the current config.
+    pub fn load(&self) -> Arc<Config> {
+        self.current.load_full()
+    }
+
+    /// Read the config file again and replace the problems and languages.
+    ///
+    /// The server config is kept because it can't be changed without restarting the server. The
+    /// reload is rejected if the new config is invalid, if any problem used by a contest, or any
+    /// problem or language used by an unfinished job, is missing from the new config, or if the
+    /// number of cases of a problem used by an unfinished job is changed.
+    pub fn reload(&self, pool: &ConnectionPool) -> ApiResult<Arc<Config>> {
+        let invalid = |error: anyhow::Error| {
+            ApiError::new(ApiErrorType::InvalidArgument, format!("{:#}", error))
+        };
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let json = fs::read_to_string(self.path.as_path())
+            .with_context(|| format!("failed to read the config [{}]", self.path.display()))
+            .map_err(invalid)?;
+        let mut config = Config::new(&json).map_err(invalid)?;
+        let current = self.load();
+
+        for (contest_id, problem_id) in contests::get_all_contest_problems(pool)? {
+            if !config.problem_map.contains_key(&problem_id) {
+                return Err(invalid(anyhow!(
+                    "problem {} is used by contest {} but missing in the new config",
+                    problem_id,
+                    contest_id
+                )));
+            }
+        }
+        for (job_id, problem_id, language) in jobs::get_unfinished_job_problems_and_languages(pool)?
+        {
+            if !config.problem_map.contains_key(&problem_id) {
+                return Err(invalid(anyhow!(
+                    "problem {} is used by unfinished job {} but missing in the new config",
+                    problem_id,
+                    job_id
+                )));
+            }
+            if !config.language_map.contains_key(&language) {
+                return Err(invalid(anyhow!(
+                    "language {} is used by unfinished job {} but missing in the new config",
+                    language,
+                    job_id
+                )));
+            }
+            if let Some(problem) = current.problem_map.get(&problem_id) {
+                check_case_count(problem, &config.problem_map[&problem_id], job_id)?;
+            }
+        }
+
+        config.server = current.server.clone();
+        let config = Arc::new(config);
+        self.current.store(config.clone());
+        Ok(config)
+    }
+}
+
+/// Check that the number of cases of a problem used by an unfinished job is not changed, as the
+/// case results of the job are initialized with the number of cases at submission.
+fn check_case_count(current: &Problem, new: &Problem, job_id: i32) -> ApiResult<()> {
+    if current.cases.len() != new.cases.len() {
+        return Err(ApiError::new(
+            ApiErrorType::InvalidState,
+            format!(
+                "problem {} can't change from {} to {} cases before job {} is finished",
+                current.id,
+                current.cases.len(),
+                new.cases.len(),
+                job_id
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Reload the problems and languages from the config file on every SIGHUP.
+pub async fn reload_on_sighup(config: SharedConfig, pool: ConnectionPool) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(error) => {
+            log::error!("Failed to listen to SIGHUP: {}", error);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        log::info!("Reloading the config as SIGHUP is received");
+        let config = config.clone();
+        let pool = pool.clone();
+        match task::spawn_blocking(move || config.reload(&pool)).await {
+            Ok(Ok(_)) => log::info!("Config reloaded"),
+            Ok(Err(error)) => log::error!("Failed to reload the config: {:?}", error),
+            Err(error) => log::error!("Failed to reload the config: {}", error),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 //! Structs that match the config file format for deserializing the config.
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 fn default_address() -> String {
     String::from("127.0.0.1")
@@ -10,32 +10,112 @@ fn default_port() -> u16 {
     12345
 }
 
+fn default_compiler_output_limit() -> usize {
+    16384
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct ServerConfig {
     #[serde(default = "default_address")]
     pub bind_address: String,
     #[serde(default = "default_port")]
     pub bind_port: u16,
+    /// The maximum number of bytes of the compiler output shown for the compilation.
+    #[serde(default = "default_compiler_output_limit")]
+    pub compiler_output_limit: usize,
+    /// Whether to require users to log in and enforce the permissions of roles.
+    ///
+    /// When it's disabled, everyone is treated as an admin, e.g. the ranklist is not frozen unless
+    /// `frozen=true` is queried.
+    #[serde(default)]
+    pub authentication: bool,
+    /// The number of judger workers, which is half the number of CPUs by default.
+    pub workers: Option<usize>,
+    /// The token of remote judge nodes. Remote judge nodes are disabled if it's not set.
+    pub node_token: Option<String>,
+    /// The directory of the problems managed through the API. Problem management is disabled if
+    /// it's not set.
+    pub data_dir: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum ProblemType {
     Standard,
     Strict,
     Spj,
     DynamicRanking,
+    Interactive,
+    Testlib,
+    Float,
+    Tokens,
 }
 
-#[derive(Deserialize)]
+/// How the score of a subtask is computed from the points of its cases.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtaskPolicy {
+    /// The sum of the scores of the cases, each of which is an equal share of the subtask.
+    Sum,
+    /// The score of the subtask times the minimum fraction of points of the cases.
+    #[default]
+    MinRatio,
+    /// The score of the subtask if all the cases are accepted, otherwise zero.
+    AllOrNothing,
+}
+
+/// What the info of a "Wrong Answer" case shows, for problems compared with the answer directly.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WrongAnswerInfo {
+    /// Nothing.
+    #[default]
+    None,
+    /// The line and column of the first difference, e.g. for hidden tests.
+    Position,
+    /// The first differing line with the expected and the actual tokens, e.g. for practice.
+    Full,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Subtask {
+    /// Case IDs starting from 1.
+    pub cases: Vec<i32>,
+    pub score: f64,
+    #[serde(default)]
+    pub policy: SubtaskPolicy,
+    /// IDs of earlier subtasks starting from 1, which must get full scores for this subtask to be
+    /// judged.
+    #[serde(default)]
+    pub dependencies: Vec<usize>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct ProblemMisc {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub packing: Option<Vec<Vec<i32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subtasks: Option<Vec<Subtask>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub special_judge: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub dynamic_ranking_ratio: Option<f64>,
+    /// The absolute error allowed by the float checker.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute_error: Option<f64>,
+    /// The relative error allowed by the float checker.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative_error: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactor: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrong_answer_info: Option<WrongAnswerInfo>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct TestCase {
+    /// Ignored if the problem has `misc.subtasks`.
+    #[serde(default)]
     pub score: f64,
     pub input_file: String,
     pub answer_file: String,
@@ -43,21 +123,58 @@ pub struct TestCase {
     pub memory_limit: usize,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Sample {
+    pub input_file: String,
+    pub answer_file: String,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
 pub struct Problem {
     pub id: i32,
     pub name: String,
     #[serde(rename = "type")]
     pub tp: ProblemType,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub misc: Option<ProblemMisc>,
     pub cases: Vec<TestCase>,
+    /// Path to the statement in Markdown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statement: Option<String>,
+    #[serde(default)]
+    pub samples: Vec<Sample>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_run_command() -> Vec<String> {
+    vec![String::from("%OUTPUT%")]
+}
+
+fn default_multiplier() -> f64 {
+    1.0
 }
 
 #[derive(Deserialize)]
 pub struct Language {
     pub name: String,
     pub file_name: String,
-    pub command: Vec<String>,
+    /// The compile command, which can be omitted if the source code is run without compilation.
+    pub command: Option<Vec<String>>,
+    /// The command to run the solution, which runs the compiled program by default.
+    #[serde(default = "default_run_command")]
+    pub run_command: Vec<String>,
+    #[serde(default)]
+    pub sandbox: bool,
+    /// Whether to forbid the solutions to create processes in the sandbox.
+    #[serde(default)]
+    pub forbid_fork: bool,
+    /// The time limits of the cases are multiplied by it.
+    #[serde(default = "default_multiplier")]
+    pub time_multiplier: f64,
+    /// The memory limits of the cases are multiplied by it.
+    #[serde(default = "default_multiplier")]
+    pub memory_multiplier: f64,
 }
 
 #[derive(Deserialize)]
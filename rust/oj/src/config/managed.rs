@@ -0,0 +1,378 @@
+//! Problems managed through the API, which are stored in `server.data_dir`.
+//!
+//! Each problem is stored in `{data_dir}/{id}/`:
+//!
+//! - `problem.json`: the problem in the config file format, with absolute paths.
+//! - `statement.md`: the statement in Markdown, if any.
+//! - `data/{version}/`: the test data extracted from an uploaded zip archive, where the version
+//!   increases with each upload. Older versions are kept because running jobs may still use them.
+
+use super::{deserialize, Config, Problem, SharedConfig};
+use crate::db::connection::ConnectionPool;
+use crate::db::jobs;
+use crate::error::{ApiError, ApiErrorType, ApiResult};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::PoisonError;
+use tempfile::NamedTempFile;
+
+/// The maximum size of an uploaded zip archive.
+pub const MAX_ARCHIVE_SIZE: usize = 256 << 20;
+/// The maximum total size of the test data extracted from an archive.
+const MAX_DATA_SIZE: u64 = 1 << 30;
+
+const PROBLEM_FILE: &str = "problem.json";
+const STATEMENT_FILE: &str = "statement.md";
+const VERSIONS_DIR: &str = "data";
+
+/// The settings of a managed problem, which are the same as a problem in the config file, except
+/// that the test data is uploaded and the statement is given as Markdown.
+#[derive(Deserialize)]
+pub struct ProblemSettings {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub tp: deserialize::ProblemType,
+    pub misc: Option<deserialize::ProblemMisc>,
+    /// The settings of each case in the latest uploaded test data, in the same order.
+    pub cases: Vec<CaseSettings>,
+    /// The statement in Markdown.
+    pub statement: Option<String>,
+    /// Case IDs starting from 1, which are shown as samples.
+    #[serde(default)]
+    pub samples: Vec<usize>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// The score and limits of a case, the same as in the config file.
+#[derive(Deserialize)]
+pub struct CaseSettings {
+    /// Ignored if the problem has `misc.subtasks`.
+    #[serde(default)]
+    pub score: f64,
+    pub time_limit: u64,
+    pub memory_limit: usize,
+}
+
+/// The file names of a case in the uploaded test data.
+#[derive(Serialize)]
+pub struct UploadedCase {
+    pub input_file: String,
+    pub answer_file: String,
+}
+
+fn invalid(error: anyhow::Error) -> ApiError {
+    ApiError::new(ApiErrorType::InvalidArgument, format!("{:#}", error))
+}
+
+/// Load the managed problems in the data directory, which is created if missing.
+pub(super) fn load_problems(data_dir: &Path) -> Result<Vec<deserialize::Problem>> {
+    fs::create_dir_all(data_dir).with_context(|| {
+        format!(
+            "failed to create the data directory [{}]",
+            data_dir.display()
+        )
+    })?;
+    let mut problems = Vec::new();
+    for entry in fs::read_dir(data_dir)? {
+        let path = entry?.path().join(PROBLEM_FILE);
+        if !path.is_file() {
+            continue;
+        }
+        let json = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read the problem [{}]", path.display()))?;
+        let problem = serde_json::from_str(&json)
+            .with_context(|| format!("invalid content in the problem [{}]", path.display()))?;
+        problems.push(problem);
+    }
+    Ok(problems)
+}
+
+/// The key to sort names naturally, e.g. `2` before `10`, which splits a name into runs of other
+/// characters, each followed by a number.
+fn natural_key(name: &str) -> Vec<(String, u128)> {
+    let mut key = Vec::new();
+    let mut text = String::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let mut digits = String::from(c);
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            let number = digits.parse().unwrap_or(u128::MAX);
+            key.push((std::mem::take(&mut text), number));
+        } else {
+            text.push(c);
+        }
+    }
+    key.push((text, 0));
+    key
+}
+
+/// Pair the `{name}.in` and `{name}.ans` files into cases sorted naturally by `{name}`. Other files
+/// are ignored.
+fn pair_cases<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Vec<UploadedCase>> {
+    let mut pairs = HashMap::<&str, (Option<&str>, Option<&str>)>::new();
+    for name in names {
+        if let Some(stem) = name.strip_suffix(".in") {
+            pairs.entry(stem).or_default().0 = Some(name);
+        } else if let Some(stem) = name.strip_suffix(".ans") {
+            pairs.entry(stem).or_default().1 = Some(name);
+        }
+    }
+    let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+    pairs.sort_by_cached_key(|(stem, _)| (natural_key(stem), *stem));
+    if pairs.is_empty() {
+        bail!("no test cases found, which should be pairs of *.in and *.ans files");
+    }
+    pairs
+        .into_iter()
+        .map(|(stem, files)| match files {
+            (Some(input_file), Some(answer_file)) => Ok(UploadedCase {
+                input_file: input_file.to_owned(),
+                answer_file: answer_file.to_owned(),
+            }),
+            (Some(_), None) => bail!("missing the answer file {}.ans", stem),
+            (None, _) => bail!("missing the input file {}.in", stem),
+        })
+        .collect()
+}
+
+/// Extract the `*.in` and `*.ans` files in a zip archive into a directory, ignoring the directory
+/// structure of the archive, and pair them into cases.
+fn extract_cases(archive: &[u8], dir: &Path) -> Result<Vec<UploadedCase>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(archive)).context("invalid zip archive")?;
+    let mut names = HashSet::new();
+    let mut total_size = 0;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).context("invalid zip archive")?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = file
+            .enclosed_name()
+            .ok_or_else(|| anyhow!("unsafe path [{}] in the archive", file.name()))?
+            .to_path_buf();
+        // Skip hidden files and metadata, e.g. `.DS_Store` and `__MACOSX/`.
+        let hidden = path.components().any(|component| {
+            let component = component.as_os_str().to_string_lossy();
+            component.starts_with('.') || component == "__MACOSX"
+        });
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if !hidden && (name.ends_with(".in") || name.ends_with(".ans")) => {
+                name.to_owned()
+            }
+            _ => continue,
+        };
+        if !names.insert(name.clone()) {
+            bail!("duplicate file name {} in the archive", name);
+        }
+        let mut output = fs::File::create(dir.join(&name))?;
+        total_size += io::copy(
+            &mut (&mut file).take(MAX_DATA_SIZE - total_size + 1),
+            &mut output,
+        )?;
+        if total_size > MAX_DATA_SIZE {
+            bail!("the test data is larger than {} bytes", MAX_DATA_SIZE);
+        }
+    }
+    pair_cases(names.iter().map(String::as_str))
+}
+
+/// Get the latest version of the test data in the directory, or `None` if there's none.
+fn latest_version(versions_dir: &Path) -> io::Result<Option<u64>> {
+    if !versions_dir.is_dir() {
+        return Ok(None);
+    }
+    let mut latest = None;
+    for entry in fs::read_dir(versions_dir)? {
+        if let Some(version) = entry?
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u64>().ok())
+        {
+            latest = latest.max(Some(version));
+        }
+    }
+    Ok(latest)
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Write a file atomically by renaming a temporary file in the same directory.
+fn write_file(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = NamedTempFile::new_in(path.parent().unwrap_or(Path::new(".")))?;
+    file.write_all(data)?;
+    file.persist(path)?;
+    Ok(())
+}
+
+impl SharedConfig {
+    /// Get the directory of a managed problem, or an error if problem management is disabled or
+    /// the problem is defined in the config file.
+    fn problem_dir(&self, id: i32) -> ApiResult<PathBuf> {
+        let config = self.load();
+        let data_dir = config.server.data_dir.as_ref().ok_or_else(|| {
+            ApiError::new(
+                ApiErrorType::InvalidState,
+                String::from("problem management is disabled without server.data_dir"),
+            )
+        })?;
+        let problem_dir = fs::canonicalize(data_dir)?.join(id.to_string());
+        if config.problem_map.contains_key(&id) && !problem_dir.join(PROBLEM_FILE).is_file() {
+            return Err(ApiError::new(
+                ApiErrorType::InvalidState,
+                format!(
+                    "problem {} is defined in the config file and can't be managed",
+                    id
+                ),
+            ));
+        }
+        Ok(problem_dir)
+    }
+
+    /// Extract the test data of a managed problem from a zip archive as a new version, which is
+    /// used by the problem from the next update of its settings.
+    pub fn upload_data(&self, id: i32, archive: &[u8]) -> ApiResult<Vec<UploadedCase>> {
+        let versions_dir = self.problem_dir(id)?.join(VERSIONS_DIR);
+        fs::create_dir_all(&versions_dir)?;
+        let tmp_dir = tempfile::Builder::new()
+            .prefix(".upload-")
+            .tempdir_in(&versions_dir)?;
+        let cases = extract_cases(archive, tmp_dir.path()).map_err(invalid)?;
+
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let version = latest_version(&versions_dir)?.map_or(1, |version| version + 1);
+        fs::rename(tmp_dir.into_path(), versions_dir.join(version.to_string()))?;
+        Ok(cases)
+    }
+
+    /// Create or update a managed problem with the latest uploaded test data, and save it in the
+    /// data directory. The problem is validated in the same way as in the config file, and its
+    /// number of cases can't be changed while it has unfinished jobs.
+    pub fn update_problem(
+        &self,
+        id: i32,
+        settings: ProblemSettings,
+        pool: &ConnectionPool,
+    ) -> ApiResult<Problem> {
+        let problem_dir = self.problem_dir(id)?;
+        let _guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let versions_dir = problem_dir.join(VERSIONS_DIR);
+        let version = latest_version(&versions_dir)?.ok_or_else(|| {
+            ApiError::new(
+                ApiErrorType::InvalidState,
+                format!("no test data is uploaded for problem {}", id),
+            )
+        })?;
+        let data_dir = versions_dir.join(version.to_string());
+        let names = fs::read_dir(&data_dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<io::Result<Vec<_>>>()?;
+        let files = pair_cases(names.iter().map(String::as_str)).map_err(invalid)?;
+        if settings.cases.len() != files.len() {
+            return Err(invalid(anyhow!(
+                "problem {} has {} cases in the test data but {} in the settings",
+                id,
+                files.len(),
+                settings.cases.len()
+            )));
+        }
+
+        let samples = settings
+            .samples
+            .iter()
+            .map(|&case| {
+                let file = files.get(case.wrapping_sub(1)).ok_or_else(|| {
+                    invalid(anyhow!(
+                        "sample case {} of problem {} is out of the bound of [1, {}]",
+                        case,
+                        id,
+                        files.len()
+                    ))
+                })?;
+                Ok(deserialize::Sample {
+                    input_file: path_string(&data_dir.join(&file.input_file)),
+                    answer_file: path_string(&data_dir.join(&file.answer_file)),
+                })
+            })
+            .collect::<ApiResult<_>>()?;
+        let statement_file = problem_dir.join(STATEMENT_FILE);
+        let problem = deserialize::Problem {
+            id,
+            name: settings.name,
+            tp: settings.tp,
+            misc: settings.misc,
+            cases: files
+                .iter()
+                .zip(settings.cases)
+                .map(|(file, case)| deserialize::TestCase {
+                    score: case.score,
+                    input_file: path_string(&data_dir.join(&file.input_file)),
+                    answer_file: path_string(&data_dir.join(&file.answer_file)),
+                    time_limit: case.time_limit,
+                    memory_limit: case.memory_limit,
+                })
+                .collect(),
+            statement: settings
+                .statement
+                .as_ref()
+                .map(|_| path_string(&statement_file)),
+            samples,
+            tags: settings.tags,
+        };
+
+        // Validate with the new statement in a temporary file, so that nothing is changed if the
+        // problem is invalid.
+        let mut new_statement = NamedTempFile::new_in(&problem_dir)?;
+        new_statement.write_all(settings.statement.unwrap_or_default().as_bytes())?;
+        let validated = super::problem(deserialize::Problem {
+            statement: problem
+                .statement
+                .as_ref()
+                .map(|_| path_string(new_statement.path())),
+            ..problem.clone()
+        })
+        .map_err(invalid)?;
+        if let Some(current) = self.load().problem_map.get(&id) {
+            let unfinished_job = jobs::get_unfinished_job_problems_and_languages(pool)?
+                .into_iter()
+                .find(|(_, problem_id, _)| *problem_id == id);
+            if let Some((job_id, _, _)) = unfinished_job {
+                super::check_case_count(current, &validated, job_id)?;
+            }
+        }
+
+        let json = serde_json::to_vec_pretty(&problem).map_err(|error| {
+            ApiError::new(
+                ApiErrorType::Internal,
+                format!("failed to serialize problem {}: {}", id, error),
+            )
+        })?;
+        if problem.statement.is_some() {
+            new_statement
+                .persist(&statement_file)
+                .map_err(io::Error::from)?;
+        } else if statement_file.exists() {
+            fs::remove_file(&statement_file)?;
+        }
+        write_file(&problem_dir.join(PROBLEM_FILE), &json)?;
+
+        self.current.rcu(|config| {
+            let mut config = Config::clone(config);
+            config.problem_map.insert(id, validated.clone());
+            config.data_dirs =
+                super::data_dirs(&config.problem_map, config.server.data_dir.as_deref());
+            config
+        });
+        Ok(validated)
+    }
+}
@@ -8,9 +8,13 @@ pub(crate) mod enums;
 pub(crate) mod case_results;
 pub(crate) mod contests;
 pub(crate) mod jobs;
+pub(crate) mod rank_results;
+pub(crate) mod sessions;
+pub(crate) mod subtask_results;
 pub(crate) mod users;
 
 mod contest_problems;
+mod contest_reveals;
 mod contest_users;
 
 mod schema;
@@ -19,5 +23,5 @@ mod utils;
 
 lazy_static::lazy_static! {
     /// Path to the database file.
-    static ref DATABASE_URL: String = std::env::var("DATABASE_URL").expect("env var DATABASE_URL must be set");
+    pub(crate) static ref DATABASE_URL: String = std::env::var("DATABASE_URL").expect("env var DATABASE_URL must be set");
 }
@@ -1,12 +1,13 @@
 //! Database operations on the `case_results` table.
 
-use super::connection::ConnectionPool;
+use super::connection::{write_transaction, ConnectionPool, DbBackend, DbConnection};
 use super::enums::JobResult;
 use super::jobs;
-use super::schema::case_results::dsl;
+use super::schema::{self, case_results::dsl};
 use crate::error::*;
 use diesel::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// All columns of the `case_results` table including the the `job_id` field.
 #[derive(Insertable, Queryable, AsChangeset)]
@@ -21,7 +22,7 @@ struct FullCase {
 }
 
 /// The case that can be used in the API.
-#[derive(Serialize, Queryable)]
+#[derive(Serialize, Queryable, Clone)]
 pub struct Case {
     id: i32,
     result: JobResult,
@@ -30,12 +31,18 @@ pub struct Case {
     info: String,
 }
 
+impl Case {
+    /// Clear the result as if the case is waiting to be judged.
+    pub fn clear(&mut self) {
+        self.result = JobResult::Waiting;
+        self.time = 0;
+        self.memory = 0;
+        self.info.clear();
+    }
+}
+
 /// Insert cases for a new job.
-pub fn init_cases(
-    job_id: i32,
-    case_count: usize,
-    conn: &mut SqliteConnection,
-) -> ApiResult<Vec<Case>> {
+pub fn init_cases(job_id: i32, case_count: usize, conn: &mut DbConnection) -> ApiResult<Vec<Case>> {
     let cases = (0..=case_count)
         .map(|id| FullCase {
             job_id,
@@ -62,7 +69,7 @@ pub fn init_cases(
 }
 
 /// Update cases for rejudging a job.
-pub fn reinit_cases(job_id: i32, conn: &mut SqliteConnection) -> ApiResult<()> {
+pub fn reinit_cases(job_id: i32, conn: &mut DbConnection) -> ApiResult<()> {
     diesel::update(dsl::case_results)
         .filter(dsl::job_id.eq(job_id))
         .set((
@@ -76,7 +83,7 @@ pub fn reinit_cases(job_id: i32, conn: &mut SqliteConnection) -> ApiResult<()> {
 }
 
 /// Get cases of a single job.
-pub(super) fn get_cases(job_id: i32, conn: &mut SqliteConnection) -> ApiResult<Vec<Case>> {
+pub(super) fn get_cases(job_id: i32, conn: &mut DbConnection) -> ApiResult<Vec<Case>> {
     Ok(dsl::case_results
         .select((dsl::id, dsl::result, dsl::time, dsl::memory, dsl::info))
         .filter(dsl::job_id.eq(job_id))
@@ -84,15 +91,49 @@ pub(super) fn get_cases(job_id: i32, conn: &mut SqliteConnection) -> ApiResult<V
         .load(conn)?)
 }
 
+/// Get cases of the jobs selected by the given query in a single query, grouped by job id.
+pub(super) fn get_cases_of_jobs(
+    jobs: schema::jobs::BoxedQuery<'_, DbBackend>,
+    conn: &mut DbConnection,
+) -> ApiResult<HashMap<i32, Vec<Case>>> {
+    let cases = dsl::case_results
+        .select((
+            dsl::job_id,
+            (dsl::id, dsl::result, dsl::time, dsl::memory, dsl::info),
+        ))
+        .filter(dsl::job_id.eq_any(jobs.select(schema::jobs::id)))
+        .order((dsl::job_id, dsl::id))
+        .load::<(i32, Case)>(conn)?;
+    let mut cases_of_jobs = HashMap::<i32, Vec<Case>>::new();
+    for (job_id, case) in cases {
+        cases_of_jobs.entry(job_id).or_default().push(case);
+    }
+    Ok(cases_of_jobs)
+}
+
 /// Data used to update the judge result of a single case.
-#[derive(AsChangeset)]
+#[derive(AsChangeset, Clone, Serialize, Deserialize)]
 #[diesel(table_name = super::schema::case_results)]
 pub struct CaseUpdate {
     pub result: JobResult,
     pub time: i64,
+    pub memory: i64,
     pub info: String,
 }
 
+impl CaseUpdate {
+    /// Get the case with the given ID after the update.
+    pub fn to_case(&self, id: i32) -> Case {
+        Case {
+            id,
+            result: self.result,
+            time: self.time,
+            memory: self.memory,
+            info: self.info.clone(),
+        }
+    }
+}
+
 /// Update a single case and the total score of a job.
 ///
 /// Note: Sometimes the score does not need to be updated. But the `updated_time` of the job always
@@ -104,7 +145,7 @@ pub fn update_case(
     total_score: f64,
     pool: &ConnectionPool,
 ) -> ApiResult<()> {
-    pool.get()?.immediate_transaction(|conn| {
+    write_transaction(pool, |conn| {
         diesel::update(dsl::case_results)
             .filter(dsl::job_id.eq(job_id))
             .filter(dsl::id.eq(case_id))
@@ -115,12 +156,25 @@ pub fn update_case(
     })
 }
 
-/// Get the time of each case except the first one which is compilation.
-pub fn get_cases_time(job_id: i32, conn: &mut SqliteConnection) -> ApiResult<Vec<i64>> {
-    Ok(dsl::case_results
-        .select(dsl::time)
-        .filter(dsl::job_id.eq(job_id))
-        .filter(dsl::id.gt(0))
-        .order(dsl::id)
-        .load(conn)?)
+/// Get the time of each case except the first one which is compilation, of each of the given
+/// jobs, with job id as the key.
+pub fn get_cases_time_of_jobs(
+    job_ids: &[i32],
+    pool: &ConnectionPool,
+) -> ApiResult<HashMap<i32, Vec<i64>>> {
+    let mut conn = pool.get()?;
+    let mut map = HashMap::<i32, Vec<i64>>::new();
+    // Query in chunks to stay below the limit of bound parameters.
+    for chunk in job_ids.chunks(500) {
+        let times = dsl::case_results
+            .select((dsl::job_id, dsl::time))
+            .filter(dsl::job_id.eq_any(chunk))
+            .filter(dsl::id.gt(0))
+            .order((dsl::job_id, dsl::id))
+            .load::<(i32, i64)>(&mut conn)?;
+        for (job_id, time) in times {
+            map.entry(job_id).or_default().push(time);
+        }
+    }
+    Ok(map)
 }
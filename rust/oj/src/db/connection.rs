@@ -1,16 +1,39 @@
 //! Obtain connections to the database.
+//!
+//! The backend is chosen at compile time by the `sqlite` (default) or the `postgres` feature.
 
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("the features `sqlite` and `postgres` can't be enabled at the same time");
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("either the feature `sqlite` or `postgres` must be enabled");
+
+use crate::error::ApiResult;
+#[cfg(feature = "sqlite")]
 use diesel::connection::SimpleConnection;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool};
+#[cfg(feature = "sqlite")]
+use diesel::r2d2::CustomizeConnection;
+use diesel::r2d2::{ConnectionManager, Pool};
 use r2d2::NopErrorHandler;
 
+/// The connection type of the database backend.
+#[cfg(feature = "sqlite")]
+pub(crate) type DbConnection = SqliteConnection;
+/// The connection type of the database backend.
+#[cfg(feature = "postgres")]
+pub(crate) type DbConnection = PgConnection;
+
+/// The database backend, e.g. used in boxed queries.
+pub(crate) type DbBackend = <DbConnection as Connection>::Backend;
+
 /// To fix the database locked error. Based on <https://stackoverflow.com/a/57717533>.
+#[cfg(feature = "sqlite")]
 #[derive(Debug)]
 struct CustomConnection;
+#[cfg(feature = "sqlite")]
 impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for CustomConnection {
     fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
-        // Remember to use immediate_transaction instead of transaction!
+        // Remember to use write_transaction or read_transaction instead of transaction!
         // See <https://github.com/the-lean-crate/criner/issues/1>
         conn.batch_execute(
             r#"
@@ -26,17 +49,109 @@ impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for CustomConnec
     }
 }
 
-/// Type alias of a Sqlite connection pool.
-pub(crate) type ConnectionPool = Pool<ConnectionManager<SqliteConnection>>;
+/// Type alias of a database connection pool.
+pub(crate) type ConnectionPool = Pool<ConnectionManager<DbConnection>>;
 
 /// Construct a connection pool to the database.
 pub fn connection_pool() -> ConnectionPool {
-    let manager = ConnectionManager::<SqliteConnection>::new(super::DATABASE_URL.as_str());
-    Pool::builder()
+    let manager = ConnectionManager::<DbConnection>::new(super::DATABASE_URL.as_str());
+    let builder = Pool::builder()
         .max_size(10 + num_cpus::get() as u32)
         // Silent the false alarm "database is locked" at startup
-        .error_handler(Box::new(NopErrorHandler))
-        .connection_customizer(Box::new(CustomConnection))
-        // Use the unchecked version to immediately start the server.
-        .build_unchecked(manager)
+        .error_handler(Box::new(NopErrorHandler));
+    #[cfg(feature = "sqlite")]
+    let builder = builder.connection_customizer(Box::new(CustomConnection));
+    // Use the unchecked version to immediately start the server.
+    builder.build_unchecked(manager)
+}
+
+/// Run a transaction which may write to the database, with a connection from the pool.
+///
+/// With SQLite, this is an immediate transaction, so write transactions are serialized, which
+/// also avoids the "database is locked" error when upgrading a read lock. With PostgreSQL,
+/// transactions run concurrently, so reading and then writing, e.g. counting the rows to get a new
+/// id, must `lock` what it reads first.
+pub(crate) fn write_transaction<T>(
+    pool: &ConnectionPool,
+    f: impl FnOnce(&mut DbConnection) -> ApiResult<T>,
+) -> ApiResult<T> {
+    let mut conn = pool.get()?;
+
+    #[cfg(feature = "sqlite")]
+    return conn.immediate_transaction(f);
+
+    #[cfg(feature = "postgres")]
+    return conn.transaction(|conn| f(conn));
+}
+
+/// Run a read-only transaction with a connection from the pool, so that multiple queries see a
+/// consistent snapshot of the database without blocking writes.
+pub(crate) fn read_transaction<T>(
+    pool: &ConnectionPool,
+    f: impl FnOnce(&mut DbConnection) -> ApiResult<T>,
+) -> ApiResult<T> {
+    let mut conn = pool.get()?;
+
+    // A deferred transaction in WAL mode reads a snapshot, and never upgrades to a write lock
+    // because it doesn't write.
+    #[cfg(feature = "sqlite")]
+    return conn.transaction(|conn| f(conn));
+
+    #[cfg(feature = "postgres")]
+    return conn
+        .build_transaction()
+        .read_only()
+        .repeatable_read()
+        .run(f);
+}
+
+/// Something read and then written in a write transaction, locked until the end of the
+/// transaction.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "sqlite", allow(dead_code))]
+pub(crate) enum Lock {
+    /// Adding a job, whose id is the number of jobs, under the submission limit of the contest.
+    AddJob,
+    /// Adding a user, whose id is the number of users, or renaming a user.
+    Users,
+    /// Adding a contest, whose id is the number of contests.
+    AddContest,
+    /// Changing the state of a job.
+    Job(i32),
+    /// Changing a contest.
+    Contest(i32),
+    /// Updating the rank results of a user.
+    RankResults(i32),
+}
+
+impl Lock {
+    /// The key of the advisory lock, as two 32-bit integers.
+    #[cfg(feature = "postgres")]
+    fn key(self) -> (i32, i32) {
+        match self {
+            Lock::AddJob => (0, 0),
+            Lock::Users => (0, 1),
+            Lock::AddContest => (0, 2),
+            Lock::Job(id) => (1, id),
+            Lock::Contest(id) => (2, id),
+            Lock::RankResults(user_id) => (3, user_id),
+        }
+    }
+}
+
+/// Lock something in a write transaction until the end of the transaction. It does nothing with
+/// SQLite, where write transactions are serialized. With PostgreSQL, it's a transaction-level
+/// advisory lock.
+#[cfg_attr(feature = "sqlite", allow(unused_variables))]
+pub(crate) fn lock(conn: &mut DbConnection, lock: Lock) -> ApiResult<()> {
+    #[cfg(feature = "postgres")]
+    {
+        use diesel::sql_types::Integer;
+        let (key1, key2) = lock.key();
+        diesel::sql_query("SELECT pg_advisory_xact_lock($1, $2)")
+            .bind::<Integer, _>(key1)
+            .bind::<Integer, _>(key2)
+            .execute(conn)?;
+    }
+    Ok(())
 }
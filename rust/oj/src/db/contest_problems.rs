@@ -1,5 +1,6 @@
 //! Database operations on the `contest_problems` table.
 
+use super::connection::DbConnection;
 use super::schema::contest_problems::dsl;
 use crate::error::ApiResult;
 use diesel::prelude::*;
@@ -8,7 +9,7 @@ use diesel::prelude::*;
 pub fn insert_contest_problems(
     contest_id: i32,
     problem_ids: &[i32],
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
 ) -> ApiResult<()> {
     diesel::insert_into(dsl::contest_problems)
         .values(
@@ -22,7 +23,7 @@ pub fn insert_contest_problems(
 }
 
 /// Delete all problems from the given contest.
-pub fn delete_contest_problems(contest_id: i32, conn: &mut SqliteConnection) -> ApiResult<()> {
+pub fn delete_contest_problems(contest_id: i32, conn: &mut DbConnection) -> ApiResult<()> {
     diesel::delete(dsl::contest_problems)
         .filter(dsl::contest_id.eq(contest_id))
         .execute(conn)?;
@@ -30,7 +31,7 @@ pub fn delete_contest_problems(contest_id: i32, conn: &mut SqliteConnection) ->
 }
 
 /// Get a list of all problem ids in the given contest.
-pub fn get_contest_problems(contest_id: i32, conn: &mut SqliteConnection) -> ApiResult<Vec<i32>> {
+pub fn get_contest_problems(contest_id: i32, conn: &mut DbConnection) -> ApiResult<Vec<i32>> {
     Ok(dsl::contest_problems
         .select(dsl::problem_id)
         .filter(dsl::contest_id.eq(contest_id))
@@ -42,7 +43,7 @@ pub fn get_contest_problems(contest_id: i32, conn: &mut SqliteConnection) -> Api
 pub fn is_problem_in_contest(
     contest_id: i32,
     problem_id: i32,
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
 ) -> ApiResult<bool> {
     let count = dsl::contest_problems
         .count()
@@ -51,3 +52,11 @@ pub fn is_problem_in_contest(
         .get_result::<i64>(conn)?;
     Ok(count > 0)
 }
+
+/// Get all (contest_id, problem_id) pairs.
+pub fn get_all_contest_problems(conn: &mut DbConnection) -> ApiResult<Vec<(i32, i32)>> {
+    Ok(dsl::contest_problems
+        .select((dsl::contest_id, dsl::problem_id))
+        .order(dsl::rowid)
+        .load(conn)?)
+}
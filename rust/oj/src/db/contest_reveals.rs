@@ -0,0 +1,42 @@
+//! Database operations on the `contest_reveals` table.
+
+use super::connection::DbConnection;
+use super::schema::contest_reveals::dsl;
+use crate::error::ApiResult;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+/// Reveal the frozen submissions of the given (user_id, problem_id) pairs in the given contest up
+/// to now. The reveal time of pairs already revealed is updated.
+pub fn insert_contest_reveals(
+    contest_id: i32,
+    pairs: &[(i32, i32)],
+    conn: &mut DbConnection,
+) -> ApiResult<()> {
+    let now = Utc::now().naive_utc();
+    for (user_id, problem_id) in pairs {
+        diesel::insert_into(dsl::contest_reveals)
+            .values((
+                dsl::contest_id.eq(contest_id),
+                dsl::user_id.eq(user_id),
+                dsl::problem_id.eq(problem_id),
+                dsl::reveal_time.eq(now),
+            ))
+            .on_conflict((dsl::contest_id, dsl::user_id, dsl::problem_id))
+            .do_update()
+            .set(dsl::reveal_time.eq(now))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Get the revealed (user_id, problem_id) pairs of the given contest with their reveal time.
+pub fn get_contest_reveals(
+    contest_id: i32,
+    conn: &mut DbConnection,
+) -> ApiResult<Vec<((i32, i32), NaiveDateTime)>> {
+    Ok(dsl::contest_reveals
+        .select(((dsl::user_id, dsl::problem_id), dsl::reveal_time))
+        .filter(dsl::contest_id.eq(contest_id))
+        .load(conn)?)
+}
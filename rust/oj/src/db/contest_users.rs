@@ -1,5 +1,6 @@
 //! Database operations on the `contest_users` table.
 
+use super::connection::DbConnection;
 use super::schema::contest_users::dsl;
 use super::users::User;
 use crate::error::ApiResult;
@@ -9,7 +10,7 @@ use diesel::prelude::*;
 pub fn insert_contest_users(
     contest_id: i32,
     user_ids: &[i32],
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
 ) -> ApiResult<()> {
     diesel::insert_into(dsl::contest_users)
         .values(
@@ -23,7 +24,7 @@ pub fn insert_contest_users(
 }
 
 /// Delete all users from the given contest.
-pub fn delete_contest_users(contest_id: i32, conn: &mut SqliteConnection) -> ApiResult<()> {
+pub fn delete_contest_users(contest_id: i32, conn: &mut DbConnection) -> ApiResult<()> {
     diesel::delete(dsl::contest_users)
         .filter(dsl::contest_id.eq(contest_id))
         .execute(conn)?;
@@ -31,7 +32,7 @@ pub fn delete_contest_users(contest_id: i32, conn: &mut SqliteConnection) -> Api
 }
 
 /// Get a list of all user ids in the given contest.
-pub fn get_contest_users(contest_id: i32, conn: &mut SqliteConnection) -> ApiResult<Vec<i32>> {
+pub fn get_contest_users(contest_id: i32, conn: &mut DbConnection) -> ApiResult<Vec<i32>> {
     Ok(dsl::contest_users
         .select(dsl::user_id)
         .filter(dsl::contest_id.eq(contest_id))
@@ -43,7 +44,7 @@ pub fn get_contest_users(contest_id: i32, conn: &mut SqliteConnection) -> ApiRes
 pub fn is_user_in_contest(
     contest_id: i32,
     user_id: i32,
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
 ) -> ApiResult<bool> {
     let count = dsl::contest_users
         .count()
@@ -56,7 +57,7 @@ pub fn is_user_in_contest(
 /// Get a list of all users in the given contest along with their names.
 pub fn get_contest_users_with_names(
     contest_id: i32,
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
 ) -> ApiResult<Vec<User>> {
     use super::schema::users;
     Ok(dsl::contest_users
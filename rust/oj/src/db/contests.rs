@@ -1,14 +1,18 @@
 //! Database operations on the `contests` table.
 
-use super::connection::ConnectionPool;
+use super::connection::{
+    lock, read_transaction, write_transaction, ConnectionPool, DbConnection, Lock,
+};
+use super::enums::ContestMode;
 use super::schema::contests::dsl;
 use super::users::User;
-use super::{contest_problems, contest_users, users};
+use super::{contest_problems, contest_reveals, contest_users, users};
 use crate::error::*;
 use crate::TIME_FORMAT;
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// The API response representing a contest without the id of the contest.
 #[derive(Deserialize, Serialize, Clone)]
@@ -20,6 +24,11 @@ pub struct ContestWithoutId {
     pub problem_ids: Vec<i32>,
     pub user_ids: Vec<i32>,
     submission_limit: i32,
+    #[serde(default)]
+    mode: ContestMode,
+    /// Submissions since this time are hidden in the ranklist from non-admins until revealed.
+    #[serde(default)]
+    freeze_time: Option<String>,
 }
 
 /// The API response representing a contest.
@@ -32,13 +41,15 @@ pub struct Contest {
 
 /// The metadata of the contest without problems and users in the contest.
 #[derive(Insertable, Queryable, AsChangeset)]
-#[diesel(table_name = super::schema::contests)]
-pub(super) struct ContestInfo {
+#[diesel(table_name = super::schema::contests, treat_none_as_null = true)]
+pub struct ContestInfo {
     id: i32,
     name: String,
     pub from: NaiveDateTime,
     pub to: NaiveDateTime,
     pub submission_limit: i32,
+    pub mode: ContestMode,
+    pub freeze_time: Option<NaiveDateTime>,
 }
 
 impl Contest {
@@ -52,6 +63,10 @@ impl Contest {
                 problem_ids,
                 user_ids,
                 submission_limit: info.submission_limit,
+                mode: info.mode,
+                freeze_time: info
+                    .freeze_time
+                    .map(|time| time.format(TIME_FORMAT).to_string()),
             },
         }
     }
@@ -59,30 +74,41 @@ impl Contest {
 
 /// Validate a `ContestWithoutId` and returns a `ContestInfo` with id = 0 if valid.
 /// It doesn't check duplicated users/problems or existence of problems.
-fn validate_contest(
-    contest: &ContestWithoutId,
-    conn: &mut SqliteConnection,
-) -> ApiResult<ContestInfo> {
+fn validate_contest(contest: &ContestWithoutId, conn: &mut DbConnection) -> ApiResult<ContestInfo> {
     let user_count = users::user_count(conn)?;
-    match contest
+    if let Some(id) = contest
         .user_ids
         .iter()
         .find(|&&id| id < 0 || id >= user_count)
     {
-        Some(id) => return Err(ApiError::not_found(&format!("User {}", id))),
-        None => {}
-    };
+        return Err(ApiError::not_found(&format!("User {}", id)));
+    }
+    let from = super::utils::parse_time(&contest.from, "from")?;
+    let to = super::utils::parse_time(&contest.to, "to")?;
+    let freeze_time = contest
+        .freeze_time
+        .as_ref()
+        .map(|time| super::utils::parse_time(time, "freeze_time"))
+        .transpose()?;
+    if freeze_time.is_some_and(|time| time < from || time > to) {
+        return Err(ApiError::new(
+            ApiErrorType::InvalidArgument,
+            String::from("The 'freeze_time' should be between 'from' and 'to'."),
+        ));
+    }
     Ok(ContestInfo {
         id: 0,
         name: contest.name.clone(),
-        from: super::utils::parse_time(&contest.from, "from")?,
-        to: super::utils::parse_time(&contest.to, "to")?,
+        from,
+        to,
         submission_limit: contest.submission_limit,
+        mode: contest.mode,
+        freeze_time,
     })
 }
 
 /// Get the number of contests.
-fn contest_count(conn: &mut SqliteConnection) -> ApiResult<i64> {
+fn contest_count(conn: &mut DbConnection) -> ApiResult<i64> {
     Ok(dsl::contests.count().get_result(conn)?)
 }
 
@@ -90,7 +116,8 @@ fn contest_count(conn: &mut SqliteConnection) -> ApiResult<i64> {
 /// It validates the contest but doesn't check duplicated users/problems or existence of problems.
 /// Returns `contest_id` on success.
 pub fn add_contest(contest: &ContestWithoutId, pool: &ConnectionPool) -> ApiResult<i32> {
-    pool.get()?.immediate_transaction(|conn| {
+    write_transaction(pool, |conn| {
+        lock(conn, Lock::AddContest)?;
         let mut contest_info = validate_contest(contest, conn)?;
         let id = contest_count(conn)? as i32 + 1;
         contest_info.id = id;
@@ -106,7 +133,8 @@ pub fn add_contest(contest: &ContestWithoutId, pool: &ConnectionPool) -> ApiResu
 /// Update an existing contest.
 /// It validates the contest but doesn't check duplicated users/problems or existence of problems.
 pub fn update_contest(id: i32, contest: &ContestWithoutId, pool: &ConnectionPool) -> ApiResult<()> {
-    pool.get()?.immediate_transaction(|conn| {
+    write_transaction(pool, |conn| {
+        lock(conn, Lock::Contest(id))?;
         get_contest_info(id, conn)?; // check contest existence first
         let mut contest_info = validate_contest(contest, conn)?;
         contest_info.id = id;
@@ -124,7 +152,7 @@ pub fn update_contest(id: i32, contest: &ContestWithoutId, pool: &ConnectionPool
 
 /// Get a list of all contests.
 pub fn get_all_contests(pool: &ConnectionPool) -> ApiResult<Vec<Contest>> {
-    pool.get()?.immediate_transaction(|conn| {
+    read_transaction(pool, |conn| {
         let contests_info = dsl::contests.order(dsl::id).load::<ContestInfo>(conn)?;
         let mut contests = Vec::new();
         for info in contests_info {
@@ -137,7 +165,7 @@ pub fn get_all_contests(pool: &ConnectionPool) -> ApiResult<Vec<Contest>> {
 }
 
 /// Get the contest info of a single contest. Returns not-found error if contest not found.
-pub(super) fn get_contest_info(id: i32, conn: &mut SqliteConnection) -> ApiResult<ContestInfo> {
+pub(super) fn get_contest_info(id: i32, conn: &mut DbConnection) -> ApiResult<ContestInfo> {
     match dsl::contests
         .filter(dsl::id.eq(id))
         .first::<ContestInfo>(conn)
@@ -150,7 +178,7 @@ pub(super) fn get_contest_info(id: i32, conn: &mut SqliteConnection) -> ApiResul
 
 /// Get the contest info, users and problems of a single contest.
 pub fn get_contest(id: i32, pool: &ConnectionPool) -> ApiResult<Contest> {
-    pool.get()?.immediate_transaction(|conn| {
+    read_transaction(pool, |conn| {
         let info = get_contest_info(id, conn)?;
         let user_ids = contest_users::get_contest_users(id, conn)?;
         let problem_ids = contest_problems::get_contest_problems(id, conn)?;
@@ -158,15 +186,95 @@ pub fn get_contest(id: i32, pool: &ConnectionPool) -> ApiResult<Contest> {
     })
 }
 
-/// Get the users and problems of a single contest.
-pub fn get_contest_users_and_problem_ids(
+/// Get the contest info, users and problems of a single contest.
+pub fn get_contest_info_users_and_problem_ids(
     contest_id: i32,
     pool: &ConnectionPool,
-) -> ApiResult<(Vec<User>, Vec<i32>)> {
-    pool.get()?.immediate_transaction(|conn| {
-        get_contest_info(contest_id, conn)?;
+) -> ApiResult<(ContestInfo, Vec<User>, Vec<i32>)> {
+    read_transaction(pool, |conn| {
+        let info = get_contest_info(contest_id, conn)?;
         let users = contest_users::get_contest_users_with_names(contest_id, conn)?;
         let problem_ids = contest_problems::get_contest_problems(contest_id, conn)?;
-        Ok((users, problem_ids))
+        Ok((info, users, problem_ids))
+    })
+}
+
+/// Get the (contest_id, problem_id) pairs of all contests.
+pub fn get_all_contest_problems(pool: &ConnectionPool) -> ApiResult<Vec<(i32, i32)>> {
+    contest_problems::get_all_contest_problems(&mut *pool.get()?)
+}
+
+/// The freeze of a contest, which hides the results of some submissions from non-admins.
+pub struct Freeze {
+    freeze_time: NaiveDateTime,
+    /// The time until which the submissions of each (user_id, problem_id) pair are revealed.
+    reveals: HashMap<(i32, i32), NaiveDateTime>,
+}
+
+impl Freeze {
+    /// The freeze time of the contest.
+    pub fn freeze_time(&self) -> NaiveDateTime {
+        self.freeze_time
+    }
+
+    /// Whether a submission is hidden: submitted since the freeze time, and not revealed.
+    pub fn is_hidden(&self, user_id: i32, problem_id: i32, created_time: NaiveDateTime) -> bool {
+        created_time >= self.freeze_time
+            && self
+                .reveals
+                .get(&(user_id, problem_id))
+                .is_none_or(|&reveal_time| created_time >= reveal_time)
+    }
+}
+
+/// Get the freeze of the given contest, or `None` if it has no freeze time.
+pub(super) fn get_freeze(contest_id: i32, conn: &mut DbConnection) -> ApiResult<Option<Freeze>> {
+    let freeze_time = match get_contest_info(contest_id, conn)?.freeze_time {
+        None => return Ok(None),
+        Some(freeze_time) => freeze_time,
+    };
+    Ok(Some(Freeze {
+        freeze_time,
+        reveals: contest_reveals::get_contest_reveals(contest_id, conn)?
+            .into_iter()
+            .collect(),
+    }))
+}
+
+/// Get the freezes of all contests with a freeze time, or only of the given contest, by contest id.
+pub(super) fn get_freezes(
+    contest_id: Option<i32>,
+    conn: &mut DbConnection,
+) -> ApiResult<HashMap<i32, Freeze>> {
+    let mut query = dsl::contests
+        .select(dsl::id)
+        .filter(dsl::freeze_time.is_not_null())
+        .into_boxed();
+    if let Some(contest_id) = contest_id {
+        query = query.filter(dsl::id.eq(contest_id));
+    }
+    let mut freezes = HashMap::new();
+    for id in query.load::<i32>(conn)? {
+        if let Some(freeze) = get_freeze(id, conn)? {
+            freezes.insert(id, freeze);
+        }
+    }
+    Ok(freezes)
+}
+
+/// Get the freeze of the given contest, or `None` if it has no freeze time.
+pub fn get_contest_freeze(contest_id: i32, pool: &ConnectionPool) -> ApiResult<Option<Freeze>> {
+    get_freeze(contest_id, &mut *pool.get()?)
+}
+
+/// Reveal the frozen submissions of the given (user_id, problem_id) pairs in the given contest.
+pub fn reveal_contest_submissions(
+    contest_id: i32,
+    pairs: &[(i32, i32)],
+    pool: &ConnectionPool,
+) -> ApiResult<()> {
+    write_transaction(pool, |conn| {
+        get_contest_info(contest_id, conn)?;
+        contest_reveals::insert_contest_reveals(contest_id, pairs, conn)
     })
 }
@@ -1,5 +1,7 @@
 //! Enums used in the database.
 
+#[cfg(feature = "postgres")]
+use diesel::sql_types::SqlType;
 use diesel_derive_enum::DbEnum;
 use serde::{Deserialize, Serialize};
 
@@ -7,6 +9,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, DbEnum, Serialize, Deserialize, PartialEq, Clone, Copy)]
 #[serde(rename_all = "PascalCase")]
 #[DbValueStyle = "PascalCase"]
+#[cfg_attr(
+    feature = "postgres",
+    DieselTypePath = "crate::db::enums::JobStateMapping"
+)]
 pub enum JobState {
     Queueing,
     Running,
@@ -18,6 +24,10 @@ pub enum JobState {
 #[derive(Debug, DbEnum, Serialize, Deserialize, PartialEq, Clone, Copy)]
 #[serde(rename_all = "PascalCase")]
 #[DbValueStyle = "PascalCase"]
+#[cfg_attr(
+    feature = "postgres",
+    DieselTypePath = "crate::db::enums::JobResultMapping"
+)]
 pub enum JobResult {
     Waiting,
     Running,
@@ -48,3 +58,58 @@ pub enum JobResult {
     SPJError,
     Skipped,
 }
+
+/// The role of a user.
+#[derive(Debug, DbEnum, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "PascalCase")]
+#[DbValueStyle = "PascalCase"]
+#[cfg_attr(
+    feature = "postgres",
+    DieselTypePath = "crate::db::enums::UserRoleMapping"
+)]
+pub enum UserRole {
+    Admin,
+    Contestant,
+}
+
+/// How the ranklist of a contest is computed.
+#[derive(Debug, DbEnum, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "postgres",
+    DieselTypePath = "crate::db::enums::ContestModeMapping"
+)]
+pub enum ContestMode {
+    /// Ranked by the total score.
+    #[default]
+    #[serde(rename = "IOI")]
+    #[db_rename = "IOI"]
+    Ioi,
+    /// Ranked by the number of solved problems, then the penalty time.
+    #[serde(rename = "ICPC")]
+    #[db_rename = "ICPC"]
+    Icpc,
+}
+
+/// The PostgreSQL enum type of `JobState`.
+#[cfg(feature = "postgres")]
+#[derive(SqlType)]
+#[diesel(postgres_type(name = "job_state"))]
+pub struct JobStateMapping;
+
+/// The PostgreSQL enum type of `JobResult`.
+#[cfg(feature = "postgres")]
+#[derive(SqlType)]
+#[diesel(postgres_type(name = "job_result"))]
+pub struct JobResultMapping;
+
+/// The PostgreSQL enum type of `UserRole`.
+#[cfg(feature = "postgres")]
+#[derive(SqlType)]
+#[diesel(postgres_type(name = "user_role"))]
+pub struct UserRoleMapping;
+
+/// The PostgreSQL enum type of `ContestMode`.
+#[cfg(feature = "postgres")]
+#[derive(SqlType)]
+#[diesel(postgres_type(name = "contest_mode"))]
+pub struct ContestModeMapping;
@@ -1,22 +1,41 @@
 //! Database operations on the `jobs` table.
 
 use super::case_results::{self, Case};
-use super::connection::ConnectionPool;
+use super::connection::{
+    lock, read_transaction, write_transaction, ConnectionPool, DbBackend, DbConnection, Lock,
+};
+use super::contests::{self, Freeze};
 use super::enums::{JobResult, JobState};
-use super::schema::jobs::dsl;
-use super::{contest_problems, contest_users, contests, users};
+use super::schema::jobs::{self, dsl};
+use super::schema::users::dsl as users_dsl;
+use super::subtask_results::{self, SubtaskResult};
+use super::{contest_problems, contest_users, rank_results, users};
 use crate::error::*;
+use crate::judger::queue::JobPriority;
 use crate::judger::JobAdder;
 use crate::TIME_FORMAT;
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::{Entry, HashMap};
 
-/// Get jobs with either Queueing or Running state.
+/// Get the (id, contest_id) of jobs with either Queueing or Running state.
 /// This can be used to continue running unfinished jobs at startup.
-pub fn get_unfinished_jobs(pool: &ConnectionPool) -> ApiResult<Vec<i32>> {
+pub fn get_unfinished_jobs(pool: &ConnectionPool) -> ApiResult<Vec<(i32, i32)>> {
     Ok(dsl::jobs
-        .select(dsl::id)
+        .select((dsl::id, dsl::contest_id))
+        .filter(dsl::state.eq(JobState::Queueing))
+        .or_filter(dsl::state.eq(JobState::Running))
+        .order(dsl::id)
+        .load(&mut pool.get()?)?)
+}
+
+/// Get the (id, problem_id, language) of jobs with either Queueing or Running state.
+pub fn get_unfinished_job_problems_and_languages(
+    pool: &ConnectionPool,
+) -> ApiResult<Vec<(i32, i32, String)>> {
+    Ok(dsl::jobs
+        .select((dsl::id, dsl::problem_id, dsl::language))
         .filter(dsl::state.eq(JobState::Queueing))
         .or_filter(dsl::state.eq(JobState::Running))
         .order(dsl::id)
@@ -24,7 +43,7 @@ pub fn get_unfinished_jobs(pool: &ConnectionPool) -> ApiResult<Vec<i32>> {
 }
 
 /// Job info that is useful for the judger.
-#[derive(Queryable)]
+#[derive(Queryable, Serialize, Deserialize)]
 pub struct JobInfoForJudger {
     pub source_code: String,
     pub language: String,
@@ -34,7 +53,8 @@ pub struct JobInfoForJudger {
 /// Returns `Some(JobInfoForJudger)` and set state to "Running" if job is not canceled;
 /// returns `None` and do no update if job is canceled.
 pub fn fetch_job_for_judger(id: i32, pool: &ConnectionPool) -> ApiResult<Option<JobInfoForJudger>> {
-    pool.get()?.immediate_transaction(|conn| {
+    write_transaction(pool, |conn| {
+        lock(conn, Lock::Job(id))?;
         let state = dsl::jobs
             .select(dsl::state)
             .filter(dsl::id.eq(id))
@@ -58,21 +78,54 @@ pub fn fetch_job_for_judger(id: i32, pool: &ConnectionPool) -> ApiResult<Option<
 }
 
 /// Set job state to finished and set other fields in the parameters.
-pub fn finish_job(id: i32, result: &JobResult, score: f64, pool: &ConnectionPool) -> ApiResult<()> {
-    diesel::update(dsl::jobs)
-        .filter(dsl::id.eq(id))
-        .set((
-            dsl::updated_time.eq(Utc::now().naive_utc()),
-            dsl::state.eq(JobState::Finished),
-            dsl::result.eq(result),
-            dsl::score.eq(score),
-        ))
-        .execute(&mut pool.get()?)?;
-    Ok(())
+pub fn finish_job(
+    id: i32,
+    result: &JobResult,
+    score: f64,
+    subtasks: &[SubtaskResult],
+    pool: &ConnectionPool,
+) -> ApiResult<()> {
+    write_transaction(pool, |conn| {
+        diesel::update(dsl::jobs)
+            .filter(dsl::id.eq(id))
+            .set((
+                dsl::updated_time.eq(Utc::now().naive_utc()),
+                dsl::state.eq(JobState::Finished),
+                dsl::result.eq(result),
+                dsl::score.eq(score),
+            ))
+            .execute(conn)?;
+        rank_results::update_rank_results(id, conn)?;
+        subtask_results::set_subtask_results(id, subtasks, conn)
+    })
 }
 
-/// Update the score of a job.
-pub(super) fn update_score(id: i32, score: f64, conn: &mut SqliteConnection) -> ApiResult<()> {
+/// Set a running job back to "Queueing" and reset its result, so that it can be judged again.
+/// Returns whether the job was running, e.g. it's not requeued if it has been canceled.
+pub fn requeue_job(id: i32, pool: &ConnectionPool) -> ApiResult<bool> {
+    write_transaction(pool, |conn| {
+        let count = diesel::update(dsl::jobs)
+            .filter(dsl::id.eq(id))
+            .filter(dsl::state.eq(JobState::Running))
+            .set((
+                dsl::updated_time.eq(Utc::now().naive_utc()),
+                dsl::state.eq(JobState::Queueing),
+                dsl::result.eq(JobResult::Waiting),
+                dsl::score.eq(0.0),
+            ))
+            .execute(conn)?;
+        if count == 0 {
+            return Ok(false);
+        }
+        rank_results::update_rank_results(id, conn)?;
+        case_results::reinit_cases(id, conn)?;
+        subtask_results::delete_subtask_results(id, conn)?;
+        Ok(true)
+    })
+}
+
+/// Update the score of a running job. The rank results are updated when the job is finished.
+pub(super) fn update_score(id: i32, score: f64, conn: &mut DbConnection) -> ApiResult<()> {
     diesel::update(dsl::jobs)
         .filter(dsl::id.eq(id))
         .set((
@@ -88,14 +141,14 @@ pub(super) fn update_score(id: i32, score: f64, conn: &mut SqliteConnection) ->
 #[diesel(table_name = super::schema::jobs)]
 pub struct JobInfo {
     pub id: i32,
-    created_time: NaiveDateTime,
+    pub created_time: NaiveDateTime,
     updated_time: NaiveDateTime,
-    source_code: String,
-    language: String,
+    pub source_code: String,
+    pub language: String,
     pub user_id: i32,
-    contest_id: i32,
+    pub contest_id: i32,
     pub problem_id: i32,
-    state: JobState,
+    pub state: JobState,
     pub result: JobResult,
     pub score: f64,
 }
@@ -105,7 +158,9 @@ pub struct JobInfo {
 pub struct Submission {
     source_code: String,
     pub language: String,
-    user_id: i32,
+    /// Derived from the identity of the sender when authentication is enabled.
+    #[serde(default)]
+    pub user_id: i32,
     contest_id: i32,
     pub problem_id: i32,
 }
@@ -121,10 +176,14 @@ pub struct Job {
     result: JobResult,
     score: f64,
     cases: Vec<Case>,
+    /// The score of each subtask, which is empty until the job is finished.
+    subtasks: Vec<SubtaskResult>,
+    #[serde(skip)]
+    hidden: bool,
 }
 
 impl Job {
-    fn new(info: JobInfo, cases: Vec<Case>) -> Self {
+    fn new(info: JobInfo, cases: Vec<Case>, subtasks: Vec<SubtaskResult>) -> Self {
         Self {
             id: info.id,
             created_time: info.created_time.format(TIME_FORMAT).to_string(),
@@ -140,15 +199,41 @@ impl Job {
             result: info.result,
             score: info.score,
             cases,
+            subtasks,
+            hidden: false,
         }
     }
+
+    /// Hide the result as if the job is waiting to be judged.
+    fn hide(&mut self) {
+        self.result = JobResult::Waiting;
+        self.score = 0.0;
+        self.cases.iter_mut().for_each(Case::clear);
+        self.subtasks.clear();
+        self.hidden = true;
+    }
+
+    /// Whether the result is hidden by the freeze of the contest.
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// The user who submitted the job.
+    pub fn user_id(&self) -> i32 {
+        self.submission.user_id
+    }
+
+    /// Whether the job won't change until it's rejudged.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, JobState::Finished | JobState::Canceled)
+    }
 }
 
 /// Check a submission against a contest.
 fn check_contest_submission(
     submission: &Submission,
     now: NaiveDateTime,
-    conn: &mut SqliteConnection,
+    conn: &mut DbConnection,
 ) -> ApiResult<()> {
     let Submission {
         user_id,
@@ -208,7 +293,10 @@ pub fn add_job(
     pool: &ConnectionPool,
     adder: &JobAdder,
 ) -> ApiResult<Job> {
-    pool.get()?.immediate_transaction(|conn| {
+    // The job is added to the queue after the transaction is committed, so that the judger can
+    // find it.
+    let job = write_transaction(pool, |conn| {
+        lock(conn, Lock::AddJob)?;
         let user_count = users::user_count(conn)?;
         if submission.user_id < 0 || submission.user_id >= user_count {
             return Err(ApiError::not_found(&format!("User {}", submission.user_id)));
@@ -234,14 +322,19 @@ pub fn add_job(
         diesel::insert_into(dsl::jobs)
             .values(&job_info)
             .execute(conn)?;
+        rank_results::update_rank_results(id, conn)?;
         let cases = case_results::init_cases(id, case_count, conn)?;
-        adder.add_job(job_info.id)?;
-        Ok(Job::new(job_info, cases))
-    })
+        Ok(Job::new(job_info, cases, Vec::new()))
+    })?;
+    adder.add_job(
+        job.id,
+        JobPriority::of_submission(job.submission.contest_id),
+    )?;
+    Ok(job)
 }
 
 /// Get a single job info.
-fn get_job_info(id: i32, conn: &mut SqliteConnection) -> ApiResult<JobInfo> {
+fn get_job_info(id: i32, conn: &mut DbConnection) -> ApiResult<JobInfo> {
     let job_info = dsl::jobs.filter(dsl::id.eq(id)).first(conn).optional()?;
     match job_info {
         Some(info) => Ok(info),
@@ -249,15 +342,59 @@ fn get_job_info(id: i32, conn: &mut SqliteConnection) -> ApiResult<JobInfo> {
     }
 }
 
+/// Get the id of the user who submitted a job.
+pub fn get_job_user_id(id: i32, pool: &ConnectionPool) -> ApiResult<i32> {
+    Ok(get_job_info(id, &mut *pool.get()?)?.user_id)
+}
+
 /// Get a job with case results.
-pub fn get_job(id: i32, pool: &ConnectionPool) -> ApiResult<Job> {
-    pool.get()?.immediate_transaction(|conn| {
+///
+/// * `hide_frozen`: whether to hide the result if it's hidden by the freeze of the contest.
+pub fn get_job(id: i32, hide_frozen: bool, pool: &ConnectionPool) -> ApiResult<Job> {
+    read_transaction(pool, |conn| {
         let job_info = get_job_info(id, conn)?;
+        let hidden = hide_frozen
+            && is_frozen(
+                (
+                    job_info.contest_id,
+                    job_info.user_id,
+                    job_info.problem_id,
+                    job_info.created_time,
+                ),
+                &mut HashMap::new(),
+                conn,
+            )?;
         let cases = case_results::get_cases(id, conn)?;
-        Ok(Job::new(job_info, cases))
+        let subtasks = subtask_results::get_subtask_results(id, conn)?;
+        let mut job = Job::new(job_info, cases, subtasks);
+        if hidden {
+            job.hide();
+        }
+        Ok(job)
     })
 }
 
+/// Whether the result of a job, given as (contest_id, user_id, problem_id, created_time), is
+/// hidden from non-admins by the freeze of its contest.
+///
+/// The freezes of contests are cached in `freezes`.
+fn is_frozen(
+    (contest_id, user_id, problem_id, created_time): (i32, i32, i32, NaiveDateTime),
+    freezes: &mut HashMap<i32, Option<Freeze>>,
+    conn: &mut DbConnection,
+) -> ApiResult<bool> {
+    if contest_id == 0 {
+        return Ok(false);
+    }
+    let freeze = match freezes.entry(contest_id) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(contests::get_freeze(contest_id, conn)?),
+    };
+    Ok(freeze
+        .as_ref()
+        .is_some_and(|freeze| freeze.is_hidden(user_id, problem_id, created_time)))
+}
+
 /// The job filters in the API query params.
 #[derive(Deserialize, Queryable)]
 pub struct JobFilter {
@@ -273,53 +410,316 @@ pub struct JobFilter {
     pub result: Option<JobResult>,
 }
 
-/// Get a list of jobs under the given filter.
-pub fn get_jobs(filter: &JobFilter, pool: &ConnectionPool) -> ApiResult<Vec<Job>> {
-    pool.get()?.immediate_transaction(|conn| {
-        let mut query = dsl::jobs.order(dsl::id).into_boxed();
-        if let Some(user_id) = filter.user_id {
-            query = query.filter(dsl::user_id.eq(user_id));
+/// The column to sort jobs by.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum JobSortKey {
+    #[default]
+    Id,
+    CreatedTime,
+    UpdatedTime,
+    Score,
+}
+
+/// The sort order of jobs.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// The pagination, sorting and format options of a job list in the API query params.
+#[derive(Deserialize)]
+pub struct JobListOptions {
+    /// The page number starting from 1, used with `per_page`.
+    #[serde(default = "default_page")]
+    pub page: i64,
+    /// The number of jobs in a page. All jobs are returned if it's not set.
+    pub per_page: Option<i64>,
+    #[serde(default)]
+    pub sort_by: JobSortKey,
+    #[serde(default)]
+    pub order: SortOrder,
+    /// Omit the source code and cases of jobs.
+    #[serde(default)]
+    pub summary: bool,
+}
+
+fn default_page() -> i64 {
+    1
+}
+
+/// The API response of a job without the source code and cases.
+#[derive(Serialize)]
+pub struct JobSummary {
+    id: i32,
+    created_time: String,
+    updated_time: String,
+    submission: SubmissionSummary,
+    state: JobState,
+    result: JobResult,
+    score: f64,
+}
+
+/// The API request of a submission without the source code.
+#[derive(Serialize)]
+struct SubmissionSummary {
+    language: String,
+    user_id: i32,
+    contest_id: i32,
+    problem_id: i32,
+}
+
+/// The columns of a job in the database without the source code.
+#[derive(Queryable)]
+struct JobSummaryInfo {
+    id: i32,
+    created_time: NaiveDateTime,
+    updated_time: NaiveDateTime,
+    language: String,
+    user_id: i32,
+    contest_id: i32,
+    problem_id: i32,
+    state: JobState,
+    result: JobResult,
+    score: f64,
+}
+
+impl JobSummary {
+    fn new(info: JobSummaryInfo) -> Self {
+        Self {
+            id: info.id,
+            created_time: info.created_time.format(TIME_FORMAT).to_string(),
+            updated_time: info.updated_time.format(TIME_FORMAT).to_string(),
+            submission: SubmissionSummary {
+                language: info.language,
+                user_id: info.user_id,
+                contest_id: info.contest_id,
+                problem_id: info.problem_id,
+            },
+            state: info.state,
+            result: info.result,
+            score: info.score,
         }
-        if let Some(contest_id) = filter.contest_id {
-            query = query.filter(dsl::contest_id.eq(contest_id));
+    }
+
+    /// Hide the result as if the job is waiting to be judged.
+    fn hide(&mut self) {
+        self.result = JobResult::Waiting;
+        self.score = 0.0;
+    }
+}
+
+/// A page of jobs, either full jobs or summaries depending on `JobListOptions::summary`.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum JobList {
+    Full(Vec<Job>),
+    Summary(Vec<JobSummary>),
+}
+
+/// Get the ids of the jobs hidden by the given freezes, only among the jobs of `user_id` if it's
+/// set.
+fn get_hidden_job_ids(
+    freezes: &HashMap<i32, Freeze>,
+    user_id: Option<i32>,
+    conn: &mut DbConnection,
+) -> ApiResult<Vec<i32>> {
+    let mut ids = Vec::new();
+    for (&contest_id, freeze) in freezes {
+        let mut query = dsl::jobs
+            .select((dsl::id, dsl::user_id, dsl::problem_id, dsl::created_time))
+            .filter(dsl::contest_id.eq(contest_id))
+            .filter(dsl::created_time.ge(freeze.freeze_time()))
+            .into_boxed();
+        if let Some(user_id) = user_id {
+            query = query.filter(dsl::user_id.eq(user_id));
         }
-        if let Some(problem_id) = filter.problem_id {
-            query = query.filter(dsl::problem_id.eq(problem_id));
+        for (id, user_id, problem_id, created_time) in
+            query.load::<(i32, i32, i32, NaiveDateTime)>(conn)?
+        {
+            if freeze.is_hidden(user_id, problem_id, created_time) {
+                ids.push(id);
+            }
         }
-        if let Some(language) = &filter.language {
-            query = query.filter(dsl::language.eq(language));
+    }
+    Ok(ids)
+}
+
+/// Build the query of jobs under the given filter without ordering.
+///
+/// The jobs in `hidden` are filtered by their hidden result `Waiting`.
+fn filter_jobs<'a>(
+    filter: &'a JobFilter,
+    hidden: &[i32],
+) -> ApiResult<jobs::BoxedQuery<'a, DbBackend>> {
+    let mut query = dsl::jobs.into_boxed();
+    if let Some(user_id) = filter.user_id {
+        query = query.filter(dsl::user_id.eq(user_id));
+    }
+    if let Some(user_name) = &filter.user_name {
+        query = query.filter(
+            dsl::user_id.eq_any(
+                users_dsl::users
+                    .select(users_dsl::id)
+                    .filter(users_dsl::name.eq(user_name)),
+            ),
+        );
+    }
+    if let Some(contest_id) = filter.contest_id {
+        query = query.filter(dsl::contest_id.eq(contest_id));
+    }
+    if let Some(problem_id) = filter.problem_id {
+        query = query.filter(dsl::problem_id.eq(problem_id));
+    }
+    if let Some(language) = &filter.language {
+        query = query.filter(dsl::language.eq(language));
+    }
+    if let Some(from) = &filter.from {
+        query = query.filter(dsl::created_time.ge(super::utils::parse_time(from, "from")?));
+    }
+    if let Some(to) = &filter.to {
+        query = query.filter(dsl::created_time.le(super::utils::parse_time(to, "to")?));
+    }
+    if let Some(state) = filter.state {
+        query = query.filter(dsl::state.eq(state));
+    }
+    if let Some(result) = filter.result {
+        query = if hidden.is_empty() {
+            query.filter(dsl::result.eq(result))
+        } else if result == JobResult::Waiting {
+            query.filter(dsl::result.eq(result).or(dsl::id.eq_any(hidden.to_vec())))
+        } else {
+            query.filter(dsl::result.eq(result).and(dsl::id.ne_all(hidden.to_vec())))
+        };
+    }
+    Ok(query)
+}
+
+/// Build the query of a page of jobs under the given filter and options.
+///
+/// The jobs in `hidden` are sorted as the lowest scores.
+fn filter_jobs_page<'a>(
+    filter: &'a JobFilter,
+    options: &JobListOptions,
+    hidden: &[i32],
+) -> ApiResult<jobs::BoxedQuery<'a, DbBackend>> {
+    let mut query = filter_jobs(filter, hidden)?;
+    let hidden = (!hidden.is_empty()).then(|| dsl::id.eq_any(hidden.to_vec()));
+    // Sort by id at last to break ties.
+    query = match (options.sort_by, options.order, hidden) {
+        (JobSortKey::Score, SortOrder::Asc, Some(hidden)) => {
+            query.order((hidden.desc(), dsl::score.asc(), dsl::id.asc()))
         }
-        if let Some(from) = &filter.from {
-            query = query.filter(dsl::created_time.ge(super::utils::parse_time(from, "from")?));
+        (JobSortKey::Score, SortOrder::Desc, Some(hidden)) => {
+            query.order((hidden.asc(), dsl::score.desc(), dsl::id.desc()))
         }
-        if let Some(to) = &filter.to {
-            query = query.filter(dsl::created_time.le(super::utils::parse_time(to, "to")?));
+        (JobSortKey::Id, SortOrder::Asc, _) => query.order(dsl::id.asc()),
+        (JobSortKey::Id, SortOrder::Desc, _) => query.order(dsl::id.desc()),
+        (JobSortKey::CreatedTime, SortOrder::Asc, _) => {
+            query.order((dsl::created_time.asc(), dsl::id.asc()))
         }
-        if let Some(state) = filter.state {
-            query = query.filter(dsl::state.eq(state));
+        (JobSortKey::CreatedTime, SortOrder::Desc, _) => {
+            query.order((dsl::created_time.desc(), dsl::id.desc()))
         }
-        if let Some(result) = filter.result {
-            query = query.filter(dsl::result.eq(result));
+        (JobSortKey::UpdatedTime, SortOrder::Asc, _) => {
+            query.order((dsl::updated_time.asc(), dsl::id.asc()))
         }
-        if let Some(user_name) = &filter.user_name {
-            match users::get_user_id(user_name, conn)? {
-                None => return Ok(Vec::new()),
-                Some(user_id) => query = query.filter(dsl::user_id.eq(user_id)),
-            }
+        (JobSortKey::UpdatedTime, SortOrder::Desc, _) => {
+            query.order((dsl::updated_time.desc(), dsl::id.desc()))
         }
-        let jobs_info = query.load::<JobInfo>(conn)?;
-        let mut jobs = Vec::new();
-        for info in jobs_info {
-            let cases = case_results::get_cases(info.id, conn)?;
-            jobs.push(Job::new(info, cases));
+        (JobSortKey::Score, SortOrder::Asc, _) => query.order((dsl::score.asc(), dsl::id.asc())),
+        (JobSortKey::Score, SortOrder::Desc, _) => query.order((dsl::score.desc(), dsl::id.desc())),
+    };
+    if let Some(per_page) = options.per_page {
+        if per_page < 1 || options.page < 1 {
+            return Err(ApiError::new(
+                ApiErrorType::InvalidArgument,
+                String::from("Both 'page' and 'per_page' should be positive."),
+            ));
         }
-        Ok(jobs)
+        query = query
+            .limit(per_page)
+            .offset((options.page - 1).saturating_mul(per_page));
+    }
+    Ok(query)
+}
+
+/// Get a page of jobs under the given filter, and the total number of jobs under the filter.
+///
+/// * `hide_frozen`: whether to hide the results of the jobs hidden by the freeze of the contests.
+pub fn get_jobs(
+    filter: &JobFilter,
+    options: &JobListOptions,
+    hide_frozen: bool,
+    pool: &ConnectionPool,
+) -> ApiResult<(JobList, i64)> {
+    read_transaction(pool, |conn| {
+        let hidden = match hide_frozen {
+            true => {
+                let freezes = contests::get_freezes(filter.contest_id, conn)?;
+                get_hidden_job_ids(&freezes, filter.user_id, conn)?
+            }
+            false => Vec::new(),
+        };
+        let total_count = filter_jobs(filter, &hidden)?.count().get_result(conn)?;
+        let jobs = if options.summary {
+            let jobs_info = filter_jobs_page(filter, options, &hidden)?
+                .select((
+                    dsl::id,
+                    dsl::created_time,
+                    dsl::updated_time,
+                    dsl::language,
+                    dsl::user_id,
+                    dsl::contest_id,
+                    dsl::problem_id,
+                    dsl::state,
+                    dsl::result,
+                    dsl::score,
+                ))
+                .load::<JobSummaryInfo>(conn)?;
+            let mut jobs = Vec::new();
+            for info in jobs_info {
+                let is_hidden = hidden.contains(&info.id);
+                let mut job = JobSummary::new(info);
+                if is_hidden {
+                    job.hide();
+                }
+                jobs.push(job);
+            }
+            JobList::Summary(jobs)
+        } else {
+            let jobs_info = filter_jobs_page(filter, options, &hidden)?.load::<JobInfo>(conn)?;
+            // Get the cases of all jobs in the page in a single query.
+            let mut cases =
+                case_results::get_cases_of_jobs(filter_jobs_page(filter, options, &hidden)?, conn)?;
+            let mut subtasks = subtask_results::get_subtask_results_of_jobs(
+                filter_jobs_page(filter, options, &hidden)?,
+                conn,
+            )?;
+            let mut jobs = Vec::new();
+            for info in jobs_info {
+                let is_hidden = hidden.contains(&info.id);
+                let job_cases = cases.remove(&info.id).unwrap_or_default();
+                let job_subtasks = subtasks.remove(&info.id).unwrap_or_default();
+                let mut job = Job::new(info, job_cases, job_subtasks);
+                if is_hidden {
+                    job.hide();
+                }
+                jobs.push(job);
+            }
+            JobList::Full(jobs)
+        };
+        Ok((jobs, total_count))
     })
 }
 
 /// Rejudge a single job.
 pub fn rejudge(id: i32, adder: &JobAdder, pool: &ConnectionPool) -> ApiResult<Job> {
-    let (job_info, cases) = pool.get()?.immediate_transaction(|conn| {
+    let (job_info, cases) = write_transaction(pool, |conn| {
+        lock(conn, Lock::Job(id))?;
         let mut job_info = get_job_info(id, conn)?;
         if job_info.state != JobState::Finished {
             return Err(ApiError::new(
@@ -335,17 +735,20 @@ pub fn rejudge(id: i32, adder: &JobAdder, pool: &ConnectionPool) -> ApiResult<Jo
             .filter(dsl::id.eq(id))
             .set(&job_info)
             .execute(conn)?;
+        rank_results::update_rank_results(id, conn)?;
         case_results::reinit_cases(id, conn)?;
+        subtask_results::delete_subtask_results(id, conn)?;
         let cases = case_results::get_cases(id, conn)?;
         Ok((job_info, cases))
     })?;
-    adder.add_job(id)?;
-    Ok(Job::new(job_info, cases))
+    adder.add_job(id, JobPriority::Rejudge)?;
+    Ok(Job::new(job_info, cases, Vec::new()))
 }
 
 /// Cancel a single job if it's queueing.
 pub fn cancel_job(id: i32, pool: &ConnectionPool) -> ApiResult<()> {
-    pool.get()?.immediate_transaction(|conn| {
+    write_transaction(pool, |conn| {
+        lock(conn, Lock::Job(id))?;
         let job_info = get_job_info(id, conn)?;
         if job_info.state != JobState::Queueing {
             return Err(ApiError::new(
@@ -357,7 +760,7 @@ pub fn cancel_job(id: i32, pool: &ConnectionPool) -> ApiResult<()> {
             .filter(dsl::id.eq(id))
             .set(dsl::state.eq(JobState::Canceled))
             .execute(conn)?;
-        Ok(())
+        rank_results::update_rank_results(id, conn)
     })
 }
 
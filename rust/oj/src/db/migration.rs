@@ -3,7 +3,10 @@
 use super::connection::ConnectionPool;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+#[cfg(feature = "sqlite")]
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+#[cfg(feature = "postgres")]
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
 
 pub fn initialize_database(delete_data: bool, pool: &ConnectionPool) {
     let mut conn = pool.get().expect("failed to connect to database");
@@ -13,4 +16,7 @@ pub fn initialize_database(delete_data: bool, pool: &ConnectionPool) {
     }
     conn.run_pending_migrations(MIGRATIONS)
         .expect("failed to run migrations");
+    drop(conn);
+    super::rank_results::initialize_rank_results(pool)
+        .expect("failed to initialize the rank results");
 }
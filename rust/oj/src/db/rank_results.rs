@@ -0,0 +1,376 @@
+//! Database operations on the `rank_results` table.
+//!
+//! It summarizes the jobs of each user on each problem in each contest, and is updated in the same
+//! transaction whenever a job is added, finished, requeued, rejudged or canceled, so that the
+//! ranklist doesn't need to go through all jobs. Running jobs are not updated on each case, so
+//! their partial scores are not in the summary. The jobs of all contests are also summarized in
+//! contest 0.
+
+use super::connection::{
+    lock, read_transaction, write_transaction, ConnectionPool, DbConnection, Lock,
+};
+use super::contests::Freeze;
+use super::enums::{JobResult, JobState};
+use super::schema::jobs::dsl as jobs_dsl;
+use super::schema::rank_results::dsl;
+use crate::error::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The number of rows inserted in a single query, to stay below the limit of bound parameters.
+const INSERT_CHUNK_SIZE: usize = 50;
+
+/// The columns of a job used in the summary.
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = super::schema::jobs)]
+struct RankJob {
+    id: i32,
+    created_time: NaiveDateTime,
+    contest_id: i32,
+    user_id: i32,
+    problem_id: i32,
+    state: JobState,
+    result: JobResult,
+    score: f64,
+}
+
+/// The summary of the jobs of a user on a problem in a contest.
+#[derive(Queryable, Insertable)]
+#[diesel(table_name = super::schema::rank_results)]
+pub struct RankResult {
+    pub contest_id: i32,
+    pub user_id: i32,
+    pub problem_id: i32,
+    pub submission_count: i32,
+    pub latest_job_id: i32,
+    pub latest_score: f64,
+    /// The job with the highest score, the earliest one on tie.
+    pub highest_job_id: i32,
+    pub highest_score: f64,
+    /// The latest job with the "Accepted" result.
+    pub accepted_job_id: Option<i32>,
+    pub accepted_score: Option<f64>,
+    /// The first finished and accepted job, used in ICPC mode.
+    pub first_accepted_job_id: Option<i32>,
+    pub first_accepted_time: Option<NaiveDateTime>,
+    /// The number of rejected jobs before the first accepted one, or all rejected jobs if none is
+    /// accepted, used in ICPC mode.
+    pub rejected_count: i32,
+}
+
+/// Whether a job counts as a rejected attempt in ICPC mode. Pending jobs, compilation errors and
+/// system errors don't count.
+fn is_rejected(job: &RankJob) -> bool {
+    job.state == JobState::Finished
+        && !matches!(
+            job.result,
+            JobResult::Accepted | JobResult::CompilationError | JobResult::SystemError
+        )
+}
+
+impl RankResult {
+    /// Summarize the jobs of a user on a problem, which must be non-empty and sorted by id.
+    fn new(contest_id: i32, jobs: &[&RankJob]) -> Self {
+        let first = jobs[0];
+        let mut result = Self {
+            contest_id,
+            user_id: first.user_id,
+            problem_id: first.problem_id,
+            submission_count: jobs.len() as i32,
+            latest_job_id: first.id,
+            latest_score: first.score,
+            highest_job_id: first.id,
+            highest_score: first.score,
+            accepted_job_id: None,
+            accepted_score: None,
+            first_accepted_job_id: None,
+            first_accepted_time: None,
+            rejected_count: 0,
+        };
+        for job in jobs {
+            result.latest_job_id = job.id;
+            result.latest_score = job.score;
+            // Scores within a small difference are considered equal.
+            if job.score - result.highest_score >= 1e-10 {
+                result.highest_job_id = job.id;
+                result.highest_score = job.score;
+            }
+            if job.result == JobResult::Accepted {
+                result.accepted_job_id = Some(job.id);
+                result.accepted_score = Some(job.score);
+            }
+            if result.first_accepted_job_id.is_none() {
+                if job.state == JobState::Finished && job.result == JobResult::Accepted {
+                    result.first_accepted_job_id = Some(job.id);
+                    result.first_accepted_time = Some(job.created_time);
+                } else if is_rejected(job) {
+                    result.rejected_count += 1;
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Summarize the jobs of each user on each problem as results of the given contest.
+fn summarize<'a>(contest_id: i32, jobs: impl Iterator<Item = &'a RankJob>) -> Vec<RankResult> {
+    let mut groups = BTreeMap::<(i32, i32), Vec<&RankJob>>::new();
+    for job in jobs {
+        groups
+            .entry((job.user_id, job.problem_id))
+            .or_default()
+            .push(job);
+    }
+    groups
+        .into_values()
+        .map(|mut jobs| {
+            jobs.sort_unstable_by_key(|job| job.id);
+            RankResult::new(contest_id, &jobs)
+        })
+        .collect()
+}
+
+fn insert_rank_results(results: &[RankResult], conn: &mut DbConnection) -> ApiResult<()> {
+    for chunk in results.chunks(INSERT_CHUNK_SIZE) {
+        diesel::insert_into(dsl::rank_results)
+            .values(chunk)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Update the results of the user on the problem of the given job, in the contest of the job and
+/// in contest 0. It should be called after the job is added or updated.
+pub(super) fn update_rank_results(job_id: i32, conn: &mut DbConnection) -> ApiResult<()> {
+    let (contest_id, user_id, problem_id) = jobs_dsl::jobs
+        .select((
+            jobs_dsl::contest_id,
+            jobs_dsl::user_id,
+            jobs_dsl::problem_id,
+        ))
+        .filter(jobs_dsl::id.eq(job_id))
+        .first::<(i32, i32, i32)>(conn)?;
+    lock(conn, Lock::RankResults(user_id))?;
+    let jobs = jobs_dsl::jobs
+        .select(RankJob::as_select())
+        .filter(jobs_dsl::user_id.eq(user_id))
+        .filter(jobs_dsl::problem_id.eq(problem_id))
+        .load::<RankJob>(conn)?;
+
+    let mut contest_ids = vec![0];
+    if contest_id != 0 {
+        contest_ids.push(contest_id);
+    }
+    for contest_id in contest_ids {
+        diesel::delete(dsl::rank_results)
+            .filter(dsl::contest_id.eq(contest_id))
+            .filter(dsl::user_id.eq(user_id))
+            .filter(dsl::problem_id.eq(problem_id))
+            .execute(conn)?;
+        let results = summarize(
+            contest_id,
+            jobs.iter()
+                .filter(|job| contest_id == 0 || job.contest_id == contest_id),
+        );
+        insert_rank_results(&results, conn)?;
+    }
+    Ok(())
+}
+
+/// Summarize all jobs if the table is empty, e.g. when it's just created for an existing database.
+pub fn initialize_rank_results(pool: &ConnectionPool) -> ApiResult<()> {
+    write_transaction(pool, |conn| {
+        let result_count = dsl::rank_results.count().get_result::<i64>(conn)?;
+        let job_count = jobs_dsl::jobs.count().get_result::<i64>(conn)?;
+        if result_count > 0 || job_count == 0 {
+            return Ok(());
+        }
+
+        let jobs = jobs_dsl::jobs
+            .select(RankJob::as_select())
+            .load::<RankJob>(conn)?;
+        let mut contests = BTreeMap::<i32, Vec<&RankJob>>::new();
+        for job in jobs.iter().filter(|job| job.contest_id != 0) {
+            contests.entry(job.contest_id).or_default().push(job);
+        }
+        insert_rank_results(&summarize(0, jobs.iter()), conn)?;
+        for (contest_id, jobs) in contests {
+            insert_rank_results(&summarize(contest_id, jobs.into_iter()), conn)?;
+        }
+        Ok(())
+    })
+}
+
+/// Get the results of all users on all problems in the given contest.
+pub fn get_rank_results(contest_id: i32, pool: &ConnectionPool) -> ApiResult<Vec<RankResult>> {
+    Ok(dsl::rank_results
+        .filter(dsl::contest_id.eq(contest_id))
+        .load(&mut pool.get()?)?)
+}
+
+/// The number of hidden jobs of each (user_id, problem_id) pair.
+pub type HiddenCount = HashMap<(i32, i32), u32>;
+
+/// Get the results of all users on all problems in the given contest, excluding the jobs hidden by
+/// the freeze of the contest.
+///
+/// Only the (user_id, problem_id) pairs with hidden jobs are summarized again from their visible
+/// jobs. Returns the results and the number of hidden jobs of each such pair.
+pub fn get_frozen_rank_results(
+    contest_id: i32,
+    freeze: &Freeze,
+    pool: &ConnectionPool,
+) -> ApiResult<(Vec<RankResult>, HiddenCount)> {
+    read_transaction(pool, |conn| {
+        let mut results = dsl::rank_results
+            .filter(dsl::contest_id.eq(contest_id))
+            .load::<RankResult>(conn)?;
+
+        // Only the jobs since the freeze time can be hidden.
+        let mut hidden = HashMap::new();
+        let frozen_jobs = jobs_dsl::jobs
+            .select(RankJob::as_select())
+            .filter(jobs_dsl::contest_id.eq(contest_id))
+            .filter(jobs_dsl::created_time.ge(freeze.freeze_time()))
+            .load::<RankJob>(conn)?;
+        for job in frozen_jobs {
+            if freeze.is_hidden(job.user_id, job.problem_id, job.created_time) {
+                *hidden.entry((job.user_id, job.problem_id)).or_insert(0) += 1;
+            }
+        }
+        if hidden.is_empty() {
+            return Ok((results, hidden));
+        }
+
+        let user_ids = hidden
+            .keys()
+            .map(|&(user_id, _)| user_id)
+            .collect::<HashSet<_>>();
+        let problem_ids = hidden
+            .keys()
+            .map(|&(_, problem_id)| problem_id)
+            .collect::<HashSet<_>>();
+        let jobs = jobs_dsl::jobs
+            .select(RankJob::as_select())
+            .filter(jobs_dsl::contest_id.eq(contest_id))
+            .filter(jobs_dsl::user_id.eq_any(user_ids))
+            .filter(jobs_dsl::problem_id.eq_any(problem_ids))
+            .load::<RankJob>(conn)?;
+        results.retain(|result| !hidden.contains_key(&(result.user_id, result.problem_id)));
+        results.extend(summarize(
+            contest_id,
+            jobs.iter().filter(|job| {
+                hidden.contains_key(&(job.user_id, job.problem_id))
+                    && !freeze.is_hidden(job.user_id, job.problem_id, job.created_time)
+            }),
+        ));
+        Ok((results, hidden))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn job(id: i32, state: JobState, result: JobResult, score: f64) -> RankJob {
+        RankJob {
+            id,
+            created_time: NaiveDateTime::default() + Duration::minutes(id as i64),
+            contest_id: 1,
+            user_id: 2,
+            problem_id: 3,
+            state,
+            result,
+            score,
+        }
+    }
+
+    fn finished(id: i32, result: JobResult, score: f64) -> RankJob {
+        job(id, JobState::Finished, result, score)
+    }
+
+    fn summarize_one(jobs: &[RankJob]) -> RankResult {
+        RankResult::new(1, &jobs.iter().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn latest_and_highest() {
+        let result = summarize_one(&[
+            finished(0, JobResult::WrongAnswer, 40.0),
+            finished(1, JobResult::WrongAnswer, 60.0),
+            finished(2, JobResult::WrongAnswer, 60.0 + 1e-12),
+            finished(3, JobResult::WrongAnswer, 20.0),
+        ]);
+        assert_eq!(
+            (result.contest_id, result.user_id, result.problem_id),
+            (1, 2, 3)
+        );
+        assert_eq!(result.submission_count, 4);
+        assert_eq!((result.latest_job_id, result.latest_score), (3, 20.0));
+        // The earliest one of the nearly equal highest scores.
+        assert_eq!((result.highest_job_id, result.highest_score), (1, 60.0));
+        assert_eq!(result.accepted_job_id, None);
+        assert_eq!(result.first_accepted_job_id, None);
+        assert_eq!(result.rejected_count, 4);
+    }
+
+    #[test]
+    fn accepted() {
+        let result = summarize_one(&[
+            finished(0, JobResult::CompilationError, 0.0),
+            finished(1, JobResult::WrongAnswer, 50.0),
+            finished(2, JobResult::SystemError, 0.0),
+            finished(3, JobResult::Accepted, 100.0),
+            finished(4, JobResult::TimeLimitExceeded, 0.0),
+            finished(5, JobResult::Accepted, 100.0),
+        ]);
+        // The latest accepted job.
+        assert_eq!(result.accepted_job_id, Some(5));
+        assert_eq!(result.accepted_score, Some(100.0));
+        // Rejections after the first accepted job, compilation errors and system errors don't
+        // count.
+        assert_eq!(result.first_accepted_job_id, Some(3));
+        assert_eq!(
+            result.first_accepted_time,
+            Some(NaiveDateTime::default() + Duration::minutes(3))
+        );
+        assert_eq!(result.rejected_count, 1);
+    }
+
+    #[test]
+    fn unfinished_jobs() {
+        let result = summarize_one(&[
+            job(0, JobState::Queueing, JobResult::Waiting, 0.0),
+            finished(1, JobResult::RuntimeError, 0.0),
+            // A running job may have the result of its finished cases.
+            job(2, JobState::Running, JobResult::Accepted, 100.0),
+            job(3, JobState::Canceled, JobResult::Waiting, 0.0),
+        ]);
+        assert_eq!(result.submission_count, 4);
+        assert_eq!(result.latest_job_id, 3);
+        assert_eq!(result.first_accepted_job_id, None);
+        assert_eq!(result.first_accepted_time, None);
+        assert_eq!(result.rejected_count, 1);
+    }
+
+    #[test]
+    fn summarize_groups_by_user_and_problem() {
+        let mut jobs = vec![
+            finished(5, JobResult::Accepted, 100.0),
+            finished(1, JobResult::WrongAnswer, 0.0),
+        ];
+        let mut other = finished(3, JobResult::Accepted, 100.0);
+        other.user_id = 1;
+        jobs.push(other);
+        let results = summarize(0, jobs.iter());
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].user_id, results[0].submission_count), (1, 1));
+        assert_eq!((results[1].user_id, results[1].submission_count), (2, 2));
+        // Jobs are sorted by id.
+        assert_eq!(results[1].latest_job_id, 5);
+        assert_eq!(results[1].rejected_count, 1);
+        assert!(results.iter().all(|result| result.contest_id == 0));
+    }
+}
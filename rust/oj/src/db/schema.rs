@@ -19,6 +19,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    contest_reveals (contest_id, user_id, problem_id) {
+        contest_id -> Integer,
+        user_id -> Integer,
+        problem_id -> Integer,
+        reveal_time -> Timestamp,
+    }
+}
+
 diesel::table! {
     contest_users (rowid) {
         rowid -> Integer,
@@ -34,6 +43,8 @@ diesel::table! {
         from -> Timestamp,
         to -> Timestamp,
         submission_limit -> Integer,
+        mode -> crate::db::enums::ContestModeMapping,
+        freeze_time -> Nullable<Timestamp>,
     }
 }
 
@@ -53,24 +64,70 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    rank_results (contest_id, user_id, problem_id) {
+        contest_id -> Integer,
+        user_id -> Integer,
+        problem_id -> Integer,
+        submission_count -> Integer,
+        latest_job_id -> Integer,
+        latest_score -> Double,
+        highest_job_id -> Integer,
+        highest_score -> Double,
+        accepted_job_id -> Nullable<Integer>,
+        accepted_score -> Nullable<Double>,
+        first_accepted_job_id -> Nullable<Integer>,
+        first_accepted_time -> Nullable<Timestamp>,
+        rejected_count -> Integer,
+    }
+}
+
+diesel::table! {
+    sessions (token_hash) {
+        token_hash -> Text,
+        user_id -> Integer,
+        expire_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    subtask_results (job_id, id) {
+        job_id -> Integer,
+        id -> Integer,
+        score -> Double,
+        max_score -> Double,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Integer,
         name -> Text,
+        password_hash -> Nullable<Text>,
+        role -> crate::db::enums::UserRoleMapping,
     }
 }
 
 diesel::joinable!(case_results -> jobs (job_id));
 diesel::joinable!(contest_problems -> contests (contest_id));
+diesel::joinable!(contest_reveals -> contests (contest_id));
+diesel::joinable!(contest_reveals -> users (user_id));
 diesel::joinable!(contest_users -> contests (contest_id));
 diesel::joinable!(contest_users -> users (user_id));
 diesel::joinable!(jobs -> users (user_id));
+diesel::joinable!(rank_results -> users (user_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(subtask_results -> jobs (job_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     case_results,
     contest_problems,
+    contest_reveals,
     contest_users,
     contests,
     jobs,
+    rank_results,
+    sessions,
+    subtask_results,
     users,
 );
@@ -0,0 +1,49 @@
+//! Database operations on the `sessions` table.
+
+use super::connection::ConnectionPool;
+use super::enums::UserRole;
+use super::schema::sessions::dsl;
+use crate::error::*;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+/// Add a new session of the given user, identified by the hash of its token.
+pub fn add_session(
+    token_hash: &str,
+    user_id: i32,
+    expire_time: NaiveDateTime,
+    pool: &ConnectionPool,
+) -> ApiResult<()> {
+    diesel::insert_into(dsl::sessions)
+        .values((
+            dsl::token_hash.eq(token_hash),
+            dsl::user_id.eq(user_id),
+            dsl::expire_time.eq(expire_time),
+        ))
+        .execute(&mut pool.get()?)?;
+    Ok(())
+}
+
+/// Get the id and the role of the user of an unexpired session.
+pub fn get_session_user(
+    token_hash: &str,
+    pool: &ConnectionPool,
+) -> ApiResult<Option<(i32, UserRole)>> {
+    use super::schema::users;
+    Ok(dsl::sessions
+        .inner_join(users::table)
+        .select((users::columns::id, users::columns::role))
+        .filter(dsl::token_hash.eq(token_hash))
+        .filter(dsl::expire_time.gt(Utc::now().naive_utc()))
+        .first(&mut pool.get()?)
+        .optional()?)
+}
+
+/// Delete a session and all expired sessions.
+pub fn delete_session(token_hash: &str, pool: &ConnectionPool) -> ApiResult<()> {
+    diesel::delete(dsl::sessions)
+        .filter(dsl::token_hash.eq(token_hash))
+        .or_filter(dsl::expire_time.le(Utc::now().naive_utc()))
+        .execute(&mut pool.get()?)?;
+    Ok(())
+}
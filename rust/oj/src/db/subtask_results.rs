@@ -0,0 +1,79 @@
+//! Database operations on the `subtask_results` table.
+
+use super::connection::{DbBackend, DbConnection};
+use super::schema::{self, subtask_results::dsl};
+use crate::error::*;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The score of a subtask in a finished job.
+#[derive(Queryable, Serialize, Deserialize, Clone, Debug)]
+pub struct SubtaskResult {
+    /// The subtask id, starting from 1.
+    pub id: i32,
+    pub score: f64,
+    /// The score of the subtask if all its cases are accepted.
+    pub max_score: f64,
+}
+
+/// Replace the subtask results of a job.
+pub fn set_subtask_results(
+    job_id: i32,
+    subtasks: &[SubtaskResult],
+    conn: &mut DbConnection,
+) -> ApiResult<()> {
+    delete_subtask_results(job_id, conn)?;
+    diesel::insert_into(dsl::subtask_results)
+        .values(
+            subtasks
+                .iter()
+                .map(|subtask| {
+                    (
+                        dsl::job_id.eq(job_id),
+                        dsl::id.eq(subtask.id),
+                        dsl::score.eq(subtask.score),
+                        dsl::max_score.eq(subtask.max_score),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Delete the subtask results of a job, e.g. when it's judged again.
+pub fn delete_subtask_results(job_id: i32, conn: &mut DbConnection) -> ApiResult<()> {
+    diesel::delete(dsl::subtask_results.filter(dsl::job_id.eq(job_id))).execute(conn)?;
+    Ok(())
+}
+
+/// Get the subtask results of a single job.
+pub(super) fn get_subtask_results(
+    job_id: i32,
+    conn: &mut DbConnection,
+) -> ApiResult<Vec<SubtaskResult>> {
+    Ok(dsl::subtask_results
+        .select((dsl::id, dsl::score, dsl::max_score))
+        .filter(dsl::job_id.eq(job_id))
+        .order(dsl::id)
+        .load(conn)?)
+}
+
+/// Get the subtask results of the jobs selected by the given query in a single query, grouped by
+/// job id.
+pub(super) fn get_subtask_results_of_jobs(
+    jobs: schema::jobs::BoxedQuery<'_, DbBackend>,
+    conn: &mut DbConnection,
+) -> ApiResult<HashMap<i32, Vec<SubtaskResult>>> {
+    let subtasks = dsl::subtask_results
+        .select((dsl::job_id, (dsl::id, dsl::score, dsl::max_score)))
+        .filter(dsl::job_id.eq_any(jobs.select(schema::jobs::id)))
+        .order((dsl::job_id, dsl::id))
+        .load::<(i32, SubtaskResult)>(conn)?;
+    let mut subtasks_of_jobs = HashMap::<i32, Vec<SubtaskResult>>::new();
+    for (job_id, subtask) in subtasks {
+        subtasks_of_jobs.entry(job_id).or_default().push(subtask);
+    }
+    Ok(subtasks_of_jobs)
+}
@@ -1,13 +1,14 @@
 //! Database operations on the `users` table.
 
-use super::connection::ConnectionPool;
+use super::connection::{lock, write_transaction, ConnectionPool, DbConnection, Lock};
+use super::enums::UserRole;
 use super::schema::users::dsl;
 use crate::error::*;
 use diesel::prelude::*;
 use serde::Serialize;
 
 /// Checks that the given name is either not used or is used by the user of the given id.
-fn name_not_used(name: &str, id: Option<i32>, conn: &mut SqliteConnection) -> ApiResult<()> {
+fn name_not_used(name: &str, id: Option<i32>, conn: &mut DbConnection) -> ApiResult<()> {
     let current_id = dsl::users
         .select(dsl::id)
         .filter(dsl::name.eq(name))
@@ -23,63 +24,106 @@ fn name_not_used(name: &str, id: Option<i32>, conn: &mut SqliteConnection) -> Ap
     }
 }
 
-#[derive(Serialize, Insertable, Queryable)]
-#[diesel(table_name = super::schema::users)]
+#[derive(Serialize, Queryable)]
 pub struct User {
     pub id: i32,
-    name: String,
+    pub name: String,
+}
+
+/// The columns of a user in the database.
+#[derive(Insertable, Queryable)]
+#[diesel(table_name = super::schema::users)]
+pub(crate) struct UserInfo {
+    pub id: i32,
+    pub name: String,
+    pub password_hash: Option<String>,
+    pub role: UserRole,
+}
+
+/// The new data of a user. The `None` fields are left unchanged.
+#[derive(AsChangeset)]
+#[diesel(table_name = super::schema::users)]
+pub struct UserUpdate {
+    pub name: String,
+    pub password_hash: Option<String>,
+    pub role: Option<UserRole>,
 }
 
 /// Get the list of all users sorted by id.
 pub fn get_users(pool: &ConnectionPool) -> ApiResult<Vec<User>> {
-    Ok(dsl::users.order(dsl::id).load(&mut pool.get()?)?)
+    Ok(dsl::users
+        .select((dsl::id, dsl::name))
+        .order(dsl::id)
+        .load(&mut pool.get()?)?)
 }
 
-/// Change the name of an existing user, errors on duplicated username.
-pub fn set_name(id: i32, name: String, pool: &ConnectionPool) -> ApiResult<User> {
-    pool.get()?.immediate_transaction(|conn| {
-        name_not_used(&name, Some(id), conn)?;
+/// Update an existing user, errors on duplicated username.
+pub fn update_user(id: i32, update: &UserUpdate, pool: &ConnectionPool) -> ApiResult<User> {
+    write_transaction(pool, |conn| {
+        lock(conn, Lock::Users)?;
+        name_not_used(&update.name, Some(id), conn)?;
         let change_count = diesel::update(dsl::users)
             .filter(dsl::id.eq(id))
-            .set(dsl::name.eq(&name))
+            .set(update)
             .execute(conn)?;
         if change_count == 0 {
             Err(ApiError::not_found(&format!("User {}", id)))
         } else {
-            Ok(User { id, name })
+            Ok(User {
+                id,
+                name: update.name.clone(),
+            })
         }
     })
 }
 
 /// Get the number of existing users.
-pub(super) fn user_count(conn: &mut SqliteConnection) -> ApiResult<i32> {
+pub(super) fn user_count(conn: &mut DbConnection) -> ApiResult<i32> {
     Ok(dsl::users.count().get_result::<i64>(conn)? as i32)
 }
 
 /// Add a new user, errors on duplicated username.
-pub fn add_user(name: String, pool: &ConnectionPool) -> ApiResult<User> {
-    pool.get()?.immediate_transaction(|conn| {
-        name_not_used(&name, None, conn)?;
+pub fn add_user(update: UserUpdate, pool: &ConnectionPool) -> ApiResult<User> {
+    write_transaction(pool, |conn| {
+        lock(conn, Lock::Users)?;
+        name_not_used(&update.name, None, conn)?;
         let id = user_count(conn)?;
-        let user = User { id, name };
+        let user = UserInfo {
+            id,
+            name: update.name,
+            password_hash: update.password_hash,
+            role: update.role.unwrap_or(UserRole::Contestant),
+        };
         diesel::insert_into(dsl::users)
             .values(&user)
             .execute(conn)?;
-        Ok(user)
+        Ok(User {
+            id,
+            name: user.name,
+        })
     })
 }
 
-/// Get the id of the user with the given name.
-pub(super) fn get_user_id(name: &str, conn: &mut SqliteConnection) -> ApiResult<Option<i32>> {
+/// Get the user with the given name, including the password hash and the role.
+pub(crate) fn get_user_info(name: &str, pool: &ConnectionPool) -> ApiResult<Option<UserInfo>> {
     Ok(dsl::users
-        .select(dsl::id)
         .filter(dsl::name.eq(name))
-        .first(conn)
+        .first(&mut pool.get()?)
         .optional()?)
 }
 
+/// Set the password hash of an existing user.
+pub(crate) fn set_password_hash(id: i32, hash: &str, pool: &ConnectionPool) -> ApiResult<()> {
+    diesel::update(dsl::users)
+        .filter(dsl::id.eq(id))
+        .set(dsl::password_hash.eq(hash))
+        .execute(&mut pool.get()?)?;
+    Ok(())
+}
+
 pub fn get_single_user(id: i32, pool: &ConnectionPool) -> ApiResult<User> {
     let user = dsl::users
+        .select((dsl::id, dsl::name))
         .filter(dsl::id.eq(id))
         .first(&mut pool.get()?)
         .optional()?;
@@ -1,5 +1,6 @@
 //! Custom errors.
 
+use crate::judger::queue::QueueClosed;
 use actix_web::{http::StatusCode, HttpRequest, HttpResponse, ResponseError};
 use serde::Serialize;
 use std::fmt::{Display, Formatter};
@@ -13,6 +14,8 @@ pub enum ApiErrorType {
     RateLimit = 4,
     External = 5,
     Internal = 6,
+    Unauthorized = 7,
+    Forbidden = 8,
 }
 
 impl ApiErrorType {
@@ -24,6 +27,8 @@ impl ApiErrorType {
             ApiErrorType::RateLimit => "ERR_RATE_LIMIT",
             ApiErrorType::External => "ERR_EXTERNAL",
             ApiErrorType::Internal => "ERR_INTERNAL",
+            ApiErrorType::Unauthorized => "ERR_UNAUTHORIZED",
+            ApiErrorType::Forbidden => "ERR_FORBIDDEN",
         }
     }
 
@@ -35,6 +40,8 @@ impl ApiErrorType {
             ApiErrorType::RateLimit => StatusCode::BAD_REQUEST,
             ApiErrorType::External => StatusCode::INTERNAL_SERVER_ERROR,
             ApiErrorType::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiErrorType::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiErrorType::Forbidden => StatusCode::FORBIDDEN,
         }
     }
 }
@@ -55,6 +62,11 @@ impl ApiError {
         }
     }
 
+    /// Get the type of the error.
+    pub fn error_type(&self) -> ApiErrorType {
+        self.error_type
+    }
+
     /// Construct an `ApiError` with the not-found type.
     ///
     /// * `name`: the name of the thing not found, used in the error message.
@@ -67,10 +79,7 @@ impl ApiError {
 
     /// Construct an `actix_web::Error` from another error representing an invalid-argument error.
     /// Mainly used as an `error_handler` of `actix_web::web::{JsonConfig, PathConfig}`, etc.
-    pub fn invalid_argument(
-        error: impl std::error::Error + Display,
-        _: &HttpRequest,
-    ) -> actix_web::Error {
+    pub fn invalid_argument(error: impl std::error::Error, _: &HttpRequest) -> actix_web::Error {
         Self {
             error_type: ApiErrorType::InvalidArgument,
             message: error.to_string(),
@@ -111,8 +120,8 @@ impl From<actix_web::error::BlockingError> for ApiError {
     }
 }
 
-impl<T> From<async_channel::SendError<T>> for ApiError {
-    fn from(error: async_channel::SendError<T>) -> Self {
+impl From<QueueClosed> for ApiError {
+    fn from(error: QueueClosed) -> Self {
         ApiError::new(
             ApiErrorType::Internal,
             format!("failed to add job to queue: {}", error),
@@ -126,6 +135,18 @@ impl From<std::io::Error> for ApiError {
     }
 }
 
+impl From<csv::Error> for ApiError {
+    fn from(error: csv::Error) -> Self {
+        ApiError::new(ApiErrorType::Internal, format!("CSV error: {}", error))
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(error: reqwest::Error) -> Self {
+        ApiError::new(ApiErrorType::External, format!("HTTP error: {}", error))
+    }
+}
+
 /// The JSON API response of an `ApiError`.
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -1,27 +1,124 @@
 //! Distribute and run jobs.
 
+mod compare;
+pub mod events;
+pub mod nodes;
+mod process;
+pub mod queue;
+pub mod remote;
+pub mod runs;
 mod worker;
 
-use crate::config::Config;
+use crate::config::SharedConfig;
+use crate::db::case_results::{self, CaseUpdate};
 use crate::db::connection::ConnectionPool;
+use crate::db::enums::JobResult;
+use crate::db::jobs;
+use crate::db::subtask_results::SubtaskResult;
 use crate::error::ApiResult;
+use events::{JobEvent, JobEvents};
+use nodes::{NodeStatus, RemoteNodes};
+use queue::{JobPriority, JobQueue};
+use runs::Runner;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::{self, error::TryRecvError};
+use tokio::sync::Semaphore;
 use tokio::time;
 use tokio::{fs, signal};
-use worker::Worker;
+use worker::{LocalBackend, Worker};
 
 const TMP_DIR: &str = "tmp/judger";
 
 /// Add jobs in the job queue to be received by `Worker`s.
 #[derive(Clone)]
 pub struct JobAdder {
-    job_sender: async_channel::Sender<i32>,
+    queue: Arc<JobQueue>,
+    worker_count: usize,
+    busy_workers: Arc<AtomicUsize>,
+    nodes: RemoteNodes,
+}
+
+/// The status of the judger.
+#[derive(Serialize)]
+pub struct JudgerStatus {
+    workers: usize,
+    busy_workers: usize,
+    queue_length: usize,
+    /// The number of queueing jobs of each priority.
+    queue_lengths: BTreeMap<JobPriority, usize>,
+    /// The registered remote judge nodes, only shown to admins.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nodes: Option<Vec<NodeStatus>>,
 }
 
 impl JobAdder {
-    pub fn add_job(&self, id: i32) -> ApiResult<()> {
-        self.job_sender.send_blocking(id)?;
+    pub fn add_job(&self, id: i32, priority: JobPriority) -> ApiResult<()> {
+        self.queue.push(id, priority)?;
+        Ok(())
+    }
+
+    /// Get the status of the judger, with the remote judge nodes if `with_nodes` is set.
+    pub fn status(&self, with_nodes: bool) -> JudgerStatus {
+        let queue_lengths = self.queue.lengths();
+        JudgerStatus {
+            workers: self.worker_count,
+            busy_workers: self.busy_workers.load(Ordering::Relaxed),
+            queue_length: queue_lengths.values().sum(),
+            queue_lengths,
+            nodes: with_nodes.then(|| self.nodes.status()),
+        }
+    }
+}
+
+/// Save the progress of jobs in the database and send it to the subscribers of events.
+#[derive(Clone)]
+pub struct JobRecorder {
+    pool: ConnectionPool,
+    events: JobEvents,
+}
+
+impl JobRecorder {
+    /// Update a single case in the database and send the event to subscribers.
+    fn update_case(
+        &self,
+        job_id: i32,
+        case_id: i32,
+        update: &CaseUpdate,
+        total_score: f64,
+    ) -> ApiResult<()> {
+        case_results::update_case(job_id, case_id, update, total_score, &self.pool)?;
+        self.events.send(
+            job_id,
+            JobEvent::Case {
+                case: update.to_case(case_id),
+                score: total_score,
+            },
+        );
+        Ok(())
+    }
+
+    /// Finish a job in the database and send the event to subscribers.
+    fn finish_job(
+        &self,
+        job_id: i32,
+        result: JobResult,
+        score: f64,
+        subtasks: Vec<SubtaskResult>,
+    ) -> ApiResult<()> {
+        jobs::finish_job(job_id, &result, score, &subtasks, &self.pool)?;
+        self.events.send(
+            job_id,
+            JobEvent::Finish {
+                result,
+                score,
+                subtasks,
+            },
+        );
         Ok(())
     }
 }
@@ -29,13 +126,13 @@ impl JobAdder {
 /// Gracefully wait for unfinished jobs.
 /// See <https://tokio.rs/tokio/topics/shutdown#waiting-for-things-to-finish-shutting-down>
 pub struct JobWaiter {
-    job_sender: async_channel::Sender<i32>,
+    queue: Arc<JobQueue>,
     finished_receiver: mpsc::Receiver<()>,
 }
 
 impl JobWaiter {
     pub async fn wait(mut self) {
-        self.job_sender.close();
+        self.queue.close();
         time::sleep(Duration::from_millis(10)).await;
         match self.finished_receiver.try_recv() {
             Err(TryRecvError::Disconnected) => {}
@@ -54,51 +151,116 @@ impl JobWaiter {
         // TMP_DIR should be empty when gracefully exiting, but remove it to be safe
         match fs::remove_dir_all(TMP_DIR).await {
             Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
-            result => result.expect(&format!(
-                "failed to remove temporary directory [{}]",
-                TMP_DIR
-            )),
+            result => result.unwrap_or_else(|error| {
+                panic!(
+                    "failed to remove temporary directory [{}]: {}",
+                    TMP_DIR, error
+                )
+            }),
         }
     }
 }
 
-/// Spawn `Worker`s and return `JobAdder` to add jobs and `JobWaiter` to wait for `Worker`s to
-/// finish.
-pub fn create_judger(pool: ConnectionPool, config: &Config) -> (JobAdder, JobWaiter) {
-    let (job_sender, job_receiver) = async_channel::unbounded();
+/// Spawn `Worker`s and return `JobAdder` to add jobs, `JobWaiter` to wait for `Worker`s to
+/// finish, `RemoteNodes` to serve remote judge nodes and `Runner` to run solutions on custom
+/// input. The progress of jobs is sent to `events`.
+pub fn create_judger(
+    pool: ConnectionPool,
+    config: SharedConfig,
+    events: JobEvents,
+) -> (JobAdder, JobWaiter, RemoteNodes, Runner) {
+    let queue = Arc::new(JobQueue::default());
+    let busy_workers = Arc::new(AtomicUsize::new(0));
     let (finished_sender, finished_receiver) = mpsc::channel(1);
 
-    let worker_count = (num_cpus::get() / 2).max(1);
+    let worker_count = config
+        .load()
+        .server
+        .workers
+        .unwrap_or_else(|| (num_cpus::get() / 2).max(1));
 
     log::info!("Starting {} workers", worker_count);
 
+    // The database of PostgreSQL is not a local file to hide.
+    let database_files = if cfg!(feature = "sqlite") {
+        vec![crate::db::DATABASE_URL.as_str()]
+    } else {
+        Vec::new()
+    };
+    let hidden_dirs = sandbox_hidden_dirs(Path::new(TMP_DIR), &database_files);
+    let recorder = JobRecorder {
+        pool: pool.clone(),
+        events,
+    };
+
     // spawn workers in new tasks
     for _ in 0..worker_count {
         let worker = Worker {
             config: config.clone(),
-            job_receiver: job_receiver.clone(),
-            finished_sender: finished_sender.clone(),
-            pool: pool.clone(),
+            hidden_dirs: hidden_dirs.clone(),
+            tmp_dir: PathBuf::from(TMP_DIR),
+            busy_workers: busy_workers.clone(),
+            backend: LocalBackend {
+                queue: queue.clone(),
+                recorder: recorder.clone(),
+                _finished_sender: finished_sender.clone(),
+            },
         };
         tokio::spawn(worker.work());
     }
 
+    let runner = Runner {
+        config,
+        hidden_dirs,
+        tmp_dir: PathBuf::from(TMP_DIR),
+        // At least one run at a time even if all jobs are judged by remote nodes.
+        permits: Arc::new(Semaphore::new(worker_count.max(1))),
+    };
+
+    let nodes = RemoteNodes::new(queue.clone(), recorder);
+    tokio::spawn(nodes.clone().remove_dead_nodes());
+
     // Add unfinished jobs to the queue at startup
-    for job_id in
+    for (job_id, contest_id) in
         crate::db::jobs::get_unfinished_jobs(&pool).expect("failed to get unfinished jobs")
     {
-        job_sender
-            .send_blocking(job_id)
+        queue
+            .push(job_id, JobPriority::of_submission(contest_id))
             .expect("failed to add job in the queue");
     }
 
     (
         JobAdder {
-            job_sender: job_sender.clone(),
+            queue: queue.clone(),
+            worker_count,
+            busy_workers,
+            nodes: nodes.clone(),
         },
         JobWaiter {
-            job_sender,
+            queue,
             finished_receiver,
         },
+        nodes,
+        runner,
     )
 }
+
+/// Get the directories hidden from sandboxed programs except the test data: the temporary
+/// directory of all jobs, which is created if missing, and the directories of the other files.
+fn sandbox_hidden_dirs(tmp_dir: &Path, files: &[&str]) -> Vec<PathBuf> {
+    std::fs::create_dir_all(tmp_dir).expect("failed to create the temporary directory");
+    let mut dirs = Vec::new();
+    for file in std::iter::once(tmp_dir).chain(files.iter().map(Path::new)) {
+        // Canonicalize the paths because symlinks can't be mount points.
+        match std::fs::canonicalize(file) {
+            Ok(path) if path.is_dir() => dirs.push(path),
+            Ok(path) => dirs.extend(path.parent().map(Path::to_path_buf)),
+            Err(error) => log::warn!(
+                "Failed to find [{}] to hide in sandboxes: {}",
+                file.display(),
+                error
+            ),
+        }
+    }
+    dirs
+}
@@ -0,0 +1,392 @@
+//! Compare the output of a solution with the answer, and describe the first difference.
+
+use crate::config::{ProblemType, WrongAnswerInfo};
+
+/// The maximum number of characters of a token shown in the info.
+const TOKEN_LIMIT: usize = 32;
+
+/// The first differing line of the output and the answer.
+struct Difference<'a> {
+    /// The line number starting from 1.
+    line: usize,
+    /// The column number starting from 1, counted in characters.
+    column: usize,
+    /// The line of the answer, or `None` if the answer has ended.
+    expected: Option<&'a str>,
+    /// The line of the output, or `None` if the output has ended.
+    got: Option<&'a str>,
+}
+
+/// Compare the output with the answer according to the type of the problem, which compares lines
+/// exactly in strict mode, whitespace-separated tokens for float and tokens problems, and otherwise
+/// lines ignoring their trailing whitespace and the trailing empty lines. Returns the info of
+/// "Wrong Answer" as the error if they differ.
+pub fn compare(
+    output: &[u8],
+    answer: &str,
+    tp: &ProblemType,
+    info: WrongAnswerInfo,
+) -> Result<(), String> {
+    let output = match std::str::from_utf8(output) {
+        Ok(output) => output,
+        Err(_) if info == WrongAnswerInfo::None => return Err(String::new()),
+        Err(_) => return Err(String::from("The output is not valid UTF-8.")),
+    };
+    let difference = match tp {
+        ProblemType::Strict => first_difference(output.split('\n'), answer.split('\n')),
+        ProblemType::Float {
+            absolute_error,
+            relative_error,
+        } => {
+            return compare_tokens(output, answer, info, |got, expected| {
+                float_eq(got, expected, *absolute_error, *relative_error)
+            })
+        }
+        ProblemType::Tokens => {
+            return compare_tokens(output, answer, info, |got, expected| got == expected)
+        }
+        _ => first_difference(
+            standard_lines(output).into_iter(),
+            standard_lines(answer).into_iter(),
+        ),
+    };
+    match difference {
+        None => Ok(()),
+        Some(difference) => Err(match info {
+            WrongAnswerInfo::None => String::new(),
+            WrongAnswerInfo::Position => position_info(difference.line, difference.column),
+            WrongAnswerInfo::Full => format!(
+                "line {}: expected {}, got {}",
+                difference.line,
+                describe(difference.expected, difference.column - 1, "end of answer"),
+                describe(difference.got, difference.column - 1, "end of output")
+            ),
+        }),
+    }
+}
+
+fn position_info(line: usize, column: usize) -> String {
+    format!(
+        "The output differs from the answer at line {}, column {}.",
+        line, column
+    )
+}
+
+/// Whether a token of the output is equal to the token of the answer, or both are finite numbers
+/// within the allowed absolute or relative error.
+fn float_eq(got: &str, expected: &str, absolute_error: f64, relative_error: f64) -> bool {
+    if got == expected {
+        return true;
+    }
+    match (got.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(got), Ok(expected)) if got.is_finite() && expected.is_finite() => {
+            let error = (got - expected).abs();
+            error <= absolute_error || error <= relative_error * expected.abs()
+        }
+        _ => false,
+    }
+}
+
+/// A whitespace-separated token with its position, starting from 1.
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+/// Split the text into whitespace-separated tokens. The last item has an empty text and the
+/// position of the end of the text.
+fn tokens(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if let Some(token) = start.take() {
+                tokens.push(token_at(text, token, index));
+            }
+        } else if start.is_none() {
+            start = Some((index, line, column));
+        }
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    if let Some(token) = start {
+        tokens.push(token_at(text, token, text.len()));
+    }
+    tokens.push(Token {
+        text: "",
+        line,
+        column,
+    });
+    tokens
+}
+
+fn token_at(text: &str, (start, line, column): (usize, usize, usize), end: usize) -> Token<'_> {
+    Token {
+        text: &text[start..end],
+        line,
+        column,
+    }
+}
+
+/// Compare the whitespace-separated tokens of the output and the answer with `eq`.
+fn compare_tokens(
+    output: &str,
+    answer: &str,
+    info: WrongAnswerInfo,
+    eq: impl Fn(&str, &str) -> bool,
+) -> Result<(), String> {
+    let output = tokens(output);
+    let answer = tokens(answer);
+    let (index, got, expected) = match output
+        .iter()
+        .zip(&answer)
+        .enumerate()
+        .find(|(_, (got, expected))| !eq(got.text, expected.text))
+    {
+        None => return Ok(()),
+        Some((index, (got, expected))) => (index, got, expected),
+    };
+    let describe_token =
+        |token: &Token, end| describe((!token.text.is_empty()).then_some(token.text), 0, end);
+    Err(match info {
+        WrongAnswerInfo::None => String::new(),
+        WrongAnswerInfo::Position => position_info(got.line, got.column),
+        WrongAnswerInfo::Full => format!(
+            "token {}: expected {}, got {}",
+            index + 1,
+            describe_token(expected, "end of answer"),
+            describe_token(got, "end of output")
+        ),
+    })
+}
+
+/// The lines compared in standard mode, without trailing whitespace and trailing empty lines.
+fn standard_lines(text: &str) -> Vec<&str> {
+    let mut lines = text.lines().map(str::trim_end).collect::<Vec<_>>();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn first_difference<'a>(
+    mut output: impl Iterator<Item = &'a str>,
+    mut answer: impl Iterator<Item = &'a str>,
+) -> Option<Difference<'a>> {
+    let mut line = 0;
+    loop {
+        line += 1;
+        match (output.next(), answer.next()) {
+            (None, None) => return None,
+            (got, expected) if got == expected => continue,
+            (got, expected) => {
+                let column = match (got, expected) {
+                    (Some(got), Some(expected)) => {
+                        got.chars()
+                            .zip(expected.chars())
+                            .take_while(|(got, expected)| got == expected)
+                            .count()
+                            + 1
+                    }
+                    _ => 1,
+                };
+                return Some(Difference {
+                    line,
+                    column,
+                    expected,
+                    got,
+                });
+            }
+        }
+    }
+}
+
+/// Describe the token of the line at the character index, which is a run of non-whitespace or
+/// whitespace characters, truncated to `TOKEN_LIMIT` characters. Returns `end` if there is no
+/// line.
+fn describe(line: Option<&str>, index: usize, end: &str) -> String {
+    let chars = match line {
+        None => return String::from(end),
+        Some(line) => line.chars().collect::<Vec<_>>(),
+    };
+    if index >= chars.len() {
+        return String::from("end of line");
+    }
+    let whitespace = chars[index].is_whitespace();
+    let same_kind = |&i: &usize| chars[i].is_whitespace() == whitespace;
+    let start = (0..index)
+        .rev()
+        .take_while(same_kind)
+        .last()
+        .unwrap_or(index);
+    let end = (index..chars.len()).take_while(same_kind).count() + index;
+    let token = chars[start..end.min(start + TOKEN_LIMIT)]
+        .iter()
+        .collect::<String>();
+    format!(
+        "`{}`{}",
+        token.escape_debug(),
+        if end - start > TOKEN_LIMIT { "..." } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full(output: &str, answer: &str, tp: &ProblemType) -> Result<(), String> {
+        compare(output.as_bytes(), answer, tp, WrongAnswerInfo::Full)
+    }
+
+    #[test]
+    fn standard_ignores_trailing_whitespace() {
+        assert_eq!(
+            full("1 2  \n3\n\n\n", "1 2\n3", &ProblemType::Standard),
+            Ok(())
+        );
+        assert!(full("1 2\n3", "1  2\n3", &ProblemType::Standard).is_err());
+    }
+
+    #[test]
+    fn strict_compares_lines_exactly() {
+        assert_eq!(
+            full("1 \n2\n", "1\n2\n", &ProblemType::Strict),
+            Err(String::from("line 1: expected end of line, got ` `"))
+        );
+        assert_eq!(
+            full("1\n2", "1\n2\n", &ProblemType::Strict),
+            Err(String::from(
+                "line 3: expected end of line, got end of output"
+            ))
+        );
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let tp = ProblemType::Standard;
+        assert_eq!(
+            full("你好世界", "你好地球", &tp),
+            Err(String::from("line 1: expected `你好地球`, got `你好世界`"))
+        );
+        assert_eq!(
+            compare("é x".as_bytes(), "é y", &tp, WrongAnswerInfo::Position),
+            Err(position_info(1, 3))
+        );
+    }
+
+    #[test]
+    fn long_tokens_are_truncated() {
+        let output = "a".repeat(TOKEN_LIMIT + 8);
+        let answer = "b".repeat(TOKEN_LIMIT);
+        assert_eq!(
+            full(&output, &answer, &ProblemType::Standard),
+            Err(format!(
+                "line 1: expected `{}`, got `{}`...",
+                answer,
+                "a".repeat(TOKEN_LIMIT)
+            ))
+        );
+    }
+
+    #[test]
+    fn output_ends_first() {
+        assert_eq!(
+            full("1\n", "1\n2\n", &ProblemType::Standard),
+            Err(String::from("line 2: expected `2`, got end of output"))
+        );
+        assert_eq!(
+            full("1 2", "1 2 3", &ProblemType::Standard),
+            Err(String::from("line 1: expected ` `, got end of line"))
+        );
+    }
+
+    #[test]
+    fn answer_ends_first() {
+        assert_eq!(
+            full("1\n2\n", "1\n", &ProblemType::Standard),
+            Err(String::from("line 2: expected end of answer, got `2`"))
+        );
+    }
+
+    #[test]
+    fn float_tolerance() {
+        assert!(float_eq("1.00001", "1", 1e-4, 0.0));
+        assert!(!float_eq("1.001", "1", 1e-4, 0.0));
+        assert!(float_eq("1000.1", "1000", 0.0, 1e-3));
+        assert!(!float_eq("1000.1", "1000", 0.0, 1e-5));
+        // The relative error is useless when the answer is zero.
+        assert!(!float_eq("1e-9", "0", 0.0, 1e-3));
+        assert!(float_eq("-0", "0", 0.0, 0.0));
+        // Non-numeric tokens are compared exactly.
+        assert!(float_eq("abc", "abc", 1.0, 1.0));
+        assert!(!float_eq("abc", "abd", 1.0, 1.0));
+    }
+
+    #[test]
+    fn float_non_finite() {
+        assert!(float_eq("nan", "nan", 1.0, 1.0));
+        assert!(!float_eq("NaN", "nan", 1.0, 1.0));
+        assert!(!float_eq("nan", "0", f64::MAX, f64::MAX));
+        assert!(!float_eq("inf", "1e308", f64::MAX, f64::MAX));
+        assert!(!float_eq("1e400", "inf", 1.0, 1.0));
+        assert!(float_eq("inf", "inf", 0.0, 0.0));
+    }
+
+    #[test]
+    fn float_compares_tokens() {
+        let tp = ProblemType::Float {
+            absolute_error: 1e-6,
+            relative_error: 0.0,
+        };
+        assert_eq!(full("0.5000001\n1", "0.5 1.0000000\n", &tp), Ok(()));
+        assert_eq!(
+            full("0.5 1.1", "0.5\n1", &tp),
+            Err(String::from("token 2: expected `1`, got `1.1`"))
+        );
+        assert_eq!(
+            compare(b"0.5\n 1.1", "0.5 1", &tp, WrongAnswerInfo::Position),
+            Err(position_info(2, 2))
+        );
+    }
+
+    #[test]
+    fn tokens_ignore_separators() {
+        let tp = ProblemType::Tokens;
+        assert_eq!(full("a  b\n\nc ", "a\nb c", &tp), Ok(()));
+        assert_eq!(
+            full("a b", "a b c", &tp),
+            Err(String::from("token 3: expected `c`, got end of output"))
+        );
+        assert_eq!(
+            full("a b c", "a b", &tp),
+            Err(String::from("token 3: expected end of answer, got `c`"))
+        );
+        assert_eq!(
+            compare(b"a\nb", "a b c", &tp, WrongAnswerInfo::Position),
+            Err(position_info(2, 2))
+        );
+    }
+
+    #[test]
+    fn info_levels() {
+        let tp = ProblemType::Standard;
+        assert_eq!(
+            compare(b"1\n3", "1\n2", &tp, WrongAnswerInfo::None),
+            Err(String::new())
+        );
+        assert_eq!(
+            compare(b"1\n3", "1\n2", &tp, WrongAnswerInfo::Position),
+            Err(position_info(2, 1))
+        );
+        assert_eq!(
+            compare(b"\xff", "1", &tp, WrongAnswerInfo::Full),
+            Err(String::from("The output is not valid UTF-8."))
+        );
+    }
+}
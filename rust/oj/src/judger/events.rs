@@ -0,0 +1,87 @@
+//! Broadcast the progress of jobs to subscribers, e.g. the `GET /jobs/{id}/events` route.
+
+use crate::db::case_results::Case;
+use crate::db::enums::JobResult;
+use crate::db::subtask_results::SubtaskResult;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// The number of events kept for slow subscribers. A subscriber lagging behind more than this
+/// number of events misses some events, see `JobEventReceiver::recv`.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A change of a job in the database.
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum JobEvent {
+    /// A case is updated by `case_results::update_case`, along with the total score of the job.
+    Case { case: Case, score: f64 },
+    /// The job is finished by `jobs::finish_job`.
+    Finish {
+        result: JobResult,
+        score: f64,
+        subtasks: Vec<SubtaskResult>,
+    },
+    /// The job is canceled by `jobs::cancel_job`.
+    Cancel,
+}
+
+impl JobEvent {
+    /// Whether no more events will be sent for the job after this event.
+    pub fn is_final(&self) -> bool {
+        matches!(self, Self::Finish { .. } | Self::Cancel)
+    }
+}
+
+/// Send events of all jobs, shared by the `Worker`s and the routes.
+#[derive(Clone)]
+pub struct JobEvents {
+    sender: broadcast::Sender<(i32, JobEvent)>,
+}
+
+impl JobEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn send(&self, job_id: i32, event: JobEvent) {
+        // It's fine that there is no subscriber.
+        self.sender.send((job_id, event)).ok();
+    }
+
+    /// Subscribe to the events of a single job sent after this call.
+    pub fn subscribe(&self, job_id: i32) -> JobEventReceiver {
+        JobEventReceiver {
+            job_id,
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+impl Default for JobEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receive events of a single job.
+pub struct JobEventReceiver {
+    job_id: i32,
+    receiver: broadcast::Receiver<(i32, JobEvent)>,
+}
+
+impl JobEventReceiver {
+    /// Receive the next event of the job.
+    ///
+    /// Returns `RecvError::Lagged` if some events are missed because the receiver lags behind, in
+    /// which case the subscriber should get the current state of the job from the database.
+    pub async fn recv(&mut self) -> Result<JobEvent, RecvError> {
+        loop {
+            let (job_id, event) = self.receiver.recv().await?;
+            if job_id == self.job_id {
+                return Ok(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Keep track of remote judge nodes, which pull jobs from the queue of the server and report the
+//! progress of jobs back through the `/nodes` API.
+//!
+//! A node is considered dead if it doesn't send a heartbeat for `NODE_TIMEOUT`, and then the jobs
+//! assigned to it are put back into the queue.
+
+use super::queue::{JobPriority, JobQueue};
+use super::JobRecorder;
+use crate::db::case_results::CaseUpdate;
+use crate::db::enums::JobResult;
+use crate::db::jobs::{self, JobInfoForJudger};
+use crate::db::subtask_results::SubtaskResult;
+use crate::error::*;
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time;
+
+/// A node is removed if it doesn't send a heartbeat for this long.
+pub const NODE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Nodes should send heartbeats at this interval.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a request for a job waits before responding that there is no job.
+pub const PULL_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The first node id is random below this bound, leaving room to count up without overflowing.
+const FIRST_NODE_ID_BOUND: u32 = 1 << 30;
+
+/// A job assigned to a node.
+struct Assignment {
+    priority: JobPriority,
+    assigned_at: Instant,
+}
+
+struct Node {
+    name: String,
+    last_heartbeat: Instant,
+    jobs: HashMap<i32, Assignment>,
+}
+
+struct NodesState {
+    next_id: i32,
+    nodes: HashMap<i32, Node>,
+}
+
+/// The status of a remote judge node.
+#[derive(Serialize)]
+pub struct NodeStatus {
+    id: i32,
+    name: String,
+    /// The ids of the jobs assigned to the node.
+    jobs: Vec<i32>,
+}
+
+/// The registry of remote judge nodes.
+#[derive(Clone)]
+pub struct RemoteNodes {
+    state: Arc<Mutex<NodesState>>,
+    queue: Arc<JobQueue>,
+    recorder: JobRecorder,
+}
+
+impl RemoteNodes {
+    pub(super) fn new(queue: Arc<JobQueue>, recorder: JobRecorder) -> Self {
+        Self {
+            // Start from a random id, so that a node registered before the server restarts isn't
+            // mistaken for a new node with the same id.
+            state: Arc::new(Mutex::new(NodesState {
+                next_id: (OsRng.next_u32() % FIRST_NODE_ID_BOUND) as i32,
+                nodes: HashMap::new(),
+            })),
+            queue,
+            recorder,
+        }
+    }
+
+    fn node_not_found(id: i32) -> ApiError {
+        ApiError::not_found(&format!("Node {}", id))
+    }
+
+    /// Register a new node and return its id.
+    pub fn register(&self, name: String) -> i32 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        log::info!("Judge node registered: {} ({})", id, name);
+        state.nodes.insert(
+            id,
+            Node {
+                name,
+                last_heartbeat: Instant::now(),
+                jobs: HashMap::new(),
+            },
+        );
+        id
+    }
+
+    /// Receive a heartbeat of a node with the jobs it's running.
+    ///
+    /// Jobs assigned to the node long enough ago but not running on it are requeued, which
+    /// happens when the response of the assignment is lost.
+    pub fn heartbeat(&self, id: i32, running: &[i32]) -> ApiResult<()> {
+        let lost = {
+            let mut state = self.state.lock().unwrap();
+            let node = state
+                .nodes
+                .get_mut(&id)
+                .ok_or_else(|| Self::node_not_found(id))?;
+            node.last_heartbeat = Instant::now();
+            let lost = node
+                .jobs
+                .iter()
+                .filter(|(job_id, assignment)| {
+                    !running.contains(job_id) && assignment.assigned_at.elapsed() > NODE_TIMEOUT
+                })
+                .map(|(&job_id, assignment)| (job_id, assignment.priority))
+                .collect::<Vec<_>>();
+            for (job_id, _) in &lost {
+                node.jobs.remove(job_id);
+            }
+            lost
+        };
+        self.requeue(lost)
+    }
+
+    /// Wait for a job for a node for at most `PULL_TIMEOUT`, mark it as running and assign it to
+    /// the node. Returns `None` if there is no job.
+    pub async fn pull(&self, id: i32) -> ApiResult<Option<(i32, JobInfoForJudger)>> {
+        if !self.state.lock().unwrap().nodes.contains_key(&id) {
+            return Err(Self::node_not_found(id));
+        }
+        let deadline = time::Instant::now() + PULL_TIMEOUT;
+        loop {
+            let (job_id, priority) = match time::timeout_at(deadline, self.queue.pop()).await {
+                Ok(Some(job)) => job,
+                // timed out or the queue is closed
+                Err(_) | Ok(None) => return Ok(None),
+            };
+            let info = match jobs::fetch_job_for_judger(job_id, &self.recorder.pool) {
+                Ok(Some(info)) => info,
+                Ok(None) => continue, // job is canceled
+                Err(error) => {
+                    self.queue.push(job_id, priority)?;
+                    return Err(error);
+                }
+            };
+            let mut state = self.state.lock().unwrap();
+            match state.nodes.get_mut(&id) {
+                Some(node) => {
+                    node.jobs.insert(
+                        job_id,
+                        Assignment {
+                            priority,
+                            assigned_at: Instant::now(),
+                        },
+                    );
+                    log::info!("Job {} assigned to node {}", job_id, id);
+                    return Ok(Some((job_id, info)));
+                }
+                // the node is removed while waiting
+                None => {
+                    drop(state);
+                    self.requeue(vec![(job_id, priority)])?;
+                    return Err(Self::node_not_found(id));
+                }
+            }
+        }
+    }
+
+    /// Errors if the job is not assigned to the node.
+    fn check_assigned(&self, id: i32, job_id: i32) -> ApiResult<()> {
+        let state = self.state.lock().unwrap();
+        let node = state
+            .nodes
+            .get(&id)
+            .ok_or_else(|| Self::node_not_found(id))?;
+        if node.jobs.contains_key(&job_id) {
+            Ok(())
+        } else {
+            Err(ApiError::new(
+                ApiErrorType::InvalidState,
+                format!("Job {} not assigned to node {}.", job_id, id),
+            ))
+        }
+    }
+
+    /// Update a single case of a job assigned to a node.
+    pub fn update_case(
+        &self,
+        id: i32,
+        job_id: i32,
+        case_id: i32,
+        update: &CaseUpdate,
+        total_score: f64,
+    ) -> ApiResult<()> {
+        self.check_assigned(id, job_id)?;
+        self.recorder
+            .update_case(job_id, case_id, update, total_score)
+    }
+
+    /// Finish a job assigned to a node.
+    pub fn finish_job(
+        &self,
+        id: i32,
+        job_id: i32,
+        result: JobResult,
+        score: f64,
+        subtasks: Vec<SubtaskResult>,
+    ) -> ApiResult<()> {
+        self.check_assigned(id, job_id)?;
+        self.recorder.finish_job(job_id, result, score, subtasks)?;
+        if let Some(node) = self.state.lock().unwrap().nodes.get_mut(&id) {
+            node.jobs.remove(&job_id);
+        }
+        Ok(())
+    }
+
+    /// Put jobs back into the queue unless they are canceled.
+    fn requeue(&self, jobs: Vec<(i32, JobPriority)>) -> ApiResult<()> {
+        for (job_id, priority) in jobs {
+            if jobs::requeue_job(job_id, &self.recorder.pool)? {
+                log::warn!("Job {} requeued", job_id);
+                self.queue.push(job_id, priority)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Periodically remove nodes without heartbeats for `NODE_TIMEOUT` and requeue their jobs.
+    pub async fn remove_dead_nodes(self) {
+        let mut interval = time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let lost = {
+                let mut state = self.state.lock().unwrap();
+                let dead = state
+                    .nodes
+                    .iter()
+                    .filter(|(_, node)| node.last_heartbeat.elapsed() > NODE_TIMEOUT)
+                    .map(|(&id, _)| id)
+                    .collect::<Vec<_>>();
+                let mut lost = Vec::new();
+                for id in dead {
+                    let node = state.nodes.remove(&id).unwrap();
+                    log::warn!("Judge node {} ({}) is dead", id, node.name);
+                    lost.extend(
+                        node.jobs
+                            .into_iter()
+                            .map(|(job_id, assignment)| (job_id, assignment.priority)),
+                    );
+                }
+                lost
+            };
+            if let Err(error) = self.requeue(lost) {
+                log::error!("Failed to requeue jobs of dead nodes: {:?}", error);
+            }
+        }
+    }
+
+    pub fn status(&self) -> Vec<NodeStatus> {
+        let state = self.state.lock().unwrap();
+        let mut nodes = state
+            .nodes
+            .iter()
+            .map(|(&id, node)| {
+                let mut jobs = node.jobs.keys().cloned().collect::<Vec<_>>();
+                jobs.sort_unstable();
+                NodeStatus {
+                    id,
+                    name: node.name.clone(),
+                    jobs,
+                }
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|node| node.id);
+        nodes
+    }
+}
@@ -0,0 +1,177 @@
+//! Run processes with resource limits and measure their resource usage.
+//!
+//! The processes are run by the `oj-runner` binary, see `src/bin/oj-runner/`.
+
+use lazy_static::lazy_static;
+use std::env::consts::EXE_EXTENSION;
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::process::Command;
+use tokio::time;
+
+/// The runner is killed if it's still running after the wall-clock time limit plus this duration.
+const WAIT_EXTRA_DURATION: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    /// Path to the `oj-runner` binary, which is placed beside the server binary.
+    static ref RUNNER_PATH: PathBuf = std::env::current_exe()
+        .expect("failed to get the path of the current executable")
+        .with_file_name("oj-runner")
+        .with_extension(EXE_EXTENSION);
+}
+
+/// Resource limits of a process.
+pub struct Limits {
+    /// The limit of the CPU time (user + system).
+    pub cpu_time: Duration,
+    /// The hard limit of the wall-clock time, which prevents processes from sleeping forever.
+    pub wall_time: Duration,
+    /// The limit of the peak resident set size in bytes.
+    pub memory: usize,
+    /// The limit of the size of files written in bytes, `None` for no limit other than the
+    /// sandbox's.
+    pub file_size: Option<usize>,
+}
+
+/// The stdin, stdout and stderr of a process.
+pub struct Stdios {
+    pub stdin: Stdio,
+    pub stdout: Stdio,
+    pub stderr: Stdio,
+}
+
+/// Options of the sandbox, see `src/bin/oj-runner/sandbox.rs`.
+pub struct Sandbox<'a> {
+    /// Directories replaced with empty directories in the sandbox.
+    pub hidden_dirs: &'a [PathBuf],
+    /// The working directory, which must be an absolute path.
+    pub work_dir: &'a Path,
+    /// Whether the working directory is writable.
+    pub writable: bool,
+    /// Whether to forbid the program to create processes.
+    pub deny_fork: bool,
+}
+
+/// The exit status and resource usage of a finished process.
+pub struct Usage {
+    /// `None` if the runner is killed for not exiting in time.
+    pub status: Option<ExitStatus>,
+    /// The CPU time (user + system).
+    ///
+    /// It is greater than the limit if the process is killed for exceeding the limit.
+    pub cpu_time: Duration,
+    /// The wall-clock time.
+    ///
+    /// It is greater than the limit if the process is killed for exceeding the limit.
+    pub wall_time: Duration,
+    /// The peak resident set size in bytes.
+    ///
+    /// It is greater than the memory limit if the process is killed for exceeding the limit.
+    pub memory: usize,
+}
+
+/// Run a program with the given stdios under the limits, and wait for it to exit.
+///
+/// * `sandbox`: run the program in a sandbox if it's not `None`.
+/// * `report_path`: a path in a temporary directory used by `oj-runner` to report the usage.
+pub async fn run(
+    program: &OsStr,
+    args: &[&OsStr],
+    stdios: Stdios,
+    limits: &Limits,
+    sandbox: Option<&Sandbox<'_>>,
+    report_path: &Path,
+) -> io::Result<Usage> {
+    let mut runner = Command::new(RUNNER_PATH.as_os_str());
+    runner
+        .arg("--report")
+        .arg(report_path)
+        .arg("--memory-limit")
+        .arg(limits.memory.to_string())
+        .arg("--cpu-time-limit")
+        .arg(micros(limits.cpu_time).to_string())
+        .arg("--wall-time-limit")
+        .arg(micros(limits.wall_time).to_string());
+    if let Some(file_size) = limits.file_size {
+        runner.arg("--file-size-limit").arg(file_size.to_string());
+    }
+    if let Some(sandbox) = sandbox {
+        runner
+            .arg("--sandbox")
+            .arg("--work-dir")
+            .arg(sandbox.work_dir);
+        for dir in sandbox.hidden_dirs {
+            runner.arg("--hide").arg(dir);
+        }
+        if sandbox.writable {
+            runner.arg("--writable");
+        }
+        if sandbox.deny_fork {
+            runner.arg("--deny-fork");
+        }
+    }
+    runner
+        .arg("--")
+        .arg(program)
+        .args(args)
+        .stdin(stdios.stdin)
+        .stdout(stdios.stdout)
+        .stderr(stdios.stderr)
+        .kill_on_drop(true);
+
+    let start = Instant::now();
+    let mut child = runner.spawn()?;
+    // Close the stdios in this process, so that the ends of pipes to other processes are closed
+    // when the program exits.
+    drop(runner);
+    let wait_result = time::timeout(
+        limits.wall_time.saturating_add(WAIT_EXTRA_DURATION),
+        child.wait(),
+    )
+    .await;
+
+    match wait_result {
+        Err(_) => {
+            // Killing the runner also kills the program.
+            child.kill().await?;
+            Ok(Usage {
+                status: None,
+                cpu_time: Duration::ZERO,
+                wall_time: start.elapsed(),
+                memory: 0,
+            })
+        }
+        Ok(runner_status) => {
+            if !runner_status?.success() {
+                return Err(io::Error::other("oj-runner exited unsuccessfully"));
+            }
+            let report = fs::read_to_string(report_path).await?;
+            let report = report
+                .split_whitespace()
+                .map(str::parse::<i64>)
+                .collect::<Result<Vec<_>, _>>();
+            match report.as_deref() {
+                Ok(&[status, memory, cpu_time, wall_time]) => Ok(Usage {
+                    status: Some(ExitStatus::from_raw(status as i32)),
+                    cpu_time: Duration::from_micros(cpu_time as u64),
+                    wall_time: Duration::from_micros(wall_time as u64),
+                    memory: memory as usize,
+                }),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid report of oj-runner",
+                )),
+            }
+        }
+    }
+}
+
+/// Convert a duration to microseconds, saturating at `u64::MAX` (e.g. for `Duration::MAX`).
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
+}
@@ -0,0 +1,119 @@
+//! A job queue that serves jobs with higher priority first.
+
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// The priority of a job in the queue, from low to high.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    Rejudge,
+    Normal,
+    Contest,
+}
+
+impl JobPriority {
+    /// Get the priority of a new submission in the given contest, where zero means no contest.
+    pub fn of_submission(contest_id: i32) -> Self {
+        if contest_id == 0 {
+            Self::Normal
+        } else {
+            Self::Contest
+        }
+    }
+}
+
+/// The error of adding a job to a closed queue.
+#[derive(Debug)]
+pub struct QueueClosed;
+
+impl Display for QueueClosed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the job queue is closed")
+    }
+}
+
+impl std::error::Error for QueueClosed {}
+
+#[derive(Default)]
+struct QueueState {
+    /// Jobs ordered by priority, then by the order they are pushed.
+    heap: BinaryHeap<(JobPriority, Reverse<u64>, i32)>,
+    /// The number of jobs ever pushed, used to keep jobs of the same priority in FIFO order.
+    pushed: u64,
+    closed: bool,
+}
+
+/// A multi-producer multi-consumer priority queue of job ids.
+#[derive(Default)]
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+impl JobQueue {
+    pub fn push(&self, job_id: i32, priority: JobPriority) -> Result<(), QueueClosed> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(QueueClosed);
+        }
+        let order = Reverse(state.pushed);
+        state.pushed += 1;
+        state.heap.push((priority, order, job_id));
+        drop(state);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Wait for the job with the highest priority. Returns `None` after the queue is closed.
+    pub async fn pop(&self) -> Option<(i32, JobPriority)> {
+        loop {
+            // Register for notifications before checking the state, so that a notification
+            // between the check and the wait is not missed.
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+                if let Some((priority, _, job_id)) = state.heap.pop() {
+                    // `notify_one` stores at most one permit, so pass it on to another waiting
+                    // worker if there are more jobs.
+                    if !state.heap.is_empty() {
+                        self.notify.notify_one();
+                    }
+                    return Some((job_id, priority));
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Close the queue, so that no more jobs can be pushed or popped.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.notify.notify_waiters();
+    }
+
+    /// Get the number of queueing jobs of each priority.
+    pub fn lengths(&self) -> BTreeMap<JobPriority, usize> {
+        let mut lengths = [
+            JobPriority::Rejudge,
+            JobPriority::Normal,
+            JobPriority::Contest,
+        ]
+        .into_iter()
+        .map(|priority| (priority, 0))
+        .collect::<BTreeMap<_, _>>();
+        for (priority, _, _) in &self.state.lock().unwrap().heap {
+            *lengths.entry(*priority).or_default() += 1;
+        }
+        lengths
+    }
+}
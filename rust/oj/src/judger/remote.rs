@@ -0,0 +1,282 @@
+//! Run a remote judge node, which pulls jobs from the server and reports the progress of jobs
+//! through the `/nodes` API.
+
+use super::nodes::{HEARTBEAT_INTERVAL, PULL_TIMEOUT};
+use super::worker::{JobBackend, Worker};
+use crate::config::SharedConfig;
+use crate::db::case_results::CaseUpdate;
+use crate::db::enums::JobResult;
+use crate::db::jobs::JobInfoForJudger;
+use crate::db::subtask_results::SubtaskResult;
+use crate::error::*;
+use crate::routes::nodes::{
+    CaseUpdateRequest, FinishRequest, HeartbeatRequest, JobAssignment, RegisterRequest,
+    RegisterResponse,
+};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::{signal, time};
+
+/// The timeout of requests other than pulling jobs.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Options of a remote judge node.
+pub struct NodeOptions {
+    /// The base URL of the server, e.g. `http://127.0.0.1:12345`.
+    pub server: String,
+    /// The `server.node_token` in the config of the server.
+    pub token: String,
+    /// The name of the node shown in the judger status.
+    pub name: String,
+    pub workers: usize,
+    /// The directory to create the temporary directories of jobs in.
+    pub tmp_dir: PathBuf,
+}
+
+/// The connection of a node to the server, shared by the workers of the node.
+struct NodeClient {
+    client: reqwest::Client,
+    server: String,
+    token: String,
+    name: String,
+    /// The id of the node, which changes when the node registers again.
+    id: AtomicI32,
+    /// Jobs pulled from the server but not yet started by a worker.
+    pulled: Mutex<HashMap<i32, JobInfoForJudger>>,
+    /// Jobs assigned to the node and not finished, reported in heartbeats.
+    running: Mutex<HashSet<i32>>,
+}
+
+impl NodeClient {
+    fn url(&self, path: &str) -> String {
+        format!("{}/nodes{}", self.server.trim_end_matches('/'), path)
+    }
+
+    fn node_url(&self, path: &str) -> String {
+        self.url(&format!("/{}{}", self.id.load(Ordering::Relaxed), path))
+    }
+
+    /// Send a request with the node token. Errors if the response is not successful.
+    async fn send(&self, request: RequestBuilder) -> ApiResult<Response> {
+        let response = request.bearer_auth(&self.token).send().await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(response)
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            let error_type = if status == StatusCode::NOT_FOUND {
+                ApiErrorType::NotFound
+            } else {
+                ApiErrorType::External
+            };
+            Err(ApiError::new(
+                error_type,
+                format!("server responded {}: {}", status, body),
+            ))
+        }
+    }
+
+    async fn register(&self) -> ApiResult<()> {
+        let request = self
+            .client
+            .post(self.url(""))
+            .timeout(REQUEST_TIMEOUT)
+            .json(&RegisterRequest {
+                name: self.name.clone(),
+            });
+        let response = self.send(request).await?.json::<RegisterResponse>().await?;
+        self.id.store(response.id, Ordering::Relaxed);
+        log::info!("Registered as node {}", response.id);
+        Ok(())
+    }
+
+    async fn heartbeat(&self) -> ApiResult<()> {
+        let jobs = self.running.lock().unwrap().iter().cloned().collect();
+        let request = self
+            .client
+            .post(self.node_url("/heartbeat"))
+            .timeout(REQUEST_TIMEOUT)
+            .json(&HeartbeatRequest { jobs });
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Send heartbeats forever, and register again if the server doesn't know this node, e.g.
+    /// the server is restarted or this node was considered dead.
+    async fn keep_alive(self: Arc<Self>) {
+        let mut interval = time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            let result = match self.heartbeat().await {
+                Err(error) if matches!(error.error_type(), ApiErrorType::NotFound) => {
+                    log::warn!("Node unknown to the server, registering again");
+                    self.register().await
+                }
+                result => result,
+            };
+            if let Err(error) = result {
+                log::warn!("Failed to send heartbeat: {:?}", error);
+            }
+        }
+    }
+
+    /// Wait for a job from the server. Returns `None` if there is no job for a while.
+    async fn pull(&self) -> ApiResult<Option<i32>> {
+        let request = self
+            .client
+            .post(self.node_url("/jobs"))
+            .timeout(PULL_TIMEOUT + REQUEST_TIMEOUT);
+        let response = self.send(request).await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        let JobAssignment { job_id, job } = response.json().await?;
+        self.running.lock().unwrap().insert(job_id);
+        self.pulled.lock().unwrap().insert(job_id, job);
+        Ok(Some(job_id))
+    }
+}
+
+/// Receive jobs from the server and report the progress of jobs to the server.
+struct RemoteBackend {
+    client: Arc<NodeClient>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl JobBackend for RemoteBackend {
+    async fn next_job(&self) -> Option<i32> {
+        let mut shutdown = self.shutdown.clone();
+        loop {
+            if *shutdown.borrow() {
+                return None;
+            }
+            tokio::select! {
+                _ = shutdown.changed() => return None,
+                result = self.client.pull() => match result {
+                    Ok(Some(job_id)) => return Some(job_id),
+                    Ok(None) => {}
+                    Err(error) => {
+                        log::warn!("Failed to pull a job: {:?}", error);
+                        time::sleep(HEARTBEAT_INTERVAL).await;
+                    }
+                },
+            }
+        }
+    }
+
+    async fn fetch_job(&self, job_id: i32) -> ApiResult<Option<JobInfoForJudger>> {
+        match self.client.pulled.lock().unwrap().remove(&job_id) {
+            Some(info) => Ok(Some(info)),
+            None => Err(ApiError::new(
+                ApiErrorType::Internal,
+                format!("Job {} not pulled from the server", job_id),
+            )),
+        }
+    }
+
+    async fn update_case(
+        &self,
+        job_id: i32,
+        case_id: i32,
+        update: &CaseUpdate,
+        total_score: f64,
+    ) -> ApiResult<()> {
+        let request = self
+            .client
+            .client
+            .put(
+                self.client
+                    .node_url(&format!("/jobs/{}/cases/{}", job_id, case_id)),
+            )
+            .timeout(REQUEST_TIMEOUT)
+            .json(&CaseUpdateRequest {
+                update: update.clone(),
+                score: total_score,
+            });
+        self.client.send(request).await?;
+        Ok(())
+    }
+
+    async fn finish_job(
+        &self,
+        job_id: i32,
+        result: JobResult,
+        score: f64,
+        subtasks: Vec<SubtaskResult>,
+    ) -> ApiResult<()> {
+        let request = self
+            .client
+            .client
+            .put(self.client.node_url(&format!("/jobs/{}", job_id)))
+            .timeout(REQUEST_TIMEOUT)
+            .json(&FinishRequest {
+                result,
+                score,
+                subtasks,
+            });
+        let result = self.client.send(request).await;
+        self.client.running.lock().unwrap().remove(&job_id);
+        result?;
+        Ok(())
+    }
+}
+
+/// Register a remote judge node and run its workers until Ctrl+C is received.
+///
+/// After the first Ctrl+C, workers stop pulling jobs and the running jobs are finished.
+pub async fn run_node(config: SharedConfig, options: NodeOptions) -> ApiResult<()> {
+    let client = Arc::new(NodeClient {
+        client: reqwest::Client::new(),
+        server: options.server,
+        token: options.token,
+        name: options.name,
+        id: AtomicI32::new(0),
+        pulled: Mutex::new(HashMap::new()),
+        running: Mutex::new(HashSet::new()),
+    });
+    client.register().await?;
+    tokio::spawn(client.clone().keep_alive());
+
+    let hidden_dirs = super::sandbox_hidden_dirs(&options.tmp_dir, &[]);
+    let busy_workers = Arc::new(AtomicUsize::new(0));
+    let (shutdown_sender, shutdown) = watch::channel(false);
+
+    log::info!("Starting {} workers", options.workers);
+    let workers = (0..options.workers)
+        .map(|_| {
+            let worker = Worker {
+                config: config.clone(),
+                hidden_dirs: hidden_dirs.clone(),
+                tmp_dir: options.tmp_dir.clone(),
+                busy_workers: busy_workers.clone(),
+                backend: RemoteBackend {
+                    client: client.clone(),
+                    shutdown: shutdown.clone(),
+                },
+            };
+            tokio::spawn(worker.work())
+        })
+        .collect::<Vec<_>>();
+
+    signal::ctrl_c().await?;
+    shutdown_sender.send(true).ok();
+    if busy_workers.load(Ordering::Relaxed) > 0 {
+        eprintln!("Waiting for judgers to finish... Press Ctrl+C to forcefully exit.");
+    }
+    tokio::select! {
+        _ = async {
+            for worker in workers {
+                worker.await.ok();
+            }
+        } => {},
+        _ = signal::ctrl_c() => {
+            eprintln!("Ctrl+C received. Forcefully exiting.");
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! Run solutions on custom input without judging them, so that users can test their code without
+//! submitting it.
+
+use super::process::{Limits, Sandbox, Stdios};
+use super::worker::{self, LanguagePaths, SolutionCommand};
+use crate::config::SharedConfig;
+use crate::db::enums::JobResult;
+use crate::error::*;
+use serde::{Deserialize, Serialize};
+use std::env::consts::EXE_EXTENSION;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs::{self, File};
+use tokio::io::AsyncReadExt;
+use tokio::sync::Semaphore;
+
+/// The time limit of a run without a problem, before multiplied for the language.
+const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(1);
+/// The memory limit of a run without a problem, before multiplied for the language.
+const DEFAULT_MEMORY_LIMIT: usize = 256 << 20;
+/// The maximum time limit of a run, even if the problem has no time limit.
+const MAX_TIME_LIMIT: Duration = Duration::from_secs(10);
+/// The maximum memory limit of a run, even if the problem has no memory limit.
+const MAX_MEMORY_LIMIT: usize = 1 << 30;
+/// The limit of the size of files written by a run, including its stdout and stderr, which is
+/// smaller than the limit of judging since the output is returned in the response.
+const FILE_SIZE_LIMIT: usize = 16 << 20;
+/// The maximum number of bytes of the stdout and the stderr returned.
+const OUTPUT_LIMIT: usize = 65536;
+
+#[derive(Deserialize)]
+pub struct RunRequest {
+    pub source_code: String,
+    pub language: String,
+    /// The problem whose limits are used. The default limits are used if it's not set.
+    pub problem_id: Option<i32>,
+    /// The stdin of the solution.
+    #[serde(default)]
+    pub input: String,
+}
+
+#[derive(Serialize)]
+pub struct RunResult {
+    /// "Accepted" if the solution exits normally, but its output is not checked.
+    pub result: JobResult,
+    /// The CPU time in microseconds.
+    pub time: i64,
+    /// The memory in bytes.
+    pub memory: i64,
+    pub compiler_output: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs solutions in the server, at most as many at the same time as the workers.
+#[derive(Clone)]
+pub struct Runner {
+    pub(super) config: SharedConfig,
+    /// Directories hidden from the sandboxed compilers and solutions, in addition to the
+    /// directories of the test data.
+    pub(super) hidden_dirs: Vec<PathBuf>,
+    /// The directory to create the temporary directories of runs in.
+    pub(super) tmp_dir: PathBuf,
+    pub(super) permits: Arc<Semaphore>,
+}
+
+/// Read at most `limit` bytes of the output of a program.
+async fn read_output(path: &Path, limit: usize) -> ApiResult<String> {
+    let mut output = Vec::new();
+    File::open(path)
+        .await?
+        .take(limit as u64 + 1)
+        .read_to_end(&mut output)
+        .await?;
+    Ok(worker::truncate_info(&output, limit))
+}
+
+impl Runner {
+    /// Compile the solution and run it on the input, under the largest limits of the cases of the
+    /// problem, or the default limits.
+    pub async fn run(&self, request: RunRequest) -> ApiResult<RunResult> {
+        let config = self.config.load();
+        let language = config
+            .language_map
+            .get(&request.language)
+            .ok_or_else(|| ApiError::not_found(&format!("Language {}", request.language)))?;
+        let (time_limit, memory_limit) = match request.problem_id {
+            None => (DEFAULT_TIME_LIMIT, DEFAULT_MEMORY_LIMIT),
+            Some(problem_id) => {
+                let problem = config
+                    .problem_map
+                    .get(&problem_id)
+                    .ok_or_else(|| ApiError::not_found(&format!("Problem {}", problem_id)))?;
+                let time_limit = problem.cases.iter().map(|case| case.time_limit).max();
+                let memory_limit = problem.cases.iter().map(|case| case.memory_limit).max();
+                time_limit
+                    .zip(memory_limit)
+                    .unwrap_or((DEFAULT_TIME_LIMIT, DEFAULT_MEMORY_LIMIT))
+            }
+        };
+        let limits = Limits {
+            file_size: Some(FILE_SIZE_LIMIT),
+            ..worker::solution_limits(
+                language.time_limit(time_limit).min(MAX_TIME_LIMIT),
+                language.memory_limit(memory_limit).min(MAX_MEMORY_LIMIT),
+            )
+        };
+
+        let _permit = self.permits.acquire().await.map_err(|_| {
+            ApiError::new(ApiErrorType::Internal, String::from("the runner is closed"))
+        })?;
+
+        fs::create_dir_all(&self.tmp_dir).await?;
+        let tmp_dir = tempfile::Builder::new()
+            .prefix("run-")
+            .tempdir_in(&self.tmp_dir)?;
+        let source_file_path = tmp_dir.path().join(&language.file_name);
+        fs::write(&source_file_path, request.source_code).await?;
+        let input_path = tmp_dir.path().join("run.in");
+        fs::write(&input_path, request.input).await?;
+        let exe_file_path = tmp_dir
+            .path()
+            .join("oj-solution")
+            .with_extension(EXE_EXTENSION);
+        // the sandbox needs an absolute path of the working directory
+        let work_dir = fs::canonicalize(tmp_dir.path()).await?;
+        let hidden_dirs = self
+            .hidden_dirs
+            .iter()
+            .chain(&config.data_dirs)
+            .cloned()
+            .collect::<Vec<_>>();
+        let sandbox = |writable| Sandbox {
+            hidden_dirs: &hidden_dirs,
+            work_dir: &work_dir,
+            writable,
+            deny_fork: false,
+        };
+        let paths = LanguagePaths {
+            source_file: &source_file_path,
+            exe_file: &exe_file_path,
+            work_dir: &work_dir,
+        };
+
+        let compiler_output = match &language.command {
+            Some(command) => {
+                let (compilation_success, compilation_result) = worker::compile(
+                    0,
+                    command,
+                    &language.args,
+                    &paths,
+                    language.sandbox.then_some(&sandbox(true)),
+                    tmp_dir.path(),
+                    config.server.compiler_output_limit,
+                )
+                .await?;
+                if !compilation_success {
+                    return Ok(RunResult {
+                        result: JobResult::CompilationError,
+                        time: 0,
+                        memory: 0,
+                        compiler_output: compilation_result.info,
+                        stdout: String::new(),
+                        stderr: String::new(),
+                    });
+                }
+                compilation_result.info
+            }
+            None => String::new(),
+        };
+
+        let stdout_path = tmp_dir.path().join("run.out");
+        let stderr_path = tmp_dir.path().join("run.err");
+        let stdios = Stdios {
+            stdin: File::open(&input_path).await?.into_std().await.into(),
+            stdout: File::create(&stdout_path).await?.into_std().await.into(),
+            stderr: File::create(&stderr_path).await?.into_std().await.into(),
+        };
+        let (result, time, memory) = match worker::run_solution(
+            &SolutionCommand::new(language, &paths),
+            stdios,
+            &limits,
+            language.sandbox.then_some(&Sandbox {
+                deny_fork: language.forbid_fork,
+                ..sandbox(false)
+            }),
+            &tmp_dir.path().join("run.report"),
+        )
+        .await
+        {
+            Ok((time, memory)) => (JobResult::Accepted, time, memory),
+            Err(failure) => failure,
+        };
+        let result = RunResult {
+            result,
+            time: time.as_micros() as i64,
+            memory: memory as i64,
+            compiler_output,
+            stdout: read_output(&stdout_path, OUTPUT_LIMIT).await?,
+            stderr: read_output(&stderr_path, OUTPUT_LIMIT).await?,
+        };
+        tmp_dir.close()?;
+        Ok(result)
+    }
+}
@@ -1,15 +1,26 @@
 //! A judger worker runs at most a single job at the same time
 
-use super::TMP_DIR;
-use crate::config::{Config, ProblemType};
-use crate::db::case_results::{self, CaseUpdate};
-use crate::db::connection::ConnectionPool;
+use super::compare;
+use super::process::{self, Limits, Sandbox, Stdios};
+use super::queue::JobQueue;
+use super::JobRecorder;
+use crate::config::{
+    Language, ProblemType, SharedConfig, SubtaskPolicy, TestCase, WrongAnswerInfo,
+};
+use crate::db::case_results::CaseUpdate;
 use crate::db::enums::JobResult;
-use crate::db::jobs;
+use crate::db::jobs::{self, JobInfoForJudger};
+use crate::db::subtask_results::SubtaskResult;
 use crate::error::*;
 use std::env::consts::EXE_EXTENSION;
-use std::process::{Output, Stdio};
-use std::time::{Duration, Instant};
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::{self, OpenOptions};
 use tokio::process::Command;
 use tokio::sync::mpsc;
@@ -17,27 +28,159 @@ use tokio::time;
 
 const COMPILE_TIME_LIMIT: Duration = Duration::from_secs(60);
 const SPJ_TIME_LIMIT: Duration = Duration::from_secs(60);
-const WAIT_EXTRA_DURATION: Duration = Duration::from_secs(1);
 
-pub struct Worker {
-    pub config: Config,
-    pub job_receiver: async_channel::Receiver<i32>,
-    /// It never sends anything,
+/// Exit codes of testlib checkers.
+const TESTLIB_OK: i32 = 0;
+const TESTLIB_WA: i32 = 1;
+const TESTLIB_PE: i32 = 2;
+const TESTLIB_DIRT: i32 = 4;
+const TESTLIB_POINTS: i32 = 7;
+const TESTLIB_UNEXPECTED_EOF: i32 = 8;
+
+/// The maximum number of bytes of the message of a testlib checker shown as the info.
+const TESTLIB_INFO_LIMIT: usize = 1024;
+
+/// The wall-clock time limit of a solution is this ratio of its CPU time limit plus
+/// `WALL_TIME_EXTRA`, so that solutions sleeping or blocked can't hang the worker.
+const WALL_TIME_RATIO: u32 = 2;
+const WALL_TIME_EXTRA: Duration = Duration::from_secs(1);
+
+/// The limit of the size of files written by a compiler or a solution, including its stdout and
+/// stderr, even if the language is not sandboxed.
+const FILE_SIZE_LIMIT: usize = 1 << 30;
+
+/// The paths replacing the placeholders in the commands of a language.
+pub(super) struct LanguagePaths<'a> {
+    pub source_file: &'a Path,
+    pub exe_file: &'a Path,
+    pub work_dir: &'a Path,
+}
+
+impl LanguagePaths<'_> {
+    /// Replace `%INPUT%`, `%OUTPUT%` or `%DIR%` with the path, or keep other arguments as is.
+    pub fn substitute<'a>(&'a self, arg: &'a str) -> &'a OsStr {
+        match arg {
+            "%INPUT%" => self.source_file.as_os_str(),
+            "%OUTPUT%" => self.exe_file.as_os_str(),
+            "%DIR%" => self.work_dir.as_os_str(),
+            arg => arg.as_ref(),
+        }
+    }
+}
+
+/// The command to run the solution, with the placeholders replaced.
+pub(super) struct SolutionCommand<'a> {
+    command: &'a OsStr,
+    args: Vec<&'a OsStr>,
+}
+
+impl<'a> SolutionCommand<'a> {
+    /// Get the run command of the language with the placeholders replaced.
+    pub fn new(language: &'a Language, paths: &'a LanguagePaths) -> Self {
+        Self {
+            command: paths.substitute(&language.run_command),
+            args: language
+                .run_args
+                .iter()
+                .map(|arg| paths.substitute(arg))
+                .collect(),
+        }
+    }
+}
+
+/// Where a `Worker` receives jobs from and reports the progress of jobs to.
+pub trait JobBackend {
+    /// Wait for the next job. Returns `None` when the worker should stop.
+    async fn next_job(&self) -> Option<i32>;
+
+    /// Get the info of a job and mark it as running. Returns `None` if the job is canceled.
+    async fn fetch_job(&self, job_id: i32) -> ApiResult<Option<JobInfoForJudger>>;
+
+    /// Update the result of a single case and the total score of a job.
+    async fn update_case(
+        &self,
+        job_id: i32,
+        case_id: i32,
+        update: &CaseUpdate,
+        total_score: f64,
+    ) -> ApiResult<()>;
+
+    /// Set the final result, score and the scores of subtasks of a job.
+    async fn finish_job(
+        &self,
+        job_id: i32,
+        result: JobResult,
+        score: f64,
+        subtasks: Vec<SubtaskResult>,
+    ) -> ApiResult<()>;
+}
+
+/// Receive jobs from the queue of the server and save the results in the database.
+pub struct LocalBackend {
+    pub queue: Arc<JobQueue>,
+    pub recorder: JobRecorder,
+    /// It never sends anything, but is held until the worker is dropped, so that the receiver
+    /// knows all workers are finished once every sender is dropped.
     /// See <https://tokio.rs/tokio/topics/shutdown#waiting-for-things-to-finish-shutting-down>
-    pub finished_sender: mpsc::Sender<()>,
-    pub pool: ConnectionPool,
+    pub _finished_sender: mpsc::Sender<()>,
 }
 
-impl Worker {
+impl JobBackend for LocalBackend {
+    async fn next_job(&self) -> Option<i32> {
+        self.queue.pop().await.map(|(job_id, _)| job_id)
+    }
+
+    async fn fetch_job(&self, job_id: i32) -> ApiResult<Option<JobInfoForJudger>> {
+        jobs::fetch_job_for_judger(job_id, &self.recorder.pool)
+    }
+
+    async fn update_case(
+        &self,
+        job_id: i32,
+        case_id: i32,
+        update: &CaseUpdate,
+        total_score: f64,
+    ) -> ApiResult<()> {
+        self.recorder
+            .update_case(job_id, case_id, update, total_score)
+    }
+
+    async fn finish_job(
+        &self,
+        job_id: i32,
+        result: JobResult,
+        score: f64,
+        subtasks: Vec<SubtaskResult>,
+    ) -> ApiResult<()> {
+        self.recorder.finish_job(job_id, result, score, subtasks)
+    }
+}
+
+pub struct Worker<B> {
+    pub config: SharedConfig,
+    /// Directories hidden from the sandboxed compilers and solutions, in addition to the
+    /// directories of the test data.
+    pub hidden_dirs: Vec<PathBuf>,
+    /// The directory to create the temporary directories of jobs in.
+    pub tmp_dir: PathBuf,
+    /// The number of workers doing a job, shared by all workers.
+    pub busy_workers: Arc<AtomicUsize>,
+    pub backend: B,
+}
+
+impl<B: JobBackend> Worker<B> {
     pub async fn work(self) {
-        while let Ok(job_id) = self.job_receiver.recv().await {
-            if self.job_receiver.is_closed() {
-                break;
-            }
-            match self.do_job(job_id).await {
+        while let Some(job_id) = self.backend.next_job().await {
+            self.busy_workers.fetch_add(1, Ordering::Relaxed);
+            let result = self.do_job(job_id).await;
+            self.busy_workers.fetch_sub(1, Ordering::Relaxed);
+            match result {
                 Err(error) => {
                     log::error!("Judger error: (job_id = {}) {:?}", job_id, error);
-                    jobs::finish_job(job_id, &JobResult::SystemError, 0.0, &self.pool).ok();
+                    self.backend
+                        .finish_job(job_id, JobResult::SystemError, 0.0, Vec::new())
+                        .await
+                        .ok();
                 }
                 Ok(true) => log::info!("Job finished: {}", job_id),
                 Ok(false) => log::info!("Job skipped: {}", job_id),
@@ -49,291 +192,648 @@ impl Worker {
     /// Do a job and returns whether the job is actually done instead of skipped on success.
     async fn do_job(&self, job_id: i32) -> ApiResult<bool> {
         // get job info
-        let info = match jobs::fetch_job_for_judger(job_id, &self.pool)? {
+        let info = match self.backend.fetch_job(job_id).await? {
             Some(info) => info,
             None => return Ok(false), // job is canceled
         };
         log::info!("Job received: {}", job_id);
-        let language = self
-            .config
-            .language_map
-            .get(&info.language)
-            .ok_or_else(|| {
-                ApiError::new(
-                    ApiErrorType::Internal,
-                    format!("Unknown language {}", info.language),
-                )
-            })?;
-        let problem = self
-            .config
-            .problem_map
-            .get(&info.problem_id)
-            .ok_or_else(|| {
-                ApiError::new(
-                    ApiErrorType::Internal,
-                    format!("Unknown problem {}", info.problem_id),
-                )
-            })?;
+        // use the same config during the whole job even if it's reloaded
+        let config = self.config.load();
+        let language = config.language_map.get(&info.language).ok_or_else(|| {
+            ApiError::new(
+                ApiErrorType::Internal,
+                format!("Unknown language {}", info.language),
+            )
+        })?;
+        let problem = config.problem_map.get(&info.problem_id).ok_or_else(|| {
+            ApiError::new(
+                ApiErrorType::Internal,
+                format!("Unknown problem {}", info.problem_id),
+            )
+        })?;
 
         // create temporary directory
-        fs::create_dir_all(TMP_DIR).await?;
-        let tmp_dir = tempfile::tempdir_in(TMP_DIR)?;
+        fs::create_dir_all(&self.tmp_dir).await?;
+        let tmp_dir = tempfile::tempdir_in(&self.tmp_dir)?;
 
         // compile
-        case_results::update_case(
-            job_id,
-            0,
-            &CaseUpdate {
-                result: JobResult::Running,
-                time: 0,
-                info: String::new(),
-            },
-            0.0,
-            &self.pool,
-        )?;
+        self.backend
+            .update_case(
+                job_id,
+                0,
+                &CaseUpdate {
+                    result: JobResult::Running,
+                    time: 0,
+                    memory: 0,
+                    info: String::new(),
+                },
+                0.0,
+            )
+            .await?;
         let source_file_path = tmp_dir.path().join(&language.file_name);
         fs::write(&source_file_path, info.source_code).await?;
         let exe_file_path = tmp_dir
             .path()
             .join(format!("oj-solution-{}", job_id))
             .with_extension(EXE_EXTENSION);
-        let compilation_status = Command::new(&language.command)
-            .args(language.args.iter().map(|arg| match arg.as_str() {
-                "%INPUT%" => source_file_path.as_os_str(),
-                "%OUTPUT%" => exe_file_path.as_os_str(),
-                arg => arg.as_ref(),
-            }))
-            .stdin(Stdio::null())
-            .stderr(Stdio::null())
-            .stdout(Stdio::null())
-            .kill_on_drop(true)
-            .status();
-        let compilation_start = Instant::now();
-        let compilation_success = match time::timeout(COMPILE_TIME_LIMIT, compilation_status).await
-        {
-            Ok(Ok(status)) if status.success() => true,
-            _ => false,
+        // the sandbox needs an absolute path of the working directory
+        let work_dir = fs::canonicalize(tmp_dir.path()).await?;
+        let hidden_dirs = self
+            .hidden_dirs
+            .iter()
+            .chain(&config.data_dirs)
+            .cloned()
+            .collect::<Vec<_>>();
+        let sandbox = |writable| Sandbox {
+            hidden_dirs: &hidden_dirs,
+            work_dir: &work_dir,
+            writable,
+            deny_fork: false,
         };
-        let compilation_time = compilation_start.elapsed().as_micros();
-        let compilation_result = CaseUpdate {
-            result: if compilation_success {
-                JobResult::CompilationSuccess
-            } else {
-                JobResult::CompilationError
-            },
-            time: compilation_time as i64,
-            info: String::new(),
+        let paths = LanguagePaths {
+            source_file: &source_file_path,
+            exe_file: &exe_file_path,
+            work_dir: &work_dir,
+        };
+        let (compilation_success, compilation_result) = match &language.command {
+            Some(command) => {
+                compile(
+                    job_id,
+                    command,
+                    &language.args,
+                    &paths,
+                    language.sandbox.then_some(&sandbox(true)),
+                    tmp_dir.path(),
+                    config.server.compiler_output_limit,
+                )
+                .await?
+            }
+            // nothing to compile, the source code is run directly
+            None => (
+                true,
+                CaseUpdate {
+                    result: JobResult::CompilationSuccess,
+                    time: 0,
+                    memory: 0,
+                    info: String::new(),
+                },
+            ),
         };
-        case_results::update_case(job_id, 0, &compilation_result, 0.0, &self.pool)?;
+        self.backend
+            .update_case(job_id, 0, &compilation_result, 0.0)
+            .await?;
         if !compilation_success {
-            jobs::finish_job(job_id, &JobResult::CompilationError, 0.0, &self.pool)?;
+            self.backend
+                .finish_job(job_id, JobResult::CompilationError, 0.0, Vec::new())
+                .await?;
             return Ok(true);
         }
 
         // run on test cases
+        let solution = SolutionCommand::new(language, &paths);
+        let solution_sandbox = Sandbox {
+            deny_fork: language.forbid_fork,
+            ..sandbox(false)
+        };
         let mut total_score = 0.0;
         let mut job_result = JobResult::Accepted;
-        for subtask in &problem.packing {
-            let mut subtask_skipped = false;
-            let mut subtask_score = 0.0;
-            for case_id in subtask.iter().cloned() {
+        let ratio = match problem.tp {
+            ProblemType::DynamicRanking { ratio } => 1.0 - ratio,
+            _ => 1.0,
+        };
+        let mut subtask_results = Vec::with_capacity(problem.subtasks.len());
+        // Whether each subtask got its full score.
+        let mut subtask_passed: Vec<bool> = Vec::with_capacity(problem.subtasks.len());
+        for (subtask_id, subtask) in problem.subtasks.iter().enumerate() {
+            // A subtask is skipped entirely if any of its dependencies didn't get the full score.
+            let mut subtask_skipped = subtask
+                .dependencies
+                .iter()
+                .any(|&dependency| !subtask_passed[dependency]);
+            let mut all_points = Vec::with_capacity(subtask.cases.len());
+            for case_id in subtask.cases.iter().cloned() {
                 // handle skipped
                 if subtask_skipped {
-                    case_results::update_case(
+                    self.backend
+                        .update_case(
+                            job_id,
+                            case_id + 1,
+                            &CaseUpdate {
+                                result: JobResult::Skipped,
+                                time: 0,
+                                memory: 0,
+                                info: String::new(),
+                            },
+                            total_score,
+                        )
+                        .await?;
+                    all_points.push(0.0);
+                    continue;
+                }
+
+                // set state to running
+                self.backend
+                    .update_case(
                         job_id,
                         case_id + 1,
                         &CaseUpdate {
-                            result: JobResult::Skipped,
+                            result: JobResult::Running,
                             time: 0,
+                            memory: 0,
                             info: String::new(),
                         },
                         total_score,
-                        &self.pool,
-                    )?;
-                    continue;
-                }
-
-                // set state to running
-                case_results::update_case(
-                    job_id,
-                    case_id + 1,
-                    &CaseUpdate {
-                        result: JobResult::Running,
-                        time: 0,
-                        info: String::new(),
-                    },
-                    total_score,
-                    &self.pool,
-                )?;
+                    )
+                    .await?;
 
                 let case = &problem.cases[case_id as usize];
+                let limits = solution_limits(
+                    language.time_limit(case.time_limit),
+                    language.memory_limit(case.memory_limit),
+                );
+                let path_prefix = tmp_dir.path().join(format!("{}-{}", job_id, case_id));
+                let solution_sandbox = language.sandbox.then_some(&solution_sandbox);
 
-                // get stdin and stdout for the child
-                let input_file = OpenOptions::new()
-                    .read(true)
-                    .open(&case.input_file)
-                    .await?
-                    .into_std()
-                    .await;
-                // use file for spj and pipe for others
-                let spj_output_path = tmp_dir.path().join(format!("{}-{}.out", job_id, case_id));
-                let solution_stdout = match problem.tp {
-                    ProblemType::Spj { .. } => OpenOptions::new()
-                        .create_new(true)
-                        .write(true)
-                        .open(&spj_output_path)
+                // get judge result
+                let (case_update, points) = match &problem.tp {
+                    ProblemType::Interactive { command, args } => {
+                        Self::run_interactive(
+                            &solution,
+                            case,
+                            &limits,
+                            command,
+                            args,
+                            solution_sandbox,
+                            &path_prefix,
+                        )
                         .await?
-                        .into_std()
-                        .await
-                        .into(),
-                    _ => Stdio::piped(),
+                    }
+                    tp => {
+                        Self::run_with_files(
+                            &solution,
+                            case,
+                            &limits,
+                            tp,
+                            problem.wrong_answer_info,
+                            solution_sandbox,
+                            &path_prefix,
+                        )
+                        .await?
+                    }
                 };
 
-                // construct solution command
-                let mut solution_command = Command::new(&exe_file_path);
-                solution_command
-                    .stdin(input_file)
-                    .stdout(solution_stdout)
-                    .stderr(Stdio::null())
-                    .kill_on_drop(true);
+                // update case result in database
+                self.backend
+                    .update_case(job_id, case_id + 1, &case_update, total_score)
+                    .await?;
 
-                // get judge result
-                let case_update = match Self::run_solution(solution_command, case.time_limit).await
-                {
-                    Ok((solution_output, solution_time)) => {
-                        let mut info = String::new();
-                        let result = match &problem.tp {
-                            // check answer
-                            ProblemType::Spj { command, args } => {
-                                let spj_output = Command::new(command)
-                                    .args(args.into_iter().map(|arg| match arg.as_str() {
-                                        "%OUTPUT%" => spj_output_path.as_os_str(),
-                                        "%ANSWER%" => case.answer_file.as_ref(),
-                                        arg => arg.as_ref(),
-                                    }))
-                                    .kill_on_drop(true)
-                                    .output();
-                                match time::timeout(SPJ_TIME_LIMIT, spj_output).await {
-                                    Ok(Ok(spj_output)) if spj_output.status.success() => {
-                                        match String::from_utf8(spj_output.stdout) {
-                                            Err(_) => JobResult::SPJError,
-                                            Ok(spj_output) => {
-                                                let mut lines = spj_output.lines();
-                                                let result = match lines.next() {
-                                                    None => JobResult::SPJError,
-                                                    Some(result) => {
-                                                        if result == "Accepted" {
-                                                            JobResult::Accepted
-                                                        } else {
-                                                            JobResult::WrongAnswer
-                                                        }
-                                                    }
-                                                };
-                                                if let Some(spj_info) = lines.next() {
-                                                    info = String::from(spj_info);
-                                                }
-                                                result
-                                            }
-                                        }
-                                    }
-                                    _ => JobResult::SPJError,
-                                }
-                            }
-                            tp => {
-                                let answer = fs::read_to_string(&case.answer_file).await?;
-                                let correct = match String::from_utf8(solution_output.stdout) {
-                                    Err(_) => false,
-                                    Ok(output) => match tp {
-                                        ProblemType::Strict => output == answer,
-                                        _ => Self::as_standard_check_iter(&output)
-                                            .eq(Self::as_standard_check_iter(&answer)),
-                                    },
-                                };
-                                if correct {
-                                    JobResult::Accepted
-                                } else {
-                                    JobResult::WrongAnswer
-                                }
-                            }
-                        };
-                        CaseUpdate {
-                            result,
-                            time: solution_time.as_micros() as i64,
-                            info,
-                        }
-                    }
-                    Err((result, solution_time)) => CaseUpdate {
+                if case_update.result != JobResult::Accepted && job_result == JobResult::Accepted {
+                    job_result = case_update.result;
+                }
+                all_points.push(points);
+                // The remaining cases can't change the score of the subtask.
+                subtask_skipped = match subtask.policy {
+                    SubtaskPolicy::Sum => false,
+                    SubtaskPolicy::MinRatio => points <= 0.0,
+                    SubtaskPolicy::AllOrNothing => points < 1.0,
+                };
+            }
+
+            let min_points = all_points.iter().cloned().fold(1.0_f64, f64::min);
+            let subtask_score = match subtask.policy {
+                SubtaskPolicy::Sum => subtask
+                    .cases
+                    .iter()
+                    .zip(&all_points)
+                    .map(|(&case_id, points)| problem.cases[case_id as usize].score * points)
+                    .sum(),
+                SubtaskPolicy::MinRatio => subtask.score * min_points,
+                SubtaskPolicy::AllOrNothing if min_points >= 1.0 => subtask.score,
+                SubtaskPolicy::AllOrNothing => 0.0,
+            };
+            subtask_passed.push(min_points >= 1.0);
+            total_score += subtask_score * ratio;
+            subtask_results.push(SubtaskResult {
+                id: subtask_id as i32 + 1,
+                score: subtask_score * ratio,
+                max_score: subtask.score * ratio,
+            });
+        }
+
+        self.backend
+            .finish_job(job_id, job_result, total_score, subtask_results)
+            .await?;
+
+        tmp_dir.close()?;
+
+        Ok(true)
+    }
+
+    /// Run the solution with the input file of the test case as stdin, and check its output.
+    /// Returns the result and the fraction of the score of the case earned.
+    ///
+    /// * `path_prefix`: the prefix of paths of the temporary files of the case.
+    async fn run_with_files(
+        solution: &SolutionCommand<'_>,
+        case: &TestCase,
+        limits: &Limits,
+        tp: &ProblemType,
+        wrong_answer_info: WrongAnswerInfo,
+        sandbox: Option<&Sandbox<'_>>,
+        path_prefix: &Path,
+    ) -> ApiResult<(CaseUpdate, f64)> {
+        // get stdin and stdout for the child, the output is written to a file to be checked
+        let input_file = OpenOptions::new()
+            .read(true)
+            .open(&case.input_file)
+            .await?
+            .into_std()
+            .await;
+        let output_path = path_prefix.with_extension("out");
+        let output_file = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&output_path)
+            .await?
+            .into_std()
+            .await;
+
+        let (solution_time, solution_memory) = match run_solution(
+            solution,
+            Stdios {
+                stdin: input_file.into(),
+                stdout: output_file.into(),
+                stderr: Stdio::null(),
+            },
+            limits,
+            sandbox,
+            &path_prefix.with_extension("report"),
+        )
+        .await
+        {
+            Ok(usage) => usage,
+            Err((result, solution_time, solution_memory)) => {
+                return Ok((
+                    CaseUpdate {
                         result,
                         time: solution_time.as_micros() as i64,
+                        memory: solution_memory as i64,
                         info: String::new(),
                     },
-                };
+                    0.0,
+                ))
+            }
+        };
 
-                // update case result in database
-                case_results::update_case(
-                    job_id,
-                    case_id + 1,
-                    &case_update,
-                    total_score,
-                    &self.pool,
-                )?;
-
-                if case_update.result == JobResult::Accepted {
-                    let ratio = match problem.tp {
-                        ProblemType::DynamicRanking { ratio } => 1.0 - ratio,
-                        _ => 1.0,
-                    };
-                    subtask_score += case.score * ratio;
-                } else {
-                    subtask_score = 0.0;
-                    subtask_skipped = true;
-                    if job_result == JobResult::Accepted {
-                        job_result = case_update.result;
+        // check answer
+        let (result, info, points) = match tp {
+            ProblemType::Spj { command, args } => {
+                let spj_output = Command::new(command)
+                    .args(args.iter().map(|arg| match arg.as_str() {
+                        "%INPUT%" => case.input_file.as_ref(),
+                        "%OUTPUT%" => output_path.as_os_str(),
+                        "%ANSWER%" => case.answer_file.as_ref(),
+                        arg => arg.as_ref(),
+                    }))
+                    .kill_on_drop(true)
+                    .output();
+                let (result, info) = match time::timeout(SPJ_TIME_LIMIT, spj_output).await {
+                    Ok(Ok(spj_output)) if spj_output.status.success() => {
+                        Self::parse_spj_output(spj_output.stdout)
                     }
+                    _ => (JobResult::SPJError, String::new()),
+                };
+                (result, info, Self::full_points(result))
+            }
+            ProblemType::Testlib { command, args } => {
+                let checker_output = Command::new(command)
+                    .args(args)
+                    .arg(&case.input_file)
+                    .arg(&output_path)
+                    .arg(&case.answer_file)
+                    .kill_on_drop(true)
+                    .output();
+                match time::timeout(SPJ_TIME_LIMIT, checker_output).await {
+                    Ok(Ok(checker_output)) => {
+                        Self::parse_testlib_output(checker_output.status, &checker_output.stderr)
+                    }
+                    _ => (JobResult::SPJError, String::new(), 0.0),
                 }
             }
-            total_score += subtask_score;
-        }
+            tp => {
+                let answer = fs::read_to_string(&case.answer_file).await?;
+                let output = fs::read(&output_path).await?;
+                match compare::compare(&output, &answer, tp, wrong_answer_info) {
+                    Ok(()) => (JobResult::Accepted, String::new(), 1.0),
+                    Err(info) => (JobResult::WrongAnswer, info, 0.0),
+                }
+            }
+        };
+        Ok((
+            CaseUpdate {
+                result,
+                time: solution_time.as_micros() as i64,
+                memory: solution_memory as i64,
+                info,
+            },
+            points,
+        ))
+    }
 
-        jobs::finish_job(job_id, &job_result, total_score, &self.pool)?;
+    /// Run the solution and the interactor with the stdout of each one connected to the stdin of
+    /// the other. The interactor writes the verdict to `%OUTPUT%` in the same format as the spj.
+    /// Returns the result and the fraction of the score of the case earned.
+    ///
+    /// * `path_prefix`: the prefix of paths of the temporary files of the case.
+    async fn run_interactive(
+        solution: &SolutionCommand<'_>,
+        case: &TestCase,
+        limits: &Limits,
+        command: &str,
+        args: &[String],
+        sandbox: Option<&Sandbox<'_>>,
+        path_prefix: &Path,
+    ) -> ApiResult<(CaseUpdate, f64)> {
+        let (solution_stdin, interactor_stdout) = io::pipe()?;
+        let (interactor_stdin, solution_stdout) = io::pipe()?;
+        let report_path = path_prefix.with_extension("report");
+        let interactor_report_path = path_prefix.with_extension("interactor.report");
+        let verdict_path = path_prefix.with_extension("verdict");
+        let interactor_args = args
+            .iter()
+            .map(|arg| match arg.as_str() {
+                "%INPUT%" => case.input_file.as_ref(),
+                "%ANSWER%" => case.answer_file.as_ref(),
+                "%OUTPUT%" => verdict_path.as_os_str(),
+                arg => arg.as_ref(),
+            })
+            .collect::<Vec<_>>();
+        // The interactor may run as long as the solution, and a bit longer to write the verdict.
+        let interactor_limits = Limits {
+            cpu_time: limits.wall_time,
+            wall_time: limits.wall_time.saturating_add(WALL_TIME_EXTRA),
+            memory: limits.memory,
+            file_size: None,
+        };
 
-        tmp_dir.close()?;
+        let (solution_usage, interactor_usage) = tokio::join!(
+            run_solution(
+                solution,
+                Stdios {
+                    stdin: solution_stdin.into(),
+                    stdout: solution_stdout.into(),
+                    stderr: Stdio::null(),
+                },
+                limits,
+                sandbox,
+                &report_path,
+            ),
+            process::run(
+                command.as_ref(),
+                &interactor_args,
+                Stdios {
+                    stdin: interactor_stdin.into(),
+                    stdout: interactor_stdout.into(),
+                    stderr: Stdio::null(),
+                },
+                &interactor_limits,
+                None,
+                &interactor_report_path,
+            ),
+        );
 
-        Ok(true)
+        // The verdict of the interactor, or `None` if it failed without one.
+        let verdict = match &interactor_usage {
+            Ok(usage) if usage.status.is_some_and(|status| status.success()) => {
+                Some(match fs::read(&verdict_path).await {
+                    Ok(verdict) => Self::parse_spj_output(verdict),
+                    Err(_) => (JobResult::SPJError, String::new()),
+                })
+            }
+            _ => None,
+        };
+        let (result, info, solution_time, solution_memory) = match (solution_usage, verdict) {
+            // The solution usually fails to write after the interactor rejects it and exits.
+            (
+                Err((JobResult::RuntimeError, solution_time, solution_memory)),
+                Some((JobResult::WrongAnswer, info)),
+            ) => (JobResult::WrongAnswer, info, solution_time, solution_memory),
+            // Otherwise the solution failed on its own, which may make the interactor fail.
+            (Err((result, solution_time, solution_memory)), _) => {
+                (result, String::new(), solution_time, solution_memory)
+            }
+            (Ok((solution_time, solution_memory)), Some((result, info))) => {
+                (result, info, solution_time, solution_memory)
+            }
+            // the interactor writes to the solution after the solution exits
+            (Ok((solution_time, solution_memory)), None)
+                if interactor_usage.is_ok_and(|usage| {
+                    usage.status.and_then(|status| status.signal()) == Some(libc::SIGPIPE)
+                }) =>
+            {
+                (
+                    JobResult::WrongAnswer,
+                    String::from("The solution exited before the interaction finished."),
+                    solution_time,
+                    solution_memory,
+                )
+            }
+            (Ok((solution_time, solution_memory)), None) => (
+                JobResult::SPJError,
+                String::new(),
+                solution_time,
+                solution_memory,
+            ),
+        };
+        Ok((
+            CaseUpdate {
+                result,
+                time: solution_time.as_micros() as i64,
+                memory: solution_memory as i64,
+                info,
+            },
+            Self::full_points(result),
+        ))
     }
 
-    /// Run the command which is a compiled solution.
-    /// Return (output, time) on success.
-    /// Return (result, time) on failure.
-    async fn run_solution(
-        mut command: Command,
-        time_limit: Duration,
-    ) -> Result<(Output, Duration), (JobResult, Duration)> {
-        let run_start = Instant::now();
-        let child = command
-            .spawn()
-            .map_err(|_| (JobResult::RuntimeError, run_start.elapsed()))?;
-        let output_timeout = time::timeout(
-            time_limit.saturating_add(WAIT_EXTRA_DURATION),
-            child.wait_with_output(),
-        )
+    /// Parse the output of the spj or the interactor: the first line is the result, which is
+    /// "Accepted" or anything else for "Wrong Answer", and the optional second line is the info.
+    fn parse_spj_output(output: Vec<u8>) -> (JobResult, String) {
+        match String::from_utf8(output) {
+            Err(_) => (JobResult::SPJError, String::new()),
+            Ok(output) => {
+                let mut lines = output.lines();
+                let result = match lines.next() {
+                    None => JobResult::SPJError,
+                    Some("Accepted") => JobResult::Accepted,
+                    Some(_) => JobResult::WrongAnswer,
+                };
+                (result, lines.next().map(String::from).unwrap_or_default())
+            }
+        }
+    }
+
+    /// Interpret the exit status and the stderr of a testlib checker. Returns the result, the
+    /// info and the fraction of the score earned.
+    ///
+    /// The message of `quitp` starts with the points, which is the fraction of the score here.
+    fn parse_testlib_output(status: ExitStatus, stderr: &[u8]) -> (JobResult, String, f64) {
+        let message = String::from_utf8_lossy(stderr);
+        let message = message.lines().next().unwrap_or_default().trim();
+        let info = truncate_info(message.as_bytes(), TESTLIB_INFO_LIMIT);
+        match status.code() {
+            Some(TESTLIB_OK) => (JobResult::Accepted, info, 1.0),
+            Some(TESTLIB_WA | TESTLIB_PE | TESTLIB_DIRT | TESTLIB_UNEXPECTED_EOF) => {
+                (JobResult::WrongAnswer, info, 0.0)
+            }
+            Some(TESTLIB_POINTS) => {
+                let points = message
+                    .strip_prefix("points")
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(|points| points.parse::<f64>().ok());
+                match points {
+                    Some(points) if points >= 1.0 => (JobResult::Accepted, info, 1.0),
+                    Some(points) if points >= 0.0 => (JobResult::WrongAnswer, info, points),
+                    _ => (JobResult::SPJError, info, 0.0),
+                }
+            }
+            _ => (JobResult::SPJError, info, 0.0),
+        }
+    }
+
+    /// The fraction of the score earned by a case with the result and no partial points.
+    fn full_points(result: JobResult) -> f64 {
+        if result == JobResult::Accepted {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Compile the solution. Returns whether the compilation succeeded and the result of case 0.
+///
+/// * `tmp_dir`: the temporary directory of the job.
+pub(super) async fn compile(
+    job_id: i32,
+    command: &str,
+    args: &[String],
+    paths: &LanguagePaths<'_>,
+    sandbox: Option<&Sandbox<'_>>,
+    tmp_dir: &Path,
+    output_limit: usize,
+) -> ApiResult<(bool, CaseUpdate)> {
+    let compilation_report_path = tmp_dir.join(format!("{}.report", job_id));
+    let compilation_args = args
+        .iter()
+        .map(|arg| paths.substitute(arg))
+        .collect::<Vec<_>>();
+    // both stdout and stderr of the compiler are written to the compiler output file
+    let compiler_output_path = tmp_dir.join(format!("{}.compile.log", job_id));
+    let compiler_output_file = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&compiler_output_path)
+        .await?
+        .into_std()
         .await;
-        let time = run_start.elapsed();
-        match output_timeout {
-            Ok(output_result) if time <= time_limit => match output_result {
-                Ok(output) if output.status.success() => Ok((output, time)),
-                _ => Err((JobResult::RuntimeError, time)),
+    let compilation_usage = process::run(
+        paths.substitute(command),
+        &compilation_args,
+        Stdios {
+            stdin: Stdio::null(),
+            stdout: compiler_output_file.try_clone()?.into(),
+            stderr: compiler_output_file.into(),
+        },
+        &Limits {
+            cpu_time: COMPILE_TIME_LIMIT,
+            wall_time: COMPILE_TIME_LIMIT,
+            memory: usize::MAX,
+            file_size: Some(FILE_SIZE_LIMIT),
+        },
+        sandbox,
+        &compilation_report_path,
+    )
+    .await;
+    let compilation_success = matches!(
+        &compilation_usage,
+        Ok(process::Usage { status: Some(status), cpu_time, wall_time, .. })
+            if status.success()
+                && *cpu_time <= COMPILE_TIME_LIMIT
+                && *wall_time <= COMPILE_TIME_LIMIT
+    );
+    let compilation_time = compilation_usage.map_or(0, |usage| usage.cpu_time.as_micros());
+    Ok((
+        compilation_success,
+        CaseUpdate {
+            result: if compilation_success {
+                JobResult::CompilationSuccess
+            } else {
+                JobResult::CompilationError
             },
-            _ => Err((JobResult::TimeLimitExceeded, time)),
+            time: compilation_time as i64,
+            memory: 0,
+            info: truncate_info(&fs::read(&compiler_output_path).await?, output_limit),
+        },
+    ))
+}
+
+/// Get the limits of a solution with the time and memory limits.
+pub(super) fn solution_limits(time_limit: Duration, memory_limit: usize) -> Limits {
+    Limits {
+        cpu_time: time_limit,
+        wall_time: time_limit
+            .saturating_mul(WALL_TIME_RATIO)
+            .saturating_add(WALL_TIME_EXTRA),
+        memory: memory_limit,
+        file_size: Some(FILE_SIZE_LIMIT),
+    }
+}
+
+/// Run the solution under the limits.
+/// Return (CPU time, memory) on success.
+/// Return (result, CPU time, memory) on failure.
+pub(super) async fn run_solution(
+    solution: &SolutionCommand<'_>,
+    stdios: Stdios,
+    limits: &Limits,
+    sandbox: Option<&Sandbox<'_>>,
+    report_path: &Path,
+) -> Result<(Duration, usize), (JobResult, Duration, usize)> {
+    let usage = process::run(
+        solution.command,
+        &solution.args,
+        stdios,
+        limits,
+        sandbox,
+        report_path,
+    )
+    .await
+    .map_err(|_| (JobResult::RuntimeError, Duration::ZERO, 0))?;
+    let time = usage.cpu_time;
+    if usage.memory > limits.memory {
+        Err((JobResult::MemoryLimitExceeded, time, usage.memory))
+    } else if time > limits.cpu_time || usage.wall_time > limits.wall_time {
+        Err((JobResult::TimeLimitExceeded, time, usage.memory))
+    } else {
+        match usage.status {
+            Some(status) if status.success() => Ok((time, usage.memory)),
+            Some(_) => Err((JobResult::RuntimeError, time, usage.memory)),
+            None => Err((JobResult::TimeLimitExceeded, time, usage.memory)),
         }
     }
+}
 
-    /// Compare two outputs in standard mode by comparing the return value of this function
-    fn as_standard_check_iter(output: &str) -> impl Iterator<Item = &str> {
-        output
-            .lines()
-            .map(|line| line.trim_end())
-            .rev()
-            .skip_while(|line| line.is_empty())
+/// Convert the output of a program to a string, truncated to at most `limit` bytes.
+pub(super) fn truncate_info(output: &[u8], limit: usize) -> String {
+    let mut info = String::from_utf8_lossy(output).into_owned();
+    if info.len() > limit {
+        let mut end = limit;
+        while !info.is_char_boundary(end) {
+            end -= 1;
+        }
+        info.truncate(end);
+        info.push_str("\n...");
     }
+    info
 }
@@ -1,22 +1,25 @@
 use actix_web::middleware::{DefaultHeaders, Logger};
 use actix_web::web::{self, JsonConfig, PathConfig, QueryConfig};
-use actix_web::{post, App, HttpServer, Responder, ResponseError};
+use actix_web::{post, App, HttpServer, ResponseError};
 use tokio::fs;
 
+use oj::auth::{self, Identity};
 use oj::cli;
-use oj::config::Config;
+use oj::config::{self, Config, SharedConfig};
 use oj::db::{connection, migration};
-use oj::error::ApiError;
-use oj::judger;
+use oj::error::{ApiError, ApiResult};
+use oj::judger::{self, events::JobEvents};
 use oj::routes;
 
 // DO NOT REMOVE: used in automatic testing
+/// Only admins can shut down the server when authentication is enabled.
 #[post("/internal/exit")]
 #[allow(unreachable_code)]
-async fn exit() -> impl Responder {
+async fn exit(identity: Identity) -> ApiResult<String> {
+    identity.require_admin()?;
     log::info!("Shutdown as requested");
     std::process::exit(0);
-    format!("Exited")
+    Ok(String::from("Exited"))
 }
 
 #[actix_web::main]
@@ -36,7 +39,7 @@ async fn main() -> std::io::Result<()> {
         args.flush_data
     );
 
-    let config_json = fs::read_to_string(args.config).await?;
+    let config_json = fs::read_to_string(&args.config).await?;
     let config = match Config::new(&config_json) {
         Ok(config) => config,
         Err(err) => {
@@ -47,38 +50,59 @@ async fn main() -> std::io::Result<()> {
 
     let pool = connection::connection_pool();
     migration::initialize_database(args.flush_data, &pool);
+    if let Err(err) = auth::init_root_password(&pool) {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(1);
+    }
 
-    let (adder, waiter) = judger::create_judger(pool.clone(), &config);
+    let events = JobEvents::new();
+    let server = config.server.clone();
+    let config = SharedConfig::new(config, &args.config);
+    let (adder, waiter, nodes, runner) =
+        judger::create_judger(pool.clone(), config.clone(), events.clone());
 
-    let Config {
-        server,
-        problem_map,
-        language_map,
-    } = config;
+    tokio::spawn(config::reload_on_sighup(config.clone(), pool.clone()));
+
+    let bind_address = (server.bind_address.clone(), server.bind_port);
 
     HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
-            .wrap(DefaultHeaders::new().add(("Access-Control-Allow-Origin", "*")))
+            .wrap(
+                DefaultHeaders::new()
+                    .add(("Access-Control-Allow-Origin", "*"))
+                    .add((
+                        "Access-Control-Expose-Headers",
+                        routes::jobs::TOTAL_COUNT_HEADER,
+                    )),
+            )
             .app_data(JsonConfig::default().error_handler(ApiError::invalid_argument))
             .app_data(PathConfig::default().error_handler(ApiError::invalid_argument))
             .app_data(QueryConfig::default().error_handler(ApiError::invalid_argument))
-            .app_data(web::Data::new(problem_map.clone()))
-            .app_data(web::Data::new(language_map.clone()))
+            .app_data(web::Data::new(server.clone()))
+            .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(adder.clone()))
+            .app_data(web::Data::new(events.clone()))
+            .app_data(web::Data::new(nodes.clone()))
+            .app_data(web::Data::new(runner.clone()))
+            .service(routes::auth::routes())
+            .service(routes::config::routes())
             .service(routes::jobs::routes())
+            .service(routes::judger::routes())
             .service(routes::users::routes())
             .service(routes::contests::routes())
             .service(routes::problems::routes())
             .service(routes::languages::routes())
+            .service(routes::nodes::routes())
+            .service(routes::runs::routes())
             // DO NOT REMOVE: used in automatic testing
             .service(exit)
             .default_service(web::to(|| async {
                 ApiError::not_found("Route").error_response()
             }))
     })
-    .bind((server.bind_address, server.bind_port))?
+    .bind(bind_address)?
     .run()
     .await?;
 
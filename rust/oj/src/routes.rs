@@ -1,7 +1,12 @@
 //! API routes.
 
+pub mod auth;
+pub mod config;
 pub mod contests;
 pub mod jobs;
+pub mod judger;
 pub mod languages;
+pub mod nodes;
 pub mod problems;
+pub mod runs;
 pub mod users;
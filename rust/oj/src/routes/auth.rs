@@ -0,0 +1,42 @@
+//! `/auth` API routes.
+
+use crate::auth::{self, Identity};
+use crate::db::connection::ConnectionPool;
+use crate::db::sessions;
+use crate::error::*;
+use actix_web::{post, web, HttpRequest, HttpResponse, Responder, Scope};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct LoginData {
+    name: String,
+    password: String,
+}
+
+#[post("/login")]
+async fn login(
+    data: web::Json<LoginData>,
+    pool: web::Data<ConnectionPool>,
+) -> ApiResult<impl Responder> {
+    Ok(web::Json(
+        web::block(move || auth::login(&data.name, &data.password, &pool)).await??,
+    ))
+}
+
+#[post("/logout")]
+async fn logout(
+    req: HttpRequest,
+    identity: Identity,
+    pool: web::Data<ConnectionPool>,
+) -> ApiResult<impl Responder> {
+    identity.require_login()?;
+    if let Some(token) = auth::bearer_token(&req) {
+        let token_hash = auth::hash_token(&token);
+        web::block(move || sessions::delete_session(&token_hash, &pool)).await??;
+    }
+    Ok(HttpResponse::Ok())
+}
+
+pub fn routes() -> Scope {
+    web::scope("/auth").service(login).service(logout)
+}
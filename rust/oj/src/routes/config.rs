@@ -0,0 +1,34 @@
+//! `/config` API routes.
+
+use crate::auth::Identity;
+use crate::config::SharedConfig;
+use crate::db::connection::ConnectionPool;
+use crate::error::*;
+use actix_web::{post, web, Responder, Scope};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ReloadResponse {
+    problem_count: usize,
+    language_count: usize,
+}
+
+/// Reload the problems and languages from the config file.
+#[post("/reload")]
+async fn reload(
+    identity: Identity,
+    config: web::Data<SharedConfig>,
+    pool: web::Data<ConnectionPool>,
+) -> ApiResult<impl Responder> {
+    identity.require_admin()?;
+    let config = web::block(move || config.reload(&pool)).await??;
+    log::info!("Config reloaded");
+    Ok(web::Json(ReloadResponse {
+        problem_count: config.problem_map.len(),
+        language_count: config.language_map.len(),
+    }))
+}
+
+pub fn routes() -> Scope {
+    web::scope("/config").service(reload)
+}
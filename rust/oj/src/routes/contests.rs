@@ -1,6 +1,7 @@
 //! `/contests` API routes.
 
-use crate::config::ProblemMap;
+use crate::auth::Identity;
+use crate::config::SharedConfig;
 use crate::db::connection::ConnectionPool;
 use crate::db::contests::{self, ContestWithoutId};
 use crate::error::*;
@@ -18,9 +19,13 @@ struct ContestWithOptionalId {
 #[post("")]
 async fn post_contests(
     data: web::Json<ContestWithOptionalId>,
+    identity: Identity,
     pool: web::Data<ConnectionPool>,
-    problem_map: web::Data<ProblemMap>,
+    config: web::Data<SharedConfig>,
 ) -> ApiResult<impl Responder> {
+    identity.require_admin()?;
+    let problem_map = &config.load().problem_map;
+
     let mut problem_id_set = HashSet::new();
     for id in &data.contest.problem_ids {
         if !problem_map.contains_key(id) {
@@ -76,7 +81,9 @@ async fn get_contest(
     ))
 }
 
+mod export;
 mod ranklist;
+mod similarity;
 
 pub fn routes() -> Scope {
     web::scope("/contests")
@@ -84,4 +91,8 @@ pub fn routes() -> Scope {
         .service(get_all_contests)
         .service(get_contest)
         .service(ranklist::ranklist)
+        .service(ranklist::reveal)
+        .service(ranklist::unfreeze)
+        .service(export::export)
+        .service(similarity::similarity)
 }
@@ -0,0 +1,166 @@
+//! Export the ranklist, the final submissions and the results of a contest as an archive.
+
+use super::ranklist;
+use crate::auth::Identity;
+use crate::config::{Config, SharedConfig};
+use crate::db::connection::ConnectionPool;
+use crate::db::enums::{JobResult, JobState};
+use crate::db::jobs::{self, JobInfo};
+use crate::error::*;
+use crate::TIME_FORMAT;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, web, HttpResponse};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// The result of a job in `results.json`.
+#[derive(Serialize)]
+struct JobResultEntry {
+    id: i32,
+    created_time: String,
+    user_id: i32,
+    problem_id: i32,
+    language: String,
+    state: JobState,
+    result: JobResult,
+    score: f64,
+    /// Whether the job is used for ranking, i.e. included in `submissions/`.
+    #[serde(rename = "final")]
+    is_final: bool,
+}
+
+/// Replace characters other than ASCII alphanumerics, `-`, `_` and `.` in a path component.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Append a file to the archive.
+fn append_file<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> ApiResult<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+/// Export a `.tar.gz` archive of the contest, containing:
+///
+/// - `ranklist.csv`: the final ranklist, the same as `GET /contests/{id}/ranklist?format=csv`.
+/// - `results.json`: the results of all jobs in the contest.
+/// - `submissions/{user}/{problem}/{file_name}`: the source code of the submissions used for
+///   ranking, where `{user}` and `{problem}` are ids and names joined by `-`.
+#[get("/{id}/export")]
+async fn export(
+    contest_id: web::Path<i32>,
+    identity: Identity,
+    pool: web::Data<ConnectionPool>,
+    config: web::Data<SharedConfig>,
+) -> ApiResult<HttpResponse> {
+    identity.require_admin()?;
+    let contest_id = contest_id.into_inner();
+    let rank_list = ranklist::get_final_ranklist(contest_id, pool.clone(), &config).await?;
+    let jobs = web::block(move || {
+        if contest_id == 0 {
+            jobs::get_all_job_info(&pool)
+        } else {
+            jobs::get_contest_jobs_info(contest_id, &pool)
+        }
+    })
+    .await??;
+
+    let config = config.load();
+    let archive = web::block(move || -> ApiResult<Vec<u8>> {
+        let user_names = rank_list
+            .users()
+            .map(|user| (user.id, user.name.as_str()))
+            .collect::<HashMap<_, _>>();
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+        append_file(
+            &mut builder,
+            "ranklist.csv",
+            &rank_list.to_csv(&config.problem_map)?,
+        )?;
+
+        let results = jobs
+            .iter()
+            .map(|job| JobResultEntry {
+                id: job.id,
+                created_time: job.created_time.format(TIME_FORMAT).to_string(),
+                user_id: job.user_id,
+                problem_id: job.problem_id,
+                language: job.language.clone(),
+                state: job.state,
+                result: job.result,
+                score: job.score,
+                is_final: rank_list.job_ids.contains(&job.id),
+            })
+            .collect::<Vec<_>>();
+        let results = serde_json::to_vec_pretty(&results).map_err(|error| {
+            ApiError::new(
+                ApiErrorType::Internal,
+                format!("failed to serialize results: {}", error),
+            )
+        })?;
+        append_file(&mut builder, "results.json", &results)?;
+
+        for job in jobs
+            .iter()
+            .filter(|job| rank_list.job_ids.contains(&job.id))
+        {
+            append_file(
+                &mut builder,
+                &submission_path(job, &user_names, &config),
+                job.source_code.as_bytes(),
+            )?;
+        }
+
+        Ok(builder.into_inner()?.finish()?)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "contest-{}.tar.gz",
+                contest_id
+            ))],
+        })
+        .body(archive))
+}
+
+/// Get the path of the source code of a submission in the archive.
+fn submission_path(job: &JobInfo, user_names: &HashMap<i32, &str>, config: &Config) -> String {
+    let user = format!(
+        "{}-{}",
+        job.user_id,
+        sanitize(user_names.get(&job.user_id).unwrap_or(&""))
+    );
+    let problem = match config.problem_map.get(&job.problem_id) {
+        Some(problem) => format!("{}-{}", job.problem_id, sanitize(&problem.name)),
+        None => job.problem_id.to_string(),
+    };
+    let file_name = config.language_map.get(&job.language).map_or_else(
+        || String::from("source"),
+        |language| sanitize(&language.file_name),
+    );
+    format!("submissions/{}/{}/{}", user, problem, file_name)
+}
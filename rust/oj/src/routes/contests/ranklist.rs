@@ -1,15 +1,18 @@
-use crate::config::{Problem, ProblemMap, ProblemType};
+use crate::auth::Identity;
+use crate::config::{Problem, ProblemMap, ProblemType, SharedConfig};
 use crate::db::case_results;
 use crate::db::connection::ConnectionPool;
-use crate::db::contests;
-use crate::db::enums::JobResult;
-use crate::db::jobs::{self, JobInfo};
+use crate::db::contests::{self, ContestInfo};
+use crate::db::enums::ContestMode;
+use crate::db::rank_results::{self, RankResult};
 use crate::db::users::{self, User};
 use crate::error::*;
-use actix_web::{get, web, Responder};
+use actix_web::{get, post, web, HttpResponse};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::collections::{HashMap, HashSet};
+
+mod icpc;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -36,6 +39,33 @@ struct RankListQuery {
     #[serde(default = "default_scoring_rule")]
     scoring_rule: ScoringRule,
     tie_breaker: Option<TieBreaker>,
+    /// Hide frozen submissions from admins too, e.g. to show the ranklist while revealing.
+    ///
+    /// When authentication is disabled, everyone is treated as an admin, so the ranklist is only
+    /// frozen with this.
+    #[serde(default)]
+    frozen: bool,
+    #[serde(default)]
+    format: RankListFormat,
+}
+
+impl Default for RankListQuery {
+    fn default() -> Self {
+        Self {
+            scoring_rule: default_scoring_rule(),
+            tie_breaker: None,
+            frozen: false,
+            format: RankListFormat::Json,
+        }
+    }
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum RankListFormat {
+    #[default]
+    Json,
+    Csv,
 }
 
 /// An item in the ranklist.
@@ -44,6 +74,12 @@ struct RankItem {
     user: User,
     rank: usize,
     scores: Vec<f64>,
+    /// Only in ICPC mode contests.
+    #[serde(flatten)]
+    icpc: Option<icpc::IcpcRank>,
+    /// The number of hidden submissions on each problem, only when the ranklist is frozen.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending: Option<Vec<u32>>,
     #[serde(skip)]
     total_score: f64,
     #[serde(skip)]
@@ -52,75 +88,302 @@ struct RankItem {
     submission_count: u32,
 }
 
+/// A sorted ranklist.
+pub(super) struct RankList {
+    items: Vec<RankItem>,
+    problem_ids: Vec<i32>,
+    /// The ids of the submissions used for ranking.
+    pub job_ids: HashSet<i32>,
+}
+
+impl RankList {
+    pub fn users(&self) -> impl Iterator<Item = &User> {
+        self.items.iter().map(|item| &item.user)
+    }
+
+    /// Write the ranklist in CSV, with a row for each user and a column for each problem.
+    pub fn to_csv(&self, problem_map: &ProblemMap) -> ApiResult<Vec<u8>> {
+        let icpc = self.items.iter().any(|item| item.icpc.is_some());
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        let mut header = vec![
+            String::from("rank"),
+            String::from("user_id"),
+            String::from("user_name"),
+        ];
+        header.extend(self.problem_ids.iter().map(|id| {
+            problem_map
+                .get(id)
+                .map_or_else(|| id.to_string(), |problem| problem.name.clone())
+        }));
+        header.push(String::from("total_score"));
+        if icpc {
+            header.extend([String::from("solved"), String::from("penalty")]);
+        }
+        writer.write_record(&header)?;
+
+        for item in &self.items {
+            let mut record = vec![
+                item.rank.to_string(),
+                item.user.id.to_string(),
+                item.user.name.clone(),
+            ];
+            record.extend(item.scores.iter().map(|score| score.to_string()));
+            record.push(item.total_score.to_string());
+            if let Some(rank) = &item.icpc {
+                record.extend([rank.solved.to_string(), rank.penalty.to_string()]);
+            }
+            writer.write_record(&record)?;
+        }
+
+        writer.into_inner().map_err(|error| {
+            ApiError::new(
+                ApiErrorType::Internal,
+                format!("failed to write CSV: {}", error),
+            )
+        })
+    }
+}
+
+/// Get the ranklist in JSON, or in CSV with `format=csv`.
 #[get("/{id}/ranklist")]
 async fn ranklist(
     contest_id: web::Path<i32>,
     query: web::Query<RankListQuery>,
+    identity: Identity,
     pool: web::Data<ConnectionPool>,
-    problem_map: web::Data<ProblemMap>,
-) -> ApiResult<impl Responder> {
+    config: web::Data<SharedConfig>,
+) -> ApiResult<HttpResponse> {
+    let frozen = query.frozen || !identity.is_admin();
+    let rank_list = get_ranklist(contest_id.into_inner(), &query, frozen, pool, &config).await?;
+    Ok(match query.format {
+        RankListFormat::Json => HttpResponse::Ok().json(rank_list.items),
+        RankListFormat::Csv => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .body(rank_list.to_csv(&config.load().problem_map)?),
+    })
+}
+
+/// The (user, problem) whose frozen submissions are revealed.
+#[derive(Serialize)]
+struct RevealResponse {
+    user_id: i32,
+    problem_id: i32,
+}
+
+/// Reveal the frozen submissions of a single user on a single problem, like a resolver: the lowest
+/// ranked user with hidden submissions in the frozen ranklist, on the first such problem.
+///
+/// Responds "204 No Content" if there is nothing to reveal.
+#[post("/{id}/ranklist/reveal")]
+async fn reveal(
+    contest_id: web::Path<i32>,
+    query: web::Query<RankListQuery>,
+    identity: Identity,
+    pool: web::Data<ConnectionPool>,
+    config: web::Data<SharedConfig>,
+) -> ApiResult<HttpResponse> {
+    identity.require_admin()?;
     let contest_id = contest_id.into_inner();
+    let rank_list = get_ranklist(contest_id, &query, true, pool.clone(), &config).await?;
+    let next = rank_list.items.iter().rev().find_map(|item| {
+        let index = item.pending.as_ref()?.iter().position(|&count| count > 0)?;
+        Some((item.user.id, rank_list.problem_ids[index]))
+    });
+    match next {
+        None => Ok(HttpResponse::NoContent().finish()),
+        Some((user_id, problem_id)) => {
+            web::block(move || {
+                contests::reveal_contest_submissions(contest_id, &[(user_id, problem_id)], &pool)
+            })
+            .await??;
+            Ok(HttpResponse::Ok().json(RevealResponse {
+                user_id,
+                problem_id,
+            }))
+        }
+    }
+}
 
-    let (jobs, users, problem_ids) =
-        get_jobs_users_problem_ids(contest_id, pool.clone(), &problem_map).await?;
-    let problem_list = get_problem_list(&problem_ids, &problem_map)?;
+/// Reveal all frozen submissions up to now. Submissions after this are hidden until revealed again.
+#[post("/{id}/ranklist/unfreeze")]
+async fn unfreeze(
+    contest_id: web::Path<i32>,
+    identity: Identity,
+    pool: web::Data<ConnectionPool>,
+    config: web::Data<SharedConfig>,
+) -> ApiResult<HttpResponse> {
+    identity.require_admin()?;
+    let contest_id = contest_id.into_inner();
+    let rank_list = get_ranklist(
+        contest_id,
+        &RankListQuery::default(),
+        true,
+        pool.clone(),
+        &config,
+    )
+    .await?;
+    let pairs = rank_list
+        .items
+        .iter()
+        .flat_map(|item| {
+            let pending = item.pending.as_deref().unwrap_or_default();
+            rank_list
+                .problem_ids
+                .iter()
+                .zip(pending)
+                .filter(|(_, &count)| count > 0)
+                .map(|(&problem_id, _)| (item.user.id, problem_id))
+        })
+        .collect::<Vec<_>>();
+    web::block(move || contests::reveal_contest_submissions(contest_id, &pairs, &pool)).await??;
+    Ok(HttpResponse::Ok().finish())
+}
 
-    let (mut submission_for_rank, submission_count) = get_submission_for_rank_and_count(
-        &users,
-        &problem_ids,
-        jobs,
-        &problem_map,
-        &query.scoring_rule,
-    );
+/// Get the final ranklist of the given contest with the default query, without hiding frozen
+/// submissions.
+pub(super) async fn get_final_ranklist(
+    contest_id: i32,
+    pool: web::Data<ConnectionPool>,
+    config: &SharedConfig,
+) -> ApiResult<RankList> {
+    get_ranklist(contest_id, &RankListQuery::default(), false, pool, config).await
+}
 
-    for problem in problem_list {
-        update_scores_for_dynamic_ranking(&mut submission_for_rank, problem, pool.clone()).await?;
-    }
+/// Get the sorted ranklist of the given contest.
+///
+/// * `frozen`: whether to hide the submissions since the freeze time of the contest, except the
+///   revealed ones.
+async fn get_ranklist(
+    contest_id: i32,
+    query: &RankListQuery,
+    frozen: bool,
+    pool: web::Data<ConnectionPool>,
+    config: &SharedConfig,
+) -> ApiResult<RankList> {
+    let config = config.load();
+    let problem_map = &config.problem_map;
+
+    let (contest_info, users, problem_ids) =
+        get_contest_users_and_problem_ids(contest_id, pool.clone(), problem_map).await?;
+    let problem_list = get_problem_list(&problem_ids, problem_map)?;
+
+    let freeze = match &contest_info {
+        Some(info) if frozen && info.freeze_time.is_some() => {
+            let pool = pool.clone();
+            web::block(move || contests::get_contest_freeze(contest_id, &pool)).await??
+        }
+        _ => None,
+    };
+    let (results, pending) = match freeze {
+        Some(freeze) => {
+            let pool = pool.clone();
+            let (results, hidden_count) = web::block(move || {
+                rank_results::get_frozen_rank_results(contest_id, &freeze, &pool)
+            })
+            .await??;
+            (results, Some(hidden_count))
+        }
+        None => {
+            let pool = pool.clone();
+            let results =
+                web::block(move || rank_results::get_rank_results(contest_id, &pool)).await??;
+            (results, None)
+        }
+    };
+    let results = filter_results(results, &users, &problem_ids);
+
+    let mut icpc_ranks = match contest_info {
+        Some(info) if info.mode == ContestMode::Icpc => Some(icpc::get_icpc_ranks(
+            &users,
+            &problem_ids,
+            &results,
+            info.from,
+        )),
+        _ => None,
+    };
+
+    let (mut submission_for_rank, submission_count) =
+        get_submission_for_rank_and_count(&results, problem_map, &query.scoring_rule);
+
+    update_scores_for_dynamic_ranking(&mut submission_for_rank, &problem_list, pool).await?;
 
     let mut rank_list =
         get_unsorted_ranklist(&submission_for_rank, &submission_count, users, &problem_ids);
+    if let Some(icpc_ranks) = &mut icpc_ranks {
+        for item in &mut rank_list {
+            item.icpc = icpc_ranks.remove(&item.user.id);
+        }
+    }
+    if let Some(pending) = &pending {
+        for item in &mut rank_list {
+            item.pending = Some(
+                problem_ids
+                    .iter()
+                    .map(|problem_id| *pending.get(&(item.user.id, *problem_id)).unwrap_or(&0))
+                    .collect(),
+            );
+        }
+    }
 
     sort_ranklist(&mut rank_list, &query.tie_breaker);
 
-    Ok(web::Json(rank_list))
+    let job_ids = submission_for_rank
+        .values()
+        .flat_map(|map| map.values().map(|submission| submission.job_id))
+        .collect();
+    Ok(RankList {
+        items: rank_list,
+        problem_ids,
+        job_ids,
+    })
 }
 
-/// Get jobs, users and problem ids of the given contest.
+/// Get the contest info, users and problem ids of the given contest.
 ///
-/// If `contest_id` is zero, all jobs, users, and problem ids will be returned.
-async fn get_jobs_users_problem_ids(
+/// If `contest_id` is zero, no contest info and all users and problem ids will be returned.
+async fn get_contest_users_and_problem_ids(
     contest_id: i32,
     pool: web::Data<ConnectionPool>,
     problem_map: &ProblemMap,
-) -> ApiResult<(Vec<JobInfo>, Vec<User>, Vec<i32>)> {
+) -> ApiResult<(Option<ContestInfo>, Vec<User>, Vec<i32>)> {
     if contest_id == 0 {
-        let (jobs, users) = web::block(move || -> ApiResult<_> {
-            Ok((jobs::get_all_job_info(&pool)?, users::get_users(&pool)?))
-        })
-        .await??;
+        let users = web::block(move || users::get_users(&pool)).await??;
 
         // `users::get_users` is already sorted, so no sort is needed for users.
         // But problem_ids needs sort.
         let mut problem_ids = problem_map.keys().cloned().collect::<Vec<_>>();
         problem_ids.sort_unstable();
 
-        Ok((jobs, users, problem_ids))
+        Ok((None, users, problem_ids))
     } else {
-        let ((mut users, problem_ids), jobs) = web::block(move || -> ApiResult<_> {
-            Ok((
-                contests::get_contest_users_and_problem_ids(contest_id, &pool)?,
-                jobs::get_contest_jobs_info(contest_id, &pool)?,
-            ))
-        })
-        .await??;
+        let (info, mut users, problem_ids) =
+            web::block(move || contests::get_contest_info_users_and_problem_ids(contest_id, &pool))
+                .await??;
 
         // Sort users by id to get correct order on tie.
         users.sort_unstable_by_key(|user| user.id);
 
-        Ok((jobs, users, problem_ids))
+        Ok((Some(info), users, problem_ids))
     }
 }
 
+/// Keep only the results of the given users on the given problems.
+fn filter_results(
+    results: Vec<RankResult>,
+    users: &[User],
+    problem_ids: &[i32],
+) -> Vec<RankResult> {
+    let user_set: HashSet<i32> = users.iter().map(|user| user.id).collect();
+    let problem_set: HashSet<i32> = problem_ids.iter().cloned().collect();
+    results
+        .into_iter()
+        .filter(|result| {
+            user_set.contains(&result.user_id) && problem_set.contains(&result.problem_id)
+        })
+        .collect()
+}
+
 /// Get a list of `Problem`s of the given ids.
 ///
 /// It also checks that every problem id exists in the problem map, so that subsequent codes can
@@ -154,6 +417,14 @@ fn eps_cmp(lhs: f64, rhs: f64) -> Ordering {
     }
 }
 
+/// The submission used for ranking of a user on a problem.
+struct RankedSubmission {
+    job_id: i32,
+    score: f64,
+    /// Whether it's accepted, in which case its score is updated for dynamic ranking problems.
+    accepted: bool,
+}
+
 /// Get the submission used for ranking for each user and each problem, and get the submission
 /// count of each user.
 ///
@@ -162,117 +433,105 @@ fn eps_cmp(lhs: f64, rhs: f64) -> Ordering {
 /// id as the inner key.
 /// The second element is a map of submission count with user id as the key.
 fn get_submission_for_rank_and_count(
-    users: &[User],
-    problem_ids: &[i32],
-    jobs: Vec<JobInfo>,
+    results: &[RankResult],
     problem_map: &ProblemMap,
     scoring_rule: &ScoringRule,
-) -> (HashMap<i32, HashMap<i32, JobInfo>>, HashMap<i32, u32>) {
-    let user_set: HashSet<i32> = users.iter().map(|user| user.id).collect();
-    let problem_set: HashSet<i32> = problem_ids.iter().cloned().collect();
-
+) -> (
+    HashMap<i32, HashMap<i32, RankedSubmission>>,
+    HashMap<i32, u32>,
+) {
     let mut submission_for_rank = HashMap::new();
     let mut submission_count = HashMap::new();
 
-    // The chosen submission is greater
-    let scoring_rule_cmp = |lhs: &JobInfo, rhs: &JobInfo| match scoring_rule {
-        ScoringRule::Latest => lhs.id.cmp(&rhs.id),
-        ScoringRule::Highest => eps_cmp(lhs.score, rhs.score).then(rhs.id.cmp(&lhs.id)),
-    };
+    for result in results {
+        *submission_count.entry(result.user_id).or_insert(0) += result.submission_count as u32;
 
-    let is_accepted_dynamic_ranking = |job: &JobInfo| {
-        if job.result != JobResult::Accepted {
-            return false;
-        }
-        let problem = problem_map.get(&job.problem_id).unwrap();
-        match problem.tp {
-            ProblemType::DynamicRanking { .. } => true,
-            _ => false,
-        }
-    };
-
-    for job in jobs {
-        let user_id = job.user_id;
-        let problem_id = job.problem_id;
-        if !user_set.contains(&user_id) || !problem_set.contains(&problem_id) {
-            continue;
-        }
-        *submission_count.entry(user_id).or_insert(0) += 1;
-        match submission_for_rank
-            .entry(user_id)
-            .or_insert(HashMap::new())
-            .entry(problem_id)
-        {
-            Entry::Vacant(entry) => {
-                entry.insert(job);
-            }
-            Entry::Occupied(mut entry) => {
-                let entry_accepted_dynamic_ranking = is_accepted_dynamic_ranking(entry.get());
-                let new_accepted_dynamic_ranking = is_accepted_dynamic_ranking(&job);
-                if entry_accepted_dynamic_ranking && new_accepted_dynamic_ranking {
-                    if job.id > entry.get().id {
-                        entry.insert(job);
-                    }
-                } else if new_accepted_dynamic_ranking {
-                    entry.insert(job);
-                } else if !entry_accepted_dynamic_ranking {
-                    if scoring_rule_cmp(&job, entry.get()).is_gt() {
-                        entry.insert(job);
-                    }
+        // The latest accepted submission is always used for dynamic ranking problems.
+        let problem = problem_map.get(&result.problem_id).unwrap();
+        let submission = match (&problem.tp, result.accepted_job_id, result.accepted_score) {
+            (ProblemType::DynamicRanking { .. }, Some(job_id), Some(score)) => RankedSubmission {
+                job_id,
+                score,
+                accepted: true,
+            },
+            _ => {
+                let (job_id, score) = match scoring_rule {
+                    ScoringRule::Latest => (result.latest_job_id, result.latest_score),
+                    ScoringRule::Highest => (result.highest_job_id, result.highest_score),
+                };
+                RankedSubmission {
+                    job_id,
+                    score,
+                    accepted: false,
                 }
             }
-        }
+        };
+        submission_for_rank
+            .entry(result.user_id)
+            .or_insert(HashMap::new())
+            .insert(result.problem_id, submission);
     }
 
     (submission_for_rank, submission_count)
 }
 
-/// Update scores of the given problem if it's of dynamic ranking type.
+/// Update scores of the problems of dynamic ranking type.
+///
+/// Jobs judged with a different number of cases, e.g. before the test data of the problem is
+/// changed, keep the score of their cases without the dynamic part, until they are rejudged.
 async fn update_scores_for_dynamic_ranking(
-    submission_for_rank: &mut HashMap<i32, HashMap<i32, JobInfo>>,
-    problem: &Problem,
+    submission_for_rank: &mut HashMap<i32, HashMap<i32, RankedSubmission>>,
+    problem_list: &[&Problem],
     pool: web::Data<ConnectionPool>,
 ) -> ApiResult<()> {
-    if let ProblemType::DynamicRanking { ratio } = problem.tp {
-        let mut min_time = vec![i64::MAX; problem.cases.len()];
-        let mut job_time_map = HashMap::<i32, Vec<i64>>::new();
+    let problem_list = problem_list
+        .iter()
+        .filter_map(|problem| match problem.tp {
+            ProblemType::DynamicRanking { ratio } => Some((*problem, ratio)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let job_ids = submission_for_rank
+        .values()
+        .flat_map(|map| {
+            problem_list
+                .iter()
+                .filter_map(|(problem, _)| map.get(&problem.id))
+                .filter(|submission| submission.accepted)
+                .map(|submission| submission.job_id)
+        })
+        .collect::<Vec<_>>();
+    if job_ids.is_empty() {
+        return Ok(());
+    }
+    let job_time_map =
+        web::block(move || case_results::get_cases_time_of_jobs(&job_ids, &pool)).await??;
+
+    for (problem, ratio) in problem_list {
+        let case_count = problem.cases.len();
+        let mut min_time = vec![i64::MAX; case_count];
+        let get_job_time = |submission: &RankedSubmission| {
+            job_time_map
+                .get(&submission.job_id)
+                .filter(|job_time| submission.accepted && job_time.len() == case_count)
+        };
 
         // get min time for each test case
         for map in submission_for_rank.values() {
-            if let Some(job) = map.get(&problem.id) {
-                if job.result == JobResult::Accepted {
-                    let job_id = job.id;
-                    let mut conn = pool.get()?;
-                    let job_time =
-                        web::block(move || case_results::get_cases_time(job_id, &mut conn))
-                            .await??;
-                    if job_time.len() != min_time.len() {
-                        return Err(ApiError::new(
-                            ApiErrorType::Internal,
-                            format!(
-                                "Problem {} has {} cases, but {} cases found for job {}.",
-                                problem.id,
-                                min_time.len(),
-                                job_time.len(),
-                                job.id
-                            ),
-                        ));
-                    }
-                    for i in 0..min_time.len() {
-                        min_time[i] = min_time[i].min(job_time[i]);
-                    }
-                    job_time_map.insert(job.id, job_time);
+            if let Some(job_time) = map.get(&problem.id).and_then(get_job_time) {
+                for i in 0..min_time.len() {
+                    min_time[i] = min_time[i].min(job_time[i]);
                 }
             }
         }
 
         // update scores
         for map in submission_for_rank.values_mut() {
-            if let Some(job) = map.get_mut(&problem.id) {
-                if job.result == JobResult::Accepted {
-                    let job_time = job_time_map.get(&job.id).unwrap();
+            if let Some(submission) = map.get_mut(&problem.id) {
+                if let Some(job_time) = get_job_time(submission) {
                     for i in 0..min_time.len() {
-                        job.score += min_time[i] as f64 / job_time[i] as f64
+                        submission.score += min_time[i] as f64 / job_time[i] as f64
                             * problem.cases[i].score
                             * ratio;
                     }
@@ -286,7 +545,7 @@ async fn update_scores_for_dynamic_ranking(
 
 /// Get an unsorted ranklist with each item having `rank: 1`.
 fn get_unsorted_ranklist(
-    submission_for_rank: &HashMap<i32, HashMap<i32, JobInfo>>,
+    submission_for_rank: &HashMap<i32, HashMap<i32, RankedSubmission>>,
     submission_count: &HashMap<i32, u32>,
     users: Vec<User>,
     problem_ids: &[i32],
@@ -306,10 +565,10 @@ fn get_unsorted_ranklist(
                     for problem_id in problem_ids {
                         match used_submission.get(problem_id) {
                             None => scores.push(0.0),
-                            Some(job) => {
-                                scores.push(job.score);
-                                total_score += job.score;
-                                last_job_id = last_job_id.max(job.id);
+                            Some(submission) => {
+                                scores.push(submission.score);
+                                total_score += submission.score;
+                                last_job_id = last_job_id.max(submission.job_id);
                             }
                         }
                     }
@@ -320,6 +579,8 @@ fn get_unsorted_ranklist(
                 user,
                 rank: 1,
                 scores,
+                icpc: None,
+                pending: None,
                 total_score,
                 last_job_id,
             }
@@ -328,9 +589,16 @@ fn get_unsorted_ranklist(
 }
 
 /// Sort the ranklist and calculate the `rank` field of each item.
-fn sort_ranklist(rank_list: &mut Vec<RankItem>, tie_breaker: &Option<TieBreaker>) {
+fn sort_ranklist(rank_list: &mut [RankItem], tie_breaker: &Option<TieBreaker>) {
     let rank_cmp = |lhs: &RankItem, rhs: &RankItem| {
-        eps_cmp(rhs.total_score, lhs.total_score).then(match tie_breaker {
+        match (&lhs.icpc, &rhs.icpc) {
+            (Some(lhs), Some(rhs)) => rhs
+                .solved
+                .cmp(&lhs.solved)
+                .then(lhs.penalty.cmp(&rhs.penalty)),
+            _ => eps_cmp(rhs.total_score, lhs.total_score),
+        }
+        .then(match tie_breaker {
             None => Ordering::Equal,
             Some(TieBreaker::SubmissionTime) => lhs.last_job_id.cmp(&rhs.last_job_id),
             Some(TieBreaker::SubmissionCount) => lhs.submission_count.cmp(&rhs.submission_count),
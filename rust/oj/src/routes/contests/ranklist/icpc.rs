@@ -0,0 +1,248 @@
+//! Ranking in ICPC mode contests: by the number of solved problems, then the penalty time.
+
+use crate::db::rank_results::RankResult;
+use crate::db::users::User;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// The penalty time of each rejected attempt before a problem is solved, in minutes.
+const PENALTY_PER_REJECTION: i64 = 20;
+
+/// The status of a user on a problem.
+#[derive(Serialize, Default)]
+pub(super) struct ProblemStatus {
+    solved: bool,
+    /// The number of rejected attempts before the first accepted submission, or all rejected
+    /// attempts if not solved.
+    rejected: u32,
+    /// Minutes from the start of the contest to the first accepted submission.
+    time: Option<i64>,
+    /// Whether this is the first user to solve the problem.
+    first_to_solve: bool,
+    /// The id of the first accepted submission.
+    #[serde(skip)]
+    accepted_job_id: Option<i32>,
+}
+
+/// The ICPC mode fields of an item in the ranklist.
+#[derive(Serialize)]
+pub(super) struct IcpcRank {
+    pub solved: u32,
+    /// The sum of the solving time and the penalty of rejected attempts of solved problems, in
+    /// minutes.
+    pub penalty: i64,
+    problems: Vec<ProblemStatus>,
+}
+
+/// Get the ICPC mode ranking of each user, with user id as the key.
+///
+/// * `from`: the start time of the contest.
+pub(super) fn get_icpc_ranks(
+    users: &[User],
+    problem_ids: &[i32],
+    results: &[RankResult],
+    from: NaiveDateTime,
+) -> HashMap<i32, IcpcRank> {
+    let problem_index = problem_ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (*id, index))
+        .collect::<HashMap<_, _>>();
+    let mut statuses = users
+        .iter()
+        .map(|user| {
+            let problems = problem_ids
+                .iter()
+                .map(|_| ProblemStatus::default())
+                .collect();
+            (user.id, problems)
+        })
+        .collect::<HashMap<i32, Vec<ProblemStatus>>>();
+
+    for result in results {
+        let (Some(problems), Some(&index)) = (
+            statuses.get_mut(&result.user_id),
+            problem_index.get(&result.problem_id),
+        ) else {
+            continue;
+        };
+        problems[index] = ProblemStatus {
+            solved: result.first_accepted_job_id.is_some(),
+            rejected: result.rejected_count as u32,
+            time: result
+                .first_accepted_time
+                .map(|time| (time - from).num_minutes().max(0)),
+            first_to_solve: false,
+            accepted_job_id: result.first_accepted_job_id,
+        };
+    }
+
+    // The first user to solve a problem has the smallest accepted job id.
+    for index in 0..problem_ids.len() {
+        if let Some(status) = statuses
+            .values_mut()
+            .map(|problems| &mut problems[index])
+            .filter(|status| status.solved)
+            .min_by_key(|status| status.accepted_job_id)
+        {
+            status.first_to_solve = true;
+        }
+    }
+
+    statuses
+        .into_iter()
+        .map(|(user_id, problems)| {
+            let solved = problems.iter().filter(|status| status.solved).count() as u32;
+            let penalty = problems
+                .iter()
+                .filter_map(|status| {
+                    status
+                        .time
+                        .map(|time| time + PENALTY_PER_REJECTION * status.rejected as i64)
+                })
+                .sum();
+            (
+                user_id,
+                IcpcRank {
+                    solved,
+                    penalty,
+                    problems,
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{sort_ranklist, RankItem, TieBreaker};
+    use super::*;
+    use chrono::Duration;
+
+    fn from() -> NaiveDateTime {
+        NaiveDateTime::parse_from_str("2022-08-27 02:05:29", "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn user(id: i32) -> User {
+        User {
+            id,
+            name: format!("user{}", id),
+        }
+    }
+
+    /// The result of a user on a problem, accepted by `accepted` = (job id, minutes) if any.
+    fn result(
+        user_id: i32,
+        problem_id: i32,
+        accepted: Option<(i32, i64)>,
+        rejected: i32,
+    ) -> RankResult {
+        RankResult {
+            contest_id: 1,
+            user_id,
+            problem_id,
+            submission_count: rejected + accepted.is_some() as i32,
+            latest_job_id: 0,
+            latest_score: 0.0,
+            highest_job_id: 0,
+            highest_score: 0.0,
+            accepted_job_id: accepted.map(|(job_id, _)| job_id),
+            accepted_score: accepted.map(|_| 100.0),
+            first_accepted_job_id: accepted.map(|(job_id, _)| job_id),
+            first_accepted_time: accepted.map(|(_, minutes)| from() + Duration::minutes(minutes)),
+            rejected_count: rejected,
+        }
+    }
+
+    #[test]
+    fn penalty_counts_rejections_of_solved_problems_only() {
+        let ranks = get_icpc_ranks(
+            &[user(0), user(1)],
+            &[0, 1, 2],
+            &[
+                result(0, 0, Some((3, 30)), 2),
+                result(0, 1, None, 5),
+                result(0, 2, Some((5, 61)), 0),
+                result(1, 1, Some((4, 45)), 1),
+            ],
+            from(),
+        );
+        assert_eq!(ranks[&0].solved, 2);
+        assert_eq!(ranks[&0].penalty, 30 + 2 * PENALTY_PER_REJECTION + 61);
+        assert_eq!(ranks[&0].problems[1].rejected, 5);
+        assert_eq!(ranks[&0].problems[1].time, None);
+        assert_eq!(ranks[&1].solved, 1);
+        assert_eq!(ranks[&1].penalty, 45 + PENALTY_PER_REJECTION);
+    }
+
+    #[test]
+    fn first_to_solve_is_the_earliest_accepted_job() {
+        let ranks = get_icpc_ranks(
+            &[user(0), user(1), user(2)],
+            &[0, 1],
+            &[
+                result(0, 0, Some((7, 10)), 0),
+                result(1, 0, Some((6, 10)), 1),
+                result(2, 1, None, 3),
+                // Results of other users or problems are ignored.
+                result(3, 0, Some((1, 1)), 0),
+                result(0, 2, Some((2, 1)), 0),
+            ],
+            from(),
+        );
+        assert!(!ranks[&0].problems[0].first_to_solve);
+        assert!(ranks[&1].problems[0].first_to_solve);
+        assert!(!ranks[&2].problems[1].first_to_solve);
+        assert!(!ranks.contains_key(&3));
+        assert_eq!(ranks[&0].problems.len(), 2);
+    }
+
+    /// The (user id, rank) of each user in the sorted ranklist.
+    fn sort(results: &[RankResult], tie_breaker: Option<TieBreaker>) -> Vec<(i32, usize)> {
+        let users = (0..4).map(user).collect::<Vec<_>>();
+        let mut ranks = get_icpc_ranks(&users, &[0, 1], results, from());
+        let mut rank_list = users
+            .into_iter()
+            .map(|user| RankItem {
+                icpc: ranks.remove(&user.id),
+                // Submitted in the reverse order of user ids.
+                last_job_id: 10 - user.id,
+                submission_count: user.id as u32,
+                user,
+                rank: 1,
+                scores: Vec::new(),
+                pending: None,
+                total_score: 0.0,
+            })
+            .collect::<Vec<_>>();
+        sort_ranklist(&mut rank_list, &tie_breaker);
+        rank_list
+            .iter()
+            .map(|item| (item.user.id, item.rank))
+            .collect()
+    }
+
+    #[test]
+    fn ranked_by_solved_then_penalty_then_tie_breaker() {
+        let results = [
+            // Solves one problem with less penalty than user 1.
+            result(0, 0, Some((1, 50)), 0),
+            result(1, 1, Some((2, 30)), 2),
+            // Solves two problems, with more penalty.
+            result(2, 0, Some((3, 100)), 0),
+            result(2, 1, Some((4, 100)), 0),
+            // The same penalty as user 0.
+            result(3, 1, Some((5, 50)), 0),
+        ];
+        assert_eq!(sort(&results, None), [(2, 1), (0, 2), (3, 2), (1, 4)]);
+        assert_eq!(
+            sort(&results, Some(TieBreaker::SubmissionTime)),
+            [(2, 1), (3, 2), (0, 3), (1, 4)]
+        );
+        assert_eq!(
+            sort(&results, Some(TieBreaker::SubmissionCount)),
+            [(2, 1), (0, 2), (3, 3), (1, 4)]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Find suspiciously similar submissions in a contest.
+
+use crate::auth::Identity;
+use crate::config::SharedConfig;
+use crate::db::connection::ConnectionPool;
+use crate::db::enums::JobState;
+use crate::db::jobs::{self, JobInfo};
+use crate::error::*;
+use crate::similarity::{compare, Fingerprints, Region, Syntax};
+use actix_web::{get, web, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+fn default_threshold() -> f64 {
+    0.5
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+#[derive(Deserialize)]
+struct SimilarityQuery {
+    /// Only compare submissions of this problem. All problems are compared separately if not set.
+    problem_id: Option<i32>,
+    /// The minimum similarity of the pairs returned.
+    #[serde(default = "default_threshold")]
+    threshold: f64,
+    /// The maximum number of pairs returned.
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+#[derive(Serialize)]
+struct SubmissionRef {
+    job_id: i32,
+    user_id: i32,
+}
+
+impl SubmissionRef {
+    fn new(job: &JobInfo) -> Self {
+        Self {
+            job_id: job.id,
+            user_id: job.user_id,
+        }
+    }
+}
+
+/// A pair of similar submissions of different users on the same problem.
+#[derive(Serialize)]
+struct SuspiciousPair {
+    problem_id: i32,
+    similarity: f64,
+    submissions: [SubmissionRef; 2],
+    regions: Vec<Region>,
+}
+
+/// Compare the latest submission of each user on each problem with those of the other users, and
+/// return the most similar pairs.
+#[get("/{id}/similarity")]
+async fn similarity(
+    contest_id: web::Path<i32>,
+    query: web::Query<SimilarityQuery>,
+    identity: Identity,
+    pool: web::Data<ConnectionPool>,
+    config: web::Data<SharedConfig>,
+) -> ApiResult<impl Responder> {
+    identity.require_admin()?;
+    let contest_id = contest_id.into_inner();
+    let config = config.load();
+
+    let pairs = web::block(move || -> ApiResult<_> {
+        let jobs = if contest_id == 0 {
+            jobs::get_all_job_info(&pool)?
+        } else {
+            jobs::get_contest_jobs_info(contest_id, &pool)?
+        };
+
+        // The latest submission of each user on each problem, grouped by problem.
+        let mut latest = BTreeMap::<i32, HashMap<i32, JobInfo>>::new();
+        for job in jobs {
+            if job.state == JobState::Canceled
+                || query.problem_id.is_some_and(|id| id != job.problem_id)
+            {
+                continue;
+            }
+            let submissions = latest.entry(job.problem_id).or_default();
+            if submissions
+                .get(&job.user_id)
+                .is_none_or(|latest| latest.id < job.id)
+            {
+                submissions.insert(job.user_id, job);
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for (problem_id, submissions) in latest {
+            let mut submissions = submissions.into_values().collect::<Vec<_>>();
+            submissions.sort_unstable_by_key(|job| job.id);
+            let fingerprints = submissions
+                .iter()
+                .map(|job| {
+                    let syntax = config
+                        .language_map
+                        .get(&job.language)
+                        .map_or(Syntax::CLike, |language| {
+                            Syntax::from_file_name(&language.file_name)
+                        });
+                    Fingerprints::new(&job.source_code, syntax)
+                })
+                .collect::<Vec<_>>();
+            for i in 0..submissions.len() {
+                for j in i + 1..submissions.len() {
+                    let comparison = compare(&fingerprints[i], &fingerprints[j]);
+                    if comparison.similarity >= query.threshold {
+                        pairs.push(SuspiciousPair {
+                            problem_id,
+                            similarity: comparison.similarity,
+                            submissions: [
+                                SubmissionRef::new(&submissions[i]),
+                                SubmissionRef::new(&submissions[j]),
+                            ],
+                            regions: comparison.regions,
+                        });
+                    }
+                }
+            }
+        }
+        pairs.sort_by(|lhs, rhs| rhs.similarity.total_cmp(&lhs.similarity));
+        pairs.truncate(query.limit);
+        Ok(pairs)
+    })
+    .await??;
+
+    Ok(web::Json(pairs))
+}
@@ -1,27 +1,50 @@
 //! `/jobs` API routes.
 
-use crate::config::{LanguageMap, ProblemMap};
+use crate::auth::Identity;
+use crate::config::SharedConfig;
 use crate::db::connection::ConnectionPool;
-use crate::db::jobs::{self, JobFilter, Submission};
+use crate::db::jobs::{self, JobFilter, JobListOptions, Submission};
 use crate::error::*;
+use crate::judger::events::{JobEvent, JobEventReceiver, JobEvents};
 use crate::judger::JobAdder;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::web::Bytes;
 use actix_web::{delete, get, post, put, web, HttpResponse, Responder, Scope};
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time;
+
+/// The response header of the total number of jobs in a paginated list.
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// A comment is sent in the event stream after this duration without events, so that idle
+/// connections are not closed by proxies.
+const EVENT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
 
 #[post("")]
 async fn add_job(
-    submission: web::Json<Submission>,
-    problem_map: web::Data<ProblemMap>,
-    language_map: web::Data<LanguageMap>,
+    mut submission: web::Json<Submission>,
+    identity: Identity,
+    config: web::Data<SharedConfig>,
     pool: web::Data<ConnectionPool>,
     adder: web::Data<JobAdder>,
 ) -> ApiResult<impl Responder> {
-    if !language_map.contains_key(&submission.language) {
+    // Admins can submit on behalf of other users.
+    if let Some(user_id) = identity.require_login()? {
+        if !identity.is_admin() {
+            submission.user_id = user_id;
+        }
+    }
+    let config = config.load();
+    if !config.language_map.contains_key(&submission.language) {
         return Err(ApiError::not_found(&format!(
             "Language {}",
             submission.language
         )));
     }
-    let problem = match problem_map.get(&submission.problem_id) {
+    let problem = match config.problem_map.get(&submission.problem_id) {
         None => {
             return Err(ApiError::not_found(&format!(
                 "Problem {}",
@@ -36,29 +59,131 @@ async fn add_job(
     ))
 }
 
+/// List jobs under the filter, with the total number of these jobs in the `X-Total-Count` header.
+///
+/// Users other than admins can only list their own jobs.
 #[get("")]
 async fn get_jobs(
-    filter: web::Query<JobFilter>,
+    mut filter: web::Query<JobFilter>,
+    options: web::Query<JobListOptions>,
+    identity: Identity,
     pool: web::Data<ConnectionPool>,
 ) -> ApiResult<impl Responder> {
-    Ok(web::Json(
-        web::block(move || jobs::get_jobs(&filter, &pool)).await??,
-    ))
+    if let Some(user_id) = identity.require_login()? {
+        if !identity.is_admin() {
+            filter.user_id = Some(user_id);
+        }
+    }
+    let hide_frozen = !identity.is_admin();
+    let (jobs, total_count) =
+        web::block(move || jobs::get_jobs(&filter, &options, hide_frozen, &pool)).await??;
+    Ok(HttpResponse::Ok()
+        .insert_header((TOTAL_COUNT_HEADER, total_count.to_string()))
+        .json(jobs))
 }
 
 #[get("/{id}")]
-async fn get_job(id: web::Path<i32>, pool: web::Data<ConnectionPool>) -> ApiResult<impl Responder> {
-    Ok(web::Json(
-        web::block(move || jobs::get_job(id.into_inner(), &pool)).await??,
-    ))
+async fn get_job(
+    id: web::Path<i32>,
+    identity: Identity,
+    pool: web::Data<ConnectionPool>,
+) -> ApiResult<impl Responder> {
+    identity.require_login()?;
+    let hide_frozen = !identity.is_admin();
+    let job = web::block(move || jobs::get_job(id.into_inner(), hide_frozen, &pool)).await??;
+    identity.require_user(job.user_id())?;
+    Ok(web::Json(job))
+}
+
+/// Stream the progress of a job as server-sent events.
+///
+/// The first event is the current job, then each `JobEvent` is sent as it happens. The stream ends
+/// after the job is finished or canceled. Only the submitter and admins can watch a job, and the
+/// stream ends after the first event if the result is hidden by the freeze of the contest.
+#[get("/{id}/events")]
+async fn get_job_events(
+    id: web::Path<i32>,
+    identity: Identity,
+    events: web::Data<JobEvents>,
+    pool: web::Data<ConnectionPool>,
+) -> ApiResult<impl Responder> {
+    identity.require_login()?;
+    let id = id.into_inner();
+    let hide_frozen = !identity.is_admin();
+    // Subscribe before getting the job so that no event is missed.
+    let receiver = events.subscribe(id);
+    let job = {
+        let pool = pool.clone();
+        web::block(move || jobs::get_job(id, hide_frozen, &pool)).await??
+    };
+    identity.require_user(job.user_id())?;
+    let first = sse_event(&JobSnapshot { job: &job })?;
+    let receiver = (!job.is_done() && !job.is_hidden()).then_some(receiver);
+    let rest = stream::unfold(receiver, move |receiver| {
+        let pool = pool.clone();
+        async move {
+            let mut receiver = receiver?;
+            match time::timeout(EVENT_KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+                Err(_) => Some((Ok(Bytes::from_static(b":\n\n")), Some(receiver))),
+                Ok(Ok(event)) => {
+                    let done = event.is_final();
+                    Some((sse_event(&event), (!done).then_some(receiver)))
+                }
+                // Some events are missed, so send the whole job again.
+                Ok(Err(RecvError::Lagged(_))) => {
+                    Some(resend_job(id, hide_frozen, &pool, receiver).await)
+                }
+                Ok(Err(RecvError::Closed)) => None,
+            }
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(stream::once(async { Ok(first) }).chain(rest)))
+}
+
+/// The first event of the event stream of a job.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "job")]
+struct JobSnapshot<'a> {
+    job: &'a jobs::Job,
+}
+
+/// Get the job from the database to send it as an event, and stop receiving events if it's done
+/// or hidden.
+async fn resend_job(
+    id: i32,
+    hide_frozen: bool,
+    pool: &web::Data<ConnectionPool>,
+    receiver: JobEventReceiver,
+) -> (ApiResult<Bytes>, Option<JobEventReceiver>) {
+    let pool = pool.clone();
+    match web::block(move || jobs::get_job(id, hide_frozen, &pool)).await {
+        Ok(Ok(job)) => {
+            let receiver = (!job.is_done() && !job.is_hidden()).then_some(receiver);
+            (sse_event(&JobSnapshot { job: &job }), receiver)
+        }
+        Ok(Err(error)) => (Err(error), None),
+        Err(error) => (Err(error.into()), None),
+    }
+}
+
+/// Format a single server-sent event with the JSON data.
+fn sse_event(data: &impl Serialize) -> ApiResult<Bytes> {
+    let data = serde_json::to_string(data)
+        .map_err(|error| ApiError::new(ApiErrorType::Internal, error.to_string()))?;
+    Ok(Bytes::from(format!("data: {}\n\n", data)))
 }
 
 #[put("/{id}")]
 async fn rejudge(
     id: web::Path<i32>,
+    identity: Identity,
     adder: web::Data<JobAdder>,
     pool: web::Data<ConnectionPool>,
 ) -> ApiResult<impl Responder> {
+    identity.require_admin()?;
     Ok(web::Json(
         web::block(move || jobs::rejudge(id.into_inner(), &adder, &pool)).await??,
     ))
@@ -67,9 +192,18 @@ async fn rejudge(
 #[delete("/{id}")]
 async fn cancel_job(
     id: web::Path<i32>,
+    identity: Identity,
+    events: web::Data<JobEvents>,
     pool: web::Data<ConnectionPool>,
 ) -> ApiResult<impl Responder> {
-    web::block(move || jobs::cancel_job(id.into_inner(), &pool)).await??;
+    let id = id.into_inner();
+    if identity.require_login()?.is_some() && !identity.is_admin() {
+        let pool = pool.clone();
+        let user_id = web::block(move || jobs::get_job_user_id(id, &pool)).await??;
+        identity.require_user(user_id)?;
+    }
+    web::block(move || jobs::cancel_job(id, &pool)).await??;
+    events.send(id, JobEvent::Cancel);
     Ok(HttpResponse::Ok())
 }
 
@@ -78,6 +212,7 @@ pub fn routes() -> Scope {
         .service(add_job)
         .service(get_jobs)
         .service(get_job)
+        .service(get_job_events)
         .service(rejudge)
         .service(cancel_job)
 }
@@ -0,0 +1,15 @@
+//! `/judger` API routes.
+
+use crate::auth::Identity;
+use crate::judger::JobAdder;
+use actix_web::{get, web, Responder, Scope};
+
+/// Get the status of the judger. The remote judge nodes and their jobs are only shown to admins.
+#[get("/status")]
+async fn get_status(adder: web::Data<JobAdder>, identity: Identity) -> impl Responder {
+    web::Json(adder.status(identity.is_admin()))
+}
+
+pub fn routes() -> Scope {
+    web::scope("/judger").service(get_status)
+}
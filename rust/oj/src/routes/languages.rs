@@ -1,11 +1,16 @@
 //! `/languages` API routes.
 
-use crate::config::LanguageMap;
+use crate::config::SharedConfig;
 use actix_web::{get, web, Responder, Scope};
 
 #[get("")]
-async fn get_languages(language_map: web::Data<LanguageMap>) -> impl Responder {
-    let mut languages = language_map.keys().cloned().collect::<Vec<_>>();
+async fn get_languages(config: web::Data<SharedConfig>) -> impl Responder {
+    let mut languages = config
+        .load()
+        .language_map
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
     languages.sort_unstable();
     web::Json(languages)
 }
@@ -0,0 +1,152 @@
+//! `/nodes` API routes, used by remote judge nodes (`oj-judge`).
+//!
+//! All routes require the `server.node_token` in the config as the `Authorization: Bearer` token,
+//! and are disabled if it's not set.
+
+use crate::auth::bearer_token;
+use crate::config::ServerConfig;
+use crate::db::case_results::CaseUpdate;
+use crate::db::enums::JobResult;
+use crate::db::jobs::JobInfoForJudger;
+use crate::db::subtask_results::SubtaskResult;
+use crate::error::*;
+use crate::judger::nodes::RemoteNodes;
+use actix_web::{post, put, web, HttpRequest, HttpResponse, Responder, Scope};
+use serde::{Deserialize, Serialize};
+
+/// Errors if the request is not sent by a judge node.
+fn check_token(req: &HttpRequest, server: &ServerConfig) -> ApiResult<()> {
+    let node_token = server.node_token.as_ref().ok_or_else(|| {
+        ApiError::new(
+            ApiErrorType::Forbidden,
+            String::from("Remote judge nodes are disabled."),
+        )
+    })?;
+    if bearer_token(req).as_ref() == Some(node_token) {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            ApiErrorType::Unauthorized,
+            String::from("Invalid node token."),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterResponse {
+    pub id: i32,
+}
+
+#[post("")]
+async fn register(
+    req: HttpRequest,
+    body: web::Json<RegisterRequest>,
+    server: web::Data<ServerConfig>,
+    nodes: web::Data<RemoteNodes>,
+) -> ApiResult<impl Responder> {
+    check_token(&req, &server)?;
+    let id = nodes.register(body.into_inner().name);
+    Ok(web::Json(RegisterResponse { id }))
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HeartbeatRequest {
+    /// The ids of the jobs running on the node.
+    pub jobs: Vec<i32>,
+}
+
+#[post("/{id}/heartbeat")]
+async fn heartbeat(
+    req: HttpRequest,
+    id: web::Path<i32>,
+    body: web::Json<HeartbeatRequest>,
+    server: web::Data<ServerConfig>,
+    nodes: web::Data<RemoteNodes>,
+) -> ApiResult<impl Responder> {
+    check_token(&req, &server)?;
+    web::block(move || nodes.heartbeat(id.into_inner(), &body.jobs)).await??;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// A job assigned to a node.
+#[derive(Serialize, Deserialize)]
+pub struct JobAssignment {
+    pub job_id: i32,
+    #[serde(flatten)]
+    pub job: JobInfoForJudger,
+}
+
+/// Wait for a job. Responds "204 No Content" if there is no job for a while.
+#[post("/{id}/jobs")]
+async fn pull_job(
+    req: HttpRequest,
+    id: web::Path<i32>,
+    server: web::Data<ServerConfig>,
+    nodes: web::Data<RemoteNodes>,
+) -> ApiResult<HttpResponse> {
+    check_token(&req, &server)?;
+    Ok(match nodes.pull(id.into_inner()).await? {
+        Some((job_id, job)) => HttpResponse::Ok().json(JobAssignment { job_id, job }),
+        None => HttpResponse::NoContent().finish(),
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CaseUpdateRequest {
+    #[serde(flatten)]
+    pub update: CaseUpdate,
+    /// The total score of the job after the update.
+    pub score: f64,
+}
+
+#[put("/{id}/jobs/{job_id}/cases/{case_id}")]
+async fn update_case(
+    req: HttpRequest,
+    path: web::Path<(i32, i32, i32)>,
+    body: web::Json<CaseUpdateRequest>,
+    server: web::Data<ServerConfig>,
+    nodes: web::Data<RemoteNodes>,
+) -> ApiResult<impl Responder> {
+    check_token(&req, &server)?;
+    let (id, job_id, case_id) = path.into_inner();
+    web::block(move || nodes.update_case(id, job_id, case_id, &body.update, body.score)).await??;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FinishRequest {
+    pub result: JobResult,
+    pub score: f64,
+    #[serde(default)]
+    pub subtasks: Vec<SubtaskResult>,
+}
+
+#[put("/{id}/jobs/{job_id}")]
+async fn finish_job(
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+    body: web::Json<FinishRequest>,
+    server: web::Data<ServerConfig>,
+    nodes: web::Data<RemoteNodes>,
+) -> ApiResult<impl Responder> {
+    check_token(&req, &server)?;
+    let (id, job_id) = path.into_inner();
+    let body = body.into_inner();
+    web::block(move || nodes.finish_job(id, job_id, body.result, body.score, body.subtasks))
+        .await??;
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub fn routes() -> Scope {
+    web::scope("/nodes")
+        .service(register)
+        .service(heartbeat)
+        .service(pull_job)
+        .service(update_case)
+        .service(finish_job)
+}
@@ -1,9 +1,15 @@
 //! `/problems` API routes.
 
-use crate::config::{Problem, ProblemMap, ProblemType};
+use crate::auth::Identity;
+use crate::config::{
+    Problem, ProblemSettings, ProblemType, Sample, SharedConfig, Subtask, SubtaskPolicy, TestCase,
+    MAX_ARCHIVE_SIZE,
+};
+use crate::db::connection::ConnectionPool;
 use crate::error::*;
-use actix_web::{get, web, Responder, Scope};
+use actix_web::{get, post, put, web, Responder, Scope};
 use serde::Serialize;
+use std::time::Duration;
 
 /// Problem type without type-specific data.
 #[derive(Serialize)]
@@ -12,6 +18,10 @@ enum ProblemTypeResponse {
     Strict,
     Spj,
     DynamicRanking,
+    Interactive,
+    Testlib,
+    Float,
+    Tokens,
 }
 
 #[derive(Serialize)]
@@ -19,6 +29,7 @@ struct ProblemResponse {
     id: i32,
     name: String,
     problem_type: ProblemTypeResponse,
+    tags: Vec<String>,
 }
 
 impl ProblemResponse {
@@ -31,14 +42,106 @@ impl ProblemResponse {
                 ProblemType::Strict => ProblemTypeResponse::Strict,
                 ProblemType::Spj { .. } => ProblemTypeResponse::Spj,
                 ProblemType::DynamicRanking { .. } => ProblemTypeResponse::DynamicRanking,
+                ProblemType::Interactive { .. } => ProblemTypeResponse::Interactive,
+                ProblemType::Testlib { .. } => ProblemTypeResponse::Testlib,
+                ProblemType::Float { .. } => ProblemTypeResponse::Float,
+                ProblemType::Tokens => ProblemTypeResponse::Tokens,
             },
+            tags: problem.tags.clone(),
+        }
+    }
+}
+
+/// The limits and score of a test case. Zero limits mean no limit as in the config.
+#[derive(Serialize)]
+struct CaseResponse {
+    score: f64,
+    /// The time limit in microseconds.
+    time_limit: u64,
+    /// The memory limit in bytes.
+    memory_limit: usize,
+}
+
+impl CaseResponse {
+    fn new(case: &TestCase) -> Self {
+        Self {
+            score: case.score,
+            time_limit: match case.time_limit {
+                Duration::MAX => 0,
+                time_limit => time_limit.as_micros() as u64,
+            },
+            memory_limit: match case.memory_limit {
+                usize::MAX => 0,
+                memory_limit => memory_limit,
+            },
+        }
+    }
+}
+
+/// A subtask of a problem, with its scoring policy.
+#[derive(Serialize)]
+struct SubtaskResponse {
+    /// Case IDs starting from 1.
+    cases: Vec<i32>,
+    score: f64,
+    policy: SubtaskPolicy,
+    /// Subtask IDs starting from 1.
+    dependencies: Vec<usize>,
+}
+
+impl SubtaskResponse {
+    fn new(subtask: &Subtask) -> Self {
+        Self {
+            cases: subtask.cases.iter().map(|case| case + 1).collect(),
+            score: subtask.score,
+            policy: subtask.policy,
+            dependencies: subtask
+                .dependencies
+                .iter()
+                .map(|subtask| subtask + 1)
+                .collect(),
+        }
+    }
+}
+
+/// A single problem with its statement and test case information.
+#[derive(Serialize)]
+struct ProblemDetailResponse {
+    #[serde(flatten)]
+    problem: ProblemResponse,
+    /// The statement in HTML.
+    statement: Option<String>,
+    samples: Vec<Sample>,
+    cases: Vec<CaseResponse>,
+    total_score: f64,
+    /// Case IDs of each subtask, starting from 1.
+    packing: Vec<Vec<i32>>,
+    subtasks: Vec<SubtaskResponse>,
+}
+
+impl ProblemDetailResponse {
+    fn new(problem: &Problem) -> Self {
+        Self {
+            problem: ProblemResponse::new(problem),
+            statement: problem.statement.clone(),
+            samples: problem.samples.clone(),
+            cases: problem.cases.iter().map(CaseResponse::new).collect(),
+            total_score: problem.subtasks.iter().map(|subtask| subtask.score).sum(),
+            packing: problem
+                .subtasks
+                .iter()
+                .map(|subtask| subtask.cases.iter().map(|case| case + 1).collect())
+                .collect(),
+            subtasks: problem.subtasks.iter().map(SubtaskResponse::new).collect(),
         }
     }
 }
 
 #[get("")]
-async fn get_all_problems(problem_map: web::Data<ProblemMap>) -> impl Responder {
-    let mut problems = problem_map
+async fn get_all_problems(config: web::Data<SharedConfig>) -> impl Responder {
+    let mut problems = config
+        .load()
+        .problem_map
         .values()
         .map(ProblemResponse::new)
         .collect::<Vec<_>>();
@@ -49,16 +152,55 @@ async fn get_all_problems(problem_map: web::Data<ProblemMap>) -> impl Responder
 #[get("/{id}")]
 async fn get_problem(
     id: web::Path<i32>,
-    problem_map: web::Data<ProblemMap>,
+    config: web::Data<SharedConfig>,
 ) -> ApiResult<impl Responder> {
-    let problem = problem_map
+    let config = config.load();
+    let problem = config
+        .problem_map
         .get(&id)
         .ok_or_else(|| ApiError::not_found(&format!("Problem {}", id)))?;
-    Ok(web::Json(ProblemResponse::new(problem)))
+    Ok(web::Json(ProblemDetailResponse::new(problem)))
+}
+
+/// Upload the test data of a managed problem as a zip archive in the request body. The `*.in` and
+/// `*.ans` files are paired by name into cases, which are used after the next update of the
+/// problem.
+#[post("/{id}/data")]
+async fn upload_data(
+    id: web::Path<i32>,
+    identity: Identity,
+    archive: web::Bytes,
+    config: web::Data<SharedConfig>,
+) -> ApiResult<impl Responder> {
+    identity.require_admin()?;
+    let id = id.into_inner();
+    let cases = web::block(move || config.upload_data(id, &archive)).await??;
+    log::info!("Uploaded {} cases of problem {}", cases.len(), id);
+    Ok(web::Json(cases))
+}
+
+/// Create or update a managed problem with the latest uploaded test data.
+#[put("/{id}")]
+async fn update_problem(
+    id: web::Path<i32>,
+    identity: Identity,
+    settings: web::Json<ProblemSettings>,
+    config: web::Data<SharedConfig>,
+    pool: web::Data<ConnectionPool>,
+) -> ApiResult<impl Responder> {
+    identity.require_admin()?;
+    let id = id.into_inner();
+    let problem =
+        web::block(move || config.update_problem(id, settings.into_inner(), &pool)).await??;
+    log::info!("Problem {} updated", id);
+    Ok(web::Json(ProblemDetailResponse::new(&problem)))
 }
 
 pub fn routes() -> Scope {
     web::scope("/problems")
+        .app_data(web::PayloadConfig::new(MAX_ARCHIVE_SIZE))
         .service(get_all_problems)
         .service(get_problem)
+        .service(upload_data)
+        .service(update_problem)
 }
@@ -0,0 +1,21 @@
+//! `/runs` API routes.
+
+use crate::auth::Identity;
+use crate::error::*;
+use crate::judger::runs::{RunRequest, Runner};
+use actix_web::{post, web, Responder, Scope};
+
+/// Compile and run the source code on the input without submitting it, and return the output.
+#[post("")]
+async fn run(
+    request: web::Json<RunRequest>,
+    identity: Identity,
+    runner: web::Data<Runner>,
+) -> ApiResult<impl Responder> {
+    identity.require_login()?;
+    Ok(web::Json(runner.run(request.into_inner()).await?))
+}
+
+pub fn routes() -> Scope {
+    web::scope("/runs").service(run)
+}
@@ -1,8 +1,10 @@
 //! `/users` API routes.
 
+use crate::auth::{self, Identity};
 use crate::db::connection::ConnectionPool;
-use crate::db::users;
-use crate::error::ApiResult;
+use crate::db::enums::UserRole;
+use crate::db::users::{self, UserUpdate};
+use crate::error::*;
 use actix_web::{get, post, web, Responder, Scope};
 use serde::Deserialize;
 
@@ -10,19 +12,50 @@ use serde::Deserialize;
 struct PostData {
     id: Option<i32>,
     name: String,
+    /// Required to create a user when authentication is enabled.
+    password: Option<String>,
+    /// Only admins can set roles.
+    role: Option<UserRole>,
 }
 
 #[post("")]
 async fn post_users(
     data: web::Json<PostData>,
+    identity: Identity,
     pool: web::Data<ConnectionPool>,
 ) -> ApiResult<impl Responder> {
-    let user = match data.id {
-        Some(id) => {
-            web::block(move || users::set_name(id, data.into_inner().name, &pool)).await??
+    let PostData {
+        id,
+        name,
+        password,
+        role,
+    } = data.into_inner();
+    if let Some(id) = id {
+        identity.require_user(id)?;
+    }
+    if role.is_some() {
+        identity.require_admin()?;
+    }
+    if id.is_none() && password.is_none() && identity != Identity::Disabled {
+        return Err(ApiError::new(
+            ApiErrorType::InvalidArgument,
+            String::from("Password is required."),
+        ));
+    }
+    let user = web::block(move || {
+        let update = UserUpdate {
+            name,
+            password_hash: password
+                .map(|password| auth::hash_password(&password))
+                .transpose()?,
+            role,
+        };
+        match id {
+            Some(id) => users::update_user(id, &update, &pool),
+            None => users::add_user(update, &pool),
         }
-        None => web::block(move || users::add_user(data.into_inner().name, &pool)).await??,
-    };
+    })
+    .await??;
     Ok(web::Json(user))
 }
 
@@ -0,0 +1,455 @@
+//! Detect similar source code with winnowing fingerprints of normalized tokens.
+//!
+//! Sources are split into tokens with comments and whitespace removed, and identifiers, numbers
+//! and strings normalized, so that renaming variables or reformatting doesn't hide copied code.
+//! Hashes of every `K_GRAM` consecutive tokens are computed, and the minimum hash of every window
+//! of `WINDOW` consecutive hashes is selected as a fingerprint, as in "Winnowing: Local Algorithms
+//! for Document Fingerprinting" (Schleimer et al.).
+
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// The number of tokens hashed together. Matches shorter than this are ignored.
+const K_GRAM: usize = 10;
+
+/// The number of consecutive hashes to select a fingerprint from. Matches of at least
+/// `K_GRAM + WINDOW - 1` tokens are always detected.
+const WINDOW: usize = 5;
+
+const C_KEYWORDS: &[&str] = &[
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "include",
+    "inline",
+    "int",
+    "long",
+    "namespace",
+    "new",
+    "nullptr",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "true",
+    "typedef",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "while",
+];
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "false", "fn", "for", "if",
+    "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self",
+    "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while",
+];
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "break", "class", "continue", "def", "del", "elif", "else", "except",
+    "False", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "None",
+    "nonlocal", "not", "or", "pass", "raise", "return", "True", "try", "while", "with", "yield",
+];
+
+/// The syntax of comments and the keywords of a language.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Syntax {
+    /// C, C++ and other languages with C-like comments.
+    CLike,
+    Rust,
+    Python,
+}
+
+impl Syntax {
+    /// Guess the syntax from the file name of the source, e.g. `main.rs`.
+    pub fn from_file_name(file_name: &str) -> Self {
+        match file_name.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("rs") => Self::Rust,
+            Some("py") => Self::Python,
+            _ => Self::CLike,
+        }
+    }
+
+    fn keywords(&self) -> &'static [&'static str] {
+        match self {
+            Self::CLike => C_KEYWORDS,
+            Self::Rust => RUST_KEYWORDS,
+            Self::Python => PYTHON_KEYWORDS,
+        }
+    }
+
+    fn line_comment(&self) -> &'static str {
+        match self {
+            Self::CLike | Self::Rust => "//",
+            Self::Python => "#",
+        }
+    }
+
+    fn has_block_comments(&self) -> bool {
+        matches!(self, Self::CLike | Self::Rust)
+    }
+}
+
+/// A normalized token and the line (starting from 1) it's on.
+struct Token {
+    text: String,
+    line: usize,
+}
+
+/// Split the source into normalized tokens, without comments and whitespace.
+fn tokenize(source: &str, syntax: Syntax) -> Vec<Token> {
+    let keywords = syntax.keywords();
+    let chars = source.chars().collect::<Vec<_>>();
+    let starts_with = |index: usize, pattern: &str| {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| chars.get(index + offset) == Some(&c))
+    };
+
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let start = index;
+        let start_line = line;
+        let text = if c == '\n' {
+            line += 1;
+            index += 1;
+            continue;
+        } else if c.is_whitespace() {
+            index += 1;
+            continue;
+        } else if starts_with(index, syntax.line_comment()) {
+            while index < chars.len() && chars[index] != '\n' {
+                index += 1;
+            }
+            continue;
+        } else if syntax.has_block_comments() && starts_with(index, "/*") {
+            index += 2;
+            while index < chars.len() && !starts_with(index, "*/") {
+                if chars[index] == '\n' {
+                    line += 1;
+                }
+                index += 1;
+            }
+            index += 2;
+            continue;
+        } else if c.is_alphabetic() || c == '_' {
+            while index < chars.len() && (chars[index].is_alphanumeric() || chars[index] == '_') {
+                index += 1;
+            }
+            let word = chars[start..index].iter().collect::<String>();
+            if keywords.contains(&word.as_str()) {
+                word
+            } else {
+                String::from("$id")
+            }
+        } else if c.is_ascii_digit() {
+            while index < chars.len() && (chars[index].is_alphanumeric() || chars[index] == '.') {
+                index += 1;
+            }
+            String::from("$num")
+        } else if syntax == Syntax::Rust
+            && c == '\''
+            && chars.get(index + 2) != Some(&'\'')
+            && chars.get(index + 1) != Some(&'\\')
+        {
+            // A lifetime, e.g. `'a`, rather than a character literal.
+            index += 1;
+            String::from("'")
+        } else if c == '"' || c == '\'' {
+            index += 1;
+            while index < chars.len() && chars[index] != c && chars[index] != '\n' {
+                if chars[index] == '\\' {
+                    index += 1;
+                }
+                index += 1;
+            }
+            index += 1;
+            String::from("$str")
+        } else {
+            index += 1;
+            c.to_string()
+        };
+        tokens.push(Token {
+            text,
+            line: start_line,
+        });
+    }
+    tokens
+}
+
+/// The fingerprints of a source.
+pub struct Fingerprints {
+    /// The line of each token.
+    lines: Vec<usize>,
+    /// The selected hashes and the index of the first token of each.
+    fingerprints: Vec<(u64, usize)>,
+}
+
+impl Fingerprints {
+    pub fn new(source: &str, syntax: Syntax) -> Self {
+        let tokens = tokenize(source, syntax);
+        let hashes = tokens
+            .windows(K_GRAM)
+            .map(|gram| {
+                let mut hasher = DefaultHasher::new();
+                for token in gram {
+                    token.text.hash(&mut hasher);
+                }
+                hasher.finish()
+            })
+            .collect::<Vec<_>>();
+
+        let mut fingerprints: Vec<(u64, usize)> = Vec::new();
+        for start in 0..hashes.len().saturating_sub(WINDOW - 1).max(1) {
+            let end = (start + WINDOW).min(hashes.len());
+            // Select the rightmost minimum hash in the window.
+            let Some(position) = (start..end).rev().min_by_key(|&index| hashes[index]) else {
+                break;
+            };
+            if fingerprints.last().map(|(_, last)| *last) != Some(position) {
+                fingerprints.push((hashes[position], position));
+            }
+        }
+
+        Self {
+            lines: tokens.iter().map(|token| token.line).collect(),
+            fingerprints,
+        }
+    }
+
+    fn hash_set(&self) -> HashSet<u64> {
+        self.fingerprints.iter().map(|(hash, _)| *hash).collect()
+    }
+
+    /// The first and last lines of the tokens in the given range.
+    fn line_range(&self, start: usize, end: usize) -> [usize; 2] {
+        [self.lines[start], self.lines[end - 1]]
+    }
+}
+
+/// A region matched in two sources.
+#[derive(Serialize, Debug)]
+pub struct Region {
+    /// The first and last lines (starting from 1) of the region in each of the two sources.
+    pub lines: [[usize; 2]; 2],
+}
+
+/// The result of comparing two sources.
+pub struct Comparison {
+    /// The fraction of fingerprints shared, out of the source with fewer fingerprints.
+    pub similarity: f64,
+    pub regions: Vec<Region>,
+}
+
+/// Compare the fingerprints of two sources.
+pub fn compare(first: &Fingerprints, second: &Fingerprints) -> Comparison {
+    let (first_hashes, second_hashes) = (first.hash_set(), second.hash_set());
+    let common = first_hashes.intersection(&second_hashes).count();
+    let similarity = match first_hashes.len().min(second_hashes.len()) {
+        0 => 0.0,
+        count => common as f64 / count as f64,
+    };
+
+    let mut second_positions = HashMap::new();
+    for (hash, position) in &second.fingerprints {
+        second_positions.entry(*hash).or_insert(*position);
+    }
+
+    // Merge the matched k-grams into token ranges, while they are close in both sources.
+    let mut ranges: Vec<[[usize; 2]; 2]> = Vec::new();
+    for (hash, first_position) in &first.fingerprints {
+        let Some(&second_position) = second_positions.get(hash) else {
+            continue;
+        };
+        let first_range = [*first_position, first_position + K_GRAM];
+        let second_range = [second_position, second_position + K_GRAM];
+        match ranges.last_mut() {
+            Some([last_first, last_second])
+                if first_range[0] <= last_first[1] + WINDOW
+                    && second_range[0] >= last_second[0]
+                    && second_range[0] <= last_second[1] + WINDOW =>
+            {
+                last_first[1] = last_first[1].max(first_range[1]);
+                last_second[1] = last_second[1].max(second_range[1]);
+            }
+            _ => ranges.push([first_range, second_range]),
+        }
+    }
+
+    Comparison {
+        similarity,
+        // Short regions are mostly common idioms rather than copied code.
+        regions: ranges
+            .into_iter()
+            .filter(|[first_range, _]| first_range[1] - first_range[0] >= K_GRAM + WINDOW - 1)
+            .map(|[first_range, second_range]| Region {
+                lines: [
+                    first.line_range(first_range[0], first_range[1]),
+                    second.line_range(second_range[0], second_range[1]),
+                ],
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(source: &str, syntax: Syntax) -> Vec<String> {
+        tokenize(source, syntax)
+            .into_iter()
+            .map(|token| token.text)
+            .collect()
+    }
+
+    const SOURCE: &str = r#"#include <stdio.h>
+int main() {
+    int n, sum = 0;
+    scanf("%d", &n);
+    for (int i = 1; i <= n; i++) {
+        if (i % 3 == 0 || i % 5 == 0) sum += i;
+    }
+    printf("%d\n", sum);
+    return 0;
+}
+"#;
+
+    #[test]
+    fn tokenize_normalizes_tokens() {
+        assert_eq!(
+            texts(
+                "int x = 42; // x\n/* a\nb */ s = \"a\\\"b\";",
+                Syntax::CLike
+            ),
+            ["int", "$id", "=", "$num", ";", "$id", "=", "$str", ";"]
+        );
+        let lines = tokenize("a\n/* \n */ b\n\nc", Syntax::CLike)
+            .into_iter()
+            .map(|token| token.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [1, 3, 5]);
+    }
+
+    #[test]
+    fn tokenize_language_syntax() {
+        assert_eq!(
+            texts("fn f<'a>(c: &'a u8) -> char { 'x' }", Syntax::Rust),
+            [
+                "fn", "$id", "<", "'", "$id", ">", "(", "$id", ":", "&", "'", "$id", "$id", ")",
+                "-", ">", "$id", "{", "$str", "}"
+            ]
+        );
+        assert_eq!(
+            texts("x = '#' # comment\nprint(x)", Syntax::Python),
+            ["$id", "=", "$str", "$id", "(", "$id", ")"]
+        );
+    }
+
+    #[test]
+    fn winnowing_selects_a_fingerprint_in_every_window() {
+        let fingerprints = Fingerprints::new(SOURCE, Syntax::CLike);
+        let hash_count = fingerprints.lines.len() - K_GRAM + 1;
+        let positions = fingerprints
+            .fingerprints
+            .iter()
+            .map(|(_, position)| *position)
+            .collect::<Vec<_>>();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(positions[0] < WINDOW);
+        assert!(positions.windows(2).all(|pair| pair[1] - pair[0] <= WINDOW));
+        assert!(positions[positions.len() - 1] >= hash_count - WINDOW);
+    }
+
+    #[test]
+    fn short_source_has_no_fingerprints() {
+        let short = Fingerprints::new("int main() {}", Syntax::CLike);
+        assert!(short.fingerprints.is_empty());
+        let comparison = compare(&short, &Fingerprints::new(SOURCE, Syntax::CLike));
+        assert_eq!(comparison.similarity, 0.0);
+        assert!(comparison.regions.is_empty());
+    }
+
+    #[test]
+    fn renamed_and_reformatted_copy_is_identical() {
+        let copy = SOURCE
+            .replace("sum", "total")
+            .replace(" n", " count")
+            .replace("&n", "&count")
+            .replace("    ", "\t")
+            .replace("{\n", "{ // copied\n");
+        let comparison = compare(
+            &Fingerprints::new(SOURCE, Syntax::CLike),
+            &Fingerprints::new(&copy, Syntax::CLike),
+        );
+        assert_eq!(comparison.similarity, 1.0);
+        assert_eq!(comparison.regions.len(), 1);
+        // The region covers the whole source, except for the tokens after the last fingerprint.
+        let [first_lines, second_lines] = comparison.regions[0].lines;
+        assert_eq!(first_lines, second_lines);
+        assert_eq!(first_lines[0], 1);
+        assert!(first_lines[1] >= 8);
+    }
+
+    #[test]
+    fn copied_region_in_different_code() {
+        let other = r#"#include <iostream>
+using namespace std;
+int main() {
+    string s;
+    cin >> s;
+    cout << s.size() << endl;
+}
+"#;
+        let first = Fingerprints::new(SOURCE, Syntax::CLike);
+        let unrelated = compare(&first, &Fingerprints::new(other, Syntax::CLike));
+        assert!(unrelated.similarity < 0.5);
+        assert!(unrelated.regions.is_empty());
+
+        // The loop of `SOURCE` inserted into `other` after line 5.
+        let loop_lines = SOURCE.lines().skip(4).take(3).collect::<Vec<_>>();
+        let mut lines = other.lines().collect::<Vec<_>>();
+        lines.splice(5..5, loop_lines);
+        let combined = compare(&first, &Fingerprints::new(&lines.join("\n"), Syntax::CLike));
+        assert!(combined.similarity > unrelated.similarity);
+        assert_eq!(combined.regions.len(), 1);
+        let [first_lines, second_lines] = combined.regions[0].lines;
+        assert!(first_lines[0] >= 4 && first_lines[1] <= 8);
+        assert!(second_lines[0] >= 5 && second_lines[1] <= 9);
+    }
+}
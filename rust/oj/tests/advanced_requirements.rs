@@ -126,3 +126,53 @@ fn test_adv_07_10_pts_dynamic_ranking() {
         );
     }
 }
+
+#[test]
+fn test_adv_08_icpc_contest() {
+    // check ranking by solved problems, rejected attempts and the first to solve in ICPC mode
+    let results = TestCase::read("adv_08_icpc_contest").run();
+    let ranklist = results.last().unwrap().as_array().unwrap();
+
+    // the penalty is the solving time plus 20 minutes for each rejected attempt
+    for item in ranklist {
+        let penalty = item["problems"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|status| status["solved"].as_bool().unwrap())
+            .map(|status| {
+                status["time"].as_i64().unwrap() + 20 * status["rejected"].as_i64().unwrap()
+            })
+            .sum::<i64>();
+        assert_eq!(
+            item["penalty"].as_i64().unwrap(),
+            penalty,
+            "case adv_08_icpc_contest incorrect: wrong penalty"
+        );
+    }
+}
+
+#[test]
+fn test_adv_09_ranklist_freeze() {
+    // check that submissions since the freeze time are hidden in the frozen ranklist until they
+    // are revealed
+    TestCase::read("adv_09_ranklist_freeze").run();
+}
+
+#[test]
+fn test_adv_10_subtasks() {
+    // check the scoring policies and dependencies of subtasks
+    TestCase::read("adv_10_subtasks").run();
+}
+
+#[test]
+fn test_adv_11_authentication() {
+    // check login, admin-only APIs and that users can only see their own jobs
+    TestCase::read("adv_11_authentication").run();
+}
+
+#[test]
+fn test_adv_12_frozen_jobs() {
+    // check that the results hidden by the freeze can't be found by filtering or sorting jobs
+    TestCase::read("adv_12_frozen_jobs").run();
+}
@@ -2,8 +2,8 @@ use assert_json_diff::{assert_json_matches_no_panic, CompareMode, Config};
 use lazy_static::lazy_static;
 use reqwest::blocking::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
-use serde_json;
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::env::consts::EXE_EXTENSION;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
@@ -42,7 +42,10 @@ fn build_and_find_path(name: &str) -> PathBuf {
     static CARGO_BUILD_ONCE: Once = Once::new();
     CARGO_BUILD_ONCE.call_once(|| {
         let mut build_command = Command::new("cargo");
-        build_command.args(&["build", "--quiet"]);
+        build_command.args(["build", "--quiet"]);
+        if cfg!(feature = "postgres") {
+            build_command.args(["--no-default-features", "--features", "postgres"]);
+        }
         if !cfg!(debug_assertions) {
             build_command.arg("--release");
         }
@@ -68,17 +71,27 @@ lazy_static! {
     static ref CLIENT: Client = Client::new();
 }
 
+/// The password of the root user, set by `OJ_ROOT_PASSWORD` when starting the server.
+const ROOT_PASSWORD: &str = "root_password";
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct TestRequest {
     path: String,
     method: String,
     content: Value,
+    /// Send the request with the token of the last login of this user, when authentication is
+    /// enabled.
+    #[serde(default)]
+    user: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct TestResponse {
     status: u16,
     content: Value,
+    /// The expected values of some response headers.
+    #[serde(default)]
+    headers: HashMap<String, String>,
 }
 
 fn _default_true() -> bool {
@@ -117,6 +130,7 @@ pub struct TestCase {
     stdout_file: PathBuf,
     stderr_file: PathBuf,
     http_file: PathBuf,
+    tokens: HashMap<String, String>, // the token of each logged in user
 }
 
 impl TestCase {
@@ -149,6 +163,7 @@ impl TestCase {
             stdout_file,
             stderr_file,
             http_file,
+            tokens: HashMap::new(),
         }
     }
 
@@ -201,12 +216,30 @@ impl TestCase {
         }
     }
 
+    /// Ask the server to exit, as root if authentication is enabled.
+    fn exit_server(&self) {
+        let exit_url = format!("{}/internal/exit", self.prefix);
+        let status = match CLIENT.post(&exit_url).send() {
+            Ok(resp) => resp.status(),
+            Err(_) => return,
+        };
+        if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+            let token = CLIENT
+                .post(format!("{}/auth/login", self.prefix))
+                .json(&json!({ "name": "root", "password": ROOT_PASSWORD }))
+                .send()
+                .and_then(|resp| resp.json::<Value>())
+                .ok()
+                .and_then(|body| body["token"].as_str().map(String::from));
+            if let Some(token) = token {
+                CLIENT.post(&exit_url).bearer_auth(token).send().ok();
+            }
+        }
+    }
+
     fn start_server(&mut self, restart: bool) {
         // ensure no server is running
-        CLIENT
-            .post(&format!("{}/internal/exit", self.prefix))
-            .send()
-            .ok();
+        self.exit_server();
         // sleep 1 second for server shutdown
         std::thread::sleep(Duration::from_secs(1));
 
@@ -227,17 +260,17 @@ impl TestCase {
 
         let command = Command::new(EXE_PATH.as_os_str())
             .args(&self.arguments)
+            .env("OJ_ROOT_PASSWORD", ROOT_PASSWORD)
             .stdin(Stdio::piped())
             .stdout(Stdio::from(stdout))
             .stderr(Stdio::from(stderr))
             .spawn()
-            .expect(
-                format!(
+            .unwrap_or_else(|_| {
+                panic!(
                     "case {} incorrect: failed to execute server process",
                     self.name
                 )
-                .as_str(),
-            );
+            });
         self.running_process = Some(command);
         // sleep 1 second for server startup
         std::thread::sleep(Duration::from_secs(1));
@@ -262,9 +295,9 @@ impl TestCase {
 
     fn kill_server(&mut self) {
         if let Some(mut child) = self.running_process.take() {
-            child.kill().expect(
-                format!("case {} incorrect: cannot kill server process", self.name).as_str(),
-            );
+            child.kill().unwrap_or_else(|_| {
+                panic!("case {} incorrect: cannot kill server process", self.name)
+            });
         }
     }
 
@@ -285,12 +318,18 @@ impl TestCase {
 
         let url = format!("{}/{}", &self.prefix, &c.request.path);
         let method =
-            reqwest::Method::from_bytes(&c.request.method.to_uppercase().as_bytes()).unwrap();
+            reqwest::Method::from_bytes(c.request.method.to_uppercase().as_bytes()).unwrap();
 
         let check_status_and_get_body = |url: &str, method: reqwest::Method| -> Value {
             let mut request = CLIENT
                 .request(method.clone(), url)
                 .timeout(Duration::from_millis(c.timeout));
+            if let Some(user) = &c.request.user {
+                let token = self.tokens.get(user).unwrap_or_else(|| {
+                    panic!("case {} incorrect: {} is not logged in", self.name, user)
+                });
+                request = request.bearer_auth(token);
+            }
             if let reqwest::Method::GET = method {
                 // no json body
             } else {
@@ -299,20 +338,36 @@ impl TestCase {
 
             let (resp, mut http_file) = self
                 .log_and_send(request)
-                .expect(format!("case {} incorrect: HTTP request failed", self.name).as_str());
+                .unwrap_or_else(|_| panic!("case {} incorrect: HTTP request failed", self.name));
             let code = resp.status().as_u16();
             assert_eq!(
                 code, c.response.status,
                 "case {} incorrect: wrong status code",
                 self.name
             );
-            let json: Value = resp.json().expect(
-                format!(
-                    "case {} incorrect: cannot decode response body as JSON, status code is {}",
-                    self.name, code
-                )
-                .as_str(),
-            );
+            for (name, value) in &c.response.headers {
+                assert_eq!(
+                    resp.headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok()),
+                    Some(value.as_str()),
+                    "case {} incorrect: wrong header {}",
+                    self.name,
+                    name
+                );
+            }
+            // An empty body, e.g. of "204 No Content", is compared as `null`.
+            let body = resp.bytes().unwrap_or_default();
+            let json: Value = if body.is_empty() {
+                Value::Null
+            } else {
+                serde_json::from_slice(&body).unwrap_or_else(|_| {
+                    panic!(
+                        "case {} incorrect: cannot decode response body as JSON, status code is {}",
+                        self.name, code
+                    )
+                })
+            };
 
             serde_json::to_writer(&http_file, &json).ok();
             writeln!(http_file).ok();
@@ -329,7 +384,7 @@ impl TestCase {
             if let Value::Number(id) = &body["id"] as &Value {
                 job_id = id
                     .as_u64()
-                    .expect(format!("case {} incorrect: job id is not valid", self.name).as_str());
+                    .unwrap_or_else(|| panic!("case {} incorrect: job id is not valid", self.name));
             } else {
                 panic!(
                     "case {} incorrect: cannot get job id after submission",
@@ -341,7 +396,7 @@ impl TestCase {
             let poll_url = format!("{}/jobs/{}", &self.prefix, job_id);
             for _ in 0..c.poll_count {
                 std::thread::sleep(Duration::from_secs(1));
-                body = check_status_and_get_body(&poll_url.as_str(), reqwest::Method::GET);
+                body = check_status_and_get_body(poll_url.as_str(), reqwest::Method::GET);
                 if job_finished(&body) {
                     break;
                 }
@@ -354,6 +409,15 @@ impl TestCase {
             }
         }
 
+        // remember the token for the requests of this user
+        if c.request.path == "auth/login" && c.response.status == 200 {
+            if let (Some(name), Some(token)) =
+                (c.request.content["name"].as_str(), body["token"].as_str())
+            {
+                self.tokens.insert(name.to_string(), token.to_string());
+            }
+        }
+
         // check final result
         if let Err(error) = assert_json_matches_no_panic(
             &body,